@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::diagnostic::Span;
+use crate::parser::visit::{walk_primary, walk_statements, Visitor};
+use crate::parser::*;
+use crate::semantic::{ScopeId, SemanticModel, SymbolId};
+
+/// Where calls are made from: the main program body or a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Caller {
+    Main,
+    /// The function with this index in `Program::functions`.
+    Function(usize),
+}
+
+/// A call of the function with index `callee`, at the callee's name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Call {
+    pub callee: usize,
+    pub span: Span,
+}
+
+/// Which functions call which, from the calls written in each body. Functions are numbered
+/// by their index in `Program::functions`.
+#[derive(Debug)]
+pub struct CallGraph {
+    names: Vec<String>,
+    // calls made from each function's body, then from the main body
+    calls: Vec<Vec<Call>>,
+}
+
+impl CallGraph {
+    /// Calls that don't resolve to a function, such as calls to undeclared names, are left out.
+    pub fn build(program: &Program, model: &SemanticModel) -> CallGraph {
+        let functions = program.functions();
+        let indices: HashMap<SymbolId, usize> =
+            (0..functions.len()).filter_map(|i| Some((model.function_of(ScopeId::Function(i))?, i))).collect();
+        let bodies = functions.iter().map(|f| &f.body).chain([&program.body]);
+        let calls = bodies
+            .map(|body| {
+                let mut calls = Calls { model, indices: &indices, calls: Vec::new() };
+                walk_statements(&mut calls, &body.statements);
+                calls.calls
+            })
+            .collect();
+        CallGraph { names: functions.iter().map(|f| f.name.name.clone()).collect(), calls }
+    }
+
+    /// The number of functions.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn name(&self, function: usize) -> &str {
+        &self.names[function]
+    }
+
+    /// Every call `caller` makes, in source order.
+    pub fn calls(&self, caller: Caller) -> &[Call] {
+        match caller {
+            Caller::Main => &self.calls[self.names.len()],
+            Caller::Function(index) => &self.calls[index],
+        }
+    }
+
+    /// The functions `caller` calls, each once, in index order.
+    pub fn callees(&self, caller: Caller) -> Vec<usize> {
+        let mut callees: Vec<usize> = self.calls(caller).iter().map(|c| c.callee).collect();
+        callees.sort();
+        callees.dedup();
+        callees
+    }
+
+    /// The bodies that call `function`, each once, the main body last.
+    pub fn callers(&self, function: usize) -> Vec<Caller> {
+        let callers = (0..self.names.len()).map(Caller::Function).chain([Caller::Main]);
+        callers.filter(|c| self.calls(*c).iter().any(|call| call.callee == function)).collect()
+    }
+
+    /// Groups of functions that can call themselves: a function that calls itself directly
+    /// is a group of one, functions that call each other in a cycle are a group together.
+    /// Each group is in index order and groups are ordered by their first function.
+    pub fn recursion(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan::new(self);
+        for function in 0..self.len() {
+            if tarjan.indices[function].is_none() {
+                tarjan.visit(function);
+            }
+        }
+        let mut groups: Vec<Vec<usize>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.callees(Caller::Function(c[0])).contains(&c[0]))
+            .map(|mut c| {
+                c.sort();
+                c
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// Functions that no chain of calls from the main body reaches, in index order.
+    pub fn unreachable(&self) -> Vec<usize> {
+        let mut reached = vec![false; self.len()];
+        let mut pending = self.callees(Caller::Main);
+        while let Some(function) = pending.pop() {
+            if !std::mem::replace(&mut reached[function], true) {
+                pending.extend(self.callees(Caller::Function(function)));
+            }
+        }
+        (0..self.len()).filter(|f| !reached[*f]).collect()
+    }
+
+    /// The graph in Graphviz DOT, named after the program. An edge is drawn once however
+    /// many calls it stands for; recursive functions are bold and unreachable ones dashed.
+    pub fn to_dot(&self, program: &str) -> String {
+        let recursive: Vec<usize> = self.recursion().concat();
+        let unreachable = self.unreachable();
+        let mut dot = format!("digraph \"{}\" {{\n", program);
+        dot.push_str("    \"<main>\" [shape=box];\n");
+        for (index, name) in self.names.iter().enumerate() {
+            let mut styles = Vec::new();
+            if recursive.contains(&index) {
+                styles.push("bold");
+            }
+            if unreachable.contains(&index) {
+                styles.push("dashed");
+            }
+            match styles.is_empty() {
+                true => writeln!(dot, "    \"{}\";", name).unwrap(),
+                false => writeln!(dot, "    \"{}\" [style=\"{}\"];", name, styles.join(",")).unwrap(),
+            }
+        }
+        let callers = [Caller::Main].into_iter().chain((0..self.len()).map(Caller::Function));
+        for caller in callers {
+            let from = match caller {
+                Caller::Main => "<main>",
+                Caller::Function(index) => &self.names[index],
+            };
+            for callee in self.callees(caller) {
+                writeln!(dot, "    \"{}\" -> \"{}\";", from, self.names[callee]).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as JSON: the functions with the functions they call, and the results of
+    /// `recursion` and `unreachable` by name.
+    pub fn to_json(&self) -> String {
+        let name = |f: &usize| self.names[*f].clone();
+        let functions = (0..self.len()).map(|index| JsonFunction {
+            name: self.names[index].clone(),
+            calls: self.callees(Caller::Function(index)).iter().map(name).collect(),
+        });
+        let graph = JsonGraph {
+            main: self.callees(Caller::Main).iter().map(name).collect(),
+            functions: functions.collect(),
+            recursive: self.recursion().iter().map(|group| group.iter().map(name).collect()).collect(),
+            unreachable: self.unreachable().iter().map(name).collect(),
+        };
+        serde_json::to_string_pretty(&graph).unwrap()
+    }
+}
+
+#[derive(Serialize)]
+struct JsonGraph {
+    main: Vec<String>,
+    functions: Vec<JsonFunction>,
+    recursive: Vec<Vec<String>>,
+    unreachable: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JsonFunction {
+    name: String,
+    calls: Vec<String>,
+}
+
+struct Calls<'a> {
+    model: &'a SemanticModel,
+    indices: &'a HashMap<SymbolId, usize>,
+    calls: Vec<Call>,
+}
+
+impl Visitor for Calls<'_> {
+    fn visit_primary(&mut self, primary: &Primary) {
+        if let Primary::Call { name, .. } = primary {
+            let callee = self.model.symbol_at(name.span).and_then(|s| self.indices.get(&s));
+            if let Some(callee) = callee {
+                self.calls.push(Call { callee: *callee, span: name.span });
+            }
+        }
+        walk_primary(self, primary)
+    }
+}
+
+// Tarjan's algorithm for the strongly connected components of the graph.
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    next: usize,
+    indices: Vec<Option<usize>>,
+    lowlinks: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn new(graph: &'a CallGraph) -> Self {
+        let len = graph.len();
+        Tarjan {
+            graph,
+            next: 0,
+            indices: vec![None; len],
+            lowlinks: vec![0; len],
+            on_stack: vec![false; len],
+            stack: Vec::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, function: usize) {
+        self.indices[function] = Some(self.next);
+        self.lowlinks[function] = self.next;
+        self.next += 1;
+        self.stack.push(function);
+        self.on_stack[function] = true;
+
+        for callee in self.graph.callees(Caller::Function(function)) {
+            match self.indices[callee] {
+                None => {
+                    self.visit(callee);
+                    self.lowlinks[function] = self.lowlinks[function].min(self.lowlinks[callee]);
+                }
+                Some(index) if self.on_stack[callee] => {
+                    self.lowlinks[function] = self.lowlinks[function].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.lowlinks[function]) == self.indices[function] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == function {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
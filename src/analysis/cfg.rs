@@ -0,0 +1,353 @@
+use std::fmt::Write;
+
+use crate::parser::print;
+use crate::parser::*;
+
+pub type BlockId = usize;
+
+/// What a block does, in order. Conditions and `case` selectors are evaluated last, and
+/// decide which edge is taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Element<'a> {
+    /// An assignment, `output`, `read` or `return`.
+    Statement(&'a Statement),
+    /// The initialization or update of a `for`.
+    Assignment(&'a Assignment),
+    /// The condition of an `if`, `while`, `repeat` or `for`.
+    Condition(&'a Expression),
+    /// The expression a `case` selects on.
+    Selector(&'a Expression),
+}
+
+impl Element<'_> {
+    pub fn text(&self) -> String {
+        match self {
+            Element::Statement(statement) => print::statement(statement),
+            Element::Assignment(assignment) => print::assignment(assignment),
+            Element::Condition(expression) | Element::Selector(expression) => print::expression(expression),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Jump,
+    True,
+    False,
+    /// To the statement of the `case` clause with this index.
+    Case(usize),
+    /// From a `case` to its `otherwise`, or past it when it has none.
+    Otherwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: BlockId,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock<'a> {
+    pub elements: Vec<Element<'a>>,
+    pub successors: Vec<Edge>,
+}
+
+/// The basic blocks of a function's body or the main program's, and the edges control can
+/// take between them. `exit` jumps past the innermost `loop`, and `return` to the exit
+/// block, which is empty and last. Code that can't be reached from the entry is left out.
+#[derive(Debug)]
+pub struct ControlFlowGraph<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+    entry: BlockId,
+}
+
+impl<'a> ControlFlowGraph<'a> {
+    pub fn build(body: &'a Body) -> Self {
+        let blocks = vec![BasicBlock::default(), BasicBlock::default()];
+        let mut builder = Builder { blocks, current: 0, loops: Vec::new() };
+        builder.statements(&body.statements);
+        builder.edge(EXIT, EdgeKind::Jump);
+        builder.finish()
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock<'a>] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock<'a> {
+        &self.blocks[id]
+    }
+
+    pub fn entry(&self) -> BlockId {
+        self.entry
+    }
+
+    pub fn exit(&self) -> BlockId {
+        self.blocks.len() - 1
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.blocks.iter().map(|b| b.successors.len()).sum()
+    }
+
+    /// The blocks with an edge to `id`, each once, in order.
+    pub fn predecessors(&self, id: BlockId) -> Vec<BlockId> {
+        let blocks = self.blocks.iter().enumerate();
+        blocks.filter(|(_, b)| b.successors.iter().any(|e| e.target == id)).map(|(from, _)| from).collect()
+    }
+
+    /// The graph in Graphviz DOT, each block labelled with its code.
+    pub fn to_dot(&self, name: &str) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n    node [shape=box, fontname=monospace];\n", name);
+        for (id, block) in self.blocks.iter().enumerate() {
+            let label = if id == self.entry && block.elements.is_empty() {
+                "entry".to_string()
+            } else if id == self.exit() {
+                "exit".to_string()
+            } else {
+                // left-justified lines
+                let lines = block.elements.iter().map(|e| e.text().replace('"', "\\\"") + "\\l");
+                lines.collect::<Vec<_>>().concat()
+            };
+            writeln!(dot, "    b{} [label=\"{}\"];", id, label).unwrap();
+        }
+        for (id, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                let label = match edge.kind {
+                    EdgeKind::Jump => String::new(),
+                    EdgeKind::True => " [label=\"true\"]".to_string(),
+                    EdgeKind::False => " [label=\"false\"]".to_string(),
+                    EdgeKind::Case(clause) => format!(" [label=\"case {}\"]", clause + 1),
+                    EdgeKind::Otherwise => " [label=\"otherwise\"]".to_string(),
+                };
+                writeln!(dot, "    b{} -> b{}{};", id, edge.target, label).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// the exit block while building
+const EXIT: BlockId = 1;
+
+struct Builder<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+    current: BlockId,
+    // the block after each enclosing 'loop', where its 'exit's go
+    loops: Vec<BlockId>,
+}
+
+impl<'a> Builder<'a> {
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, target: BlockId, kind: EdgeKind) {
+        self.edge_from(self.current, target, kind);
+    }
+
+    fn edge_from(&mut self, from: BlockId, target: BlockId, kind: EdgeKind) {
+        self.blocks[from].successors.push(Edge { target, kind });
+    }
+
+    fn element(&mut self, element: Element<'a>) {
+        self.blocks[self.current].elements.push(element);
+    }
+
+    /// Continues in a new block that `from` jumps to.
+    fn jump_to_new(&mut self) -> BlockId {
+        let block = self.block();
+        self.edge(block, EdgeKind::Jump);
+        self.current = block;
+        block
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Assign { .. } | Statement::Output { .. } | Statement::Read { .. } => {
+                self.element(Element::Statement(statement))
+            }
+            Statement::If { cond, then, else_stmt } => {
+                self.element(Element::Condition(cond));
+                let condition = self.current;
+                let after = self.block();
+                self.current = self.block();
+                self.edge_from(condition, self.current, EdgeKind::True);
+                self.statement(then);
+                self.edge(after, EdgeKind::Jump);
+                match else_stmt {
+                    Some(else_stmt) => {
+                        self.current = self.block();
+                        self.edge_from(condition, self.current, EdgeKind::False);
+                        self.statement(else_stmt);
+                        self.edge(after, EdgeKind::Jump);
+                    }
+                    None => self.edge_from(condition, after, EdgeKind::False),
+                }
+                self.current = after;
+            }
+            Statement::While { cond, stmt } => {
+                let head = self.jump_to_new();
+                self.element(Element::Condition(cond));
+                let body = self.block();
+                let after = self.block();
+                self.edge(body, EdgeKind::True);
+                self.edge(after, EdgeKind::False);
+                self.current = body;
+                self.statement(stmt);
+                self.edge(head, EdgeKind::Jump);
+                self.current = after;
+            }
+            Statement::Repeat { stmts, cond } => {
+                let body = self.jump_to_new();
+                self.statements(stmts);
+                self.element(Element::Condition(cond));
+                self.edge(body, EdgeKind::False);
+                let after = self.block();
+                self.edge(after, EdgeKind::True);
+                self.current = after;
+            }
+            Statement::For { init, cond, update, stmt } => {
+                if let ForStat::Assignment(init) = init {
+                    self.element(Element::Assignment(init));
+                }
+                let head = self.jump_to_new();
+                let after = self.block();
+                let body = self.block();
+                match cond {
+                    ForExp::Expression(cond) => {
+                        self.element(Element::Condition(cond));
+                        self.edge(body, EdgeKind::True);
+                        self.edge(after, EdgeKind::False);
+                    }
+                    ForExp::True => self.edge(body, EdgeKind::Jump),
+                }
+                self.current = body;
+                self.statement(stmt);
+                if let ForStat::Assignment(update) = update {
+                    self.element(Element::Assignment(update));
+                }
+                self.edge(head, EdgeKind::Jump);
+                self.current = after;
+            }
+            Statement::Loop { stmts } => {
+                let body = self.jump_to_new();
+                let after = self.block();
+                self.loops.push(after);
+                self.statements(stmts);
+                self.loops.pop();
+                self.edge(body, EdgeKind::Jump);
+                self.current = after;
+            }
+            Statement::Case { expr, cases, otherwise } => {
+                self.element(Element::Selector(expr));
+                let selector = self.current;
+                let after = self.block();
+                for (index, clause) in cases.iter().enumerate() {
+                    self.current = self.block();
+                    self.edge_from(selector, self.current, EdgeKind::Case(index));
+                    self.statement(&clause.statement);
+                    self.edge(after, EdgeKind::Jump);
+                }
+                match otherwise {
+                    Some(otherwise) => {
+                        self.current = self.block();
+                        self.edge_from(selector, self.current, EdgeKind::Otherwise);
+                        self.statement(&otherwise.stmt);
+                        self.edge(after, EdgeKind::Jump);
+                    }
+                    None => self.edge_from(selector, after, EdgeKind::Otherwise),
+                }
+                self.current = after;
+            }
+            Statement::Exit { .. } => {
+                // outside a loop, which is an error, 'exit' leaves the body
+                let target = self.loops.last().copied().unwrap_or(EXIT);
+                self.edge(target, EdgeKind::Jump);
+                self.current = self.block();
+            }
+            Statement::Return { .. } => {
+                self.element(Element::Statement(statement));
+                self.edge(EXIT, EdgeKind::Jump);
+                self.current = self.block();
+            }
+            Statement::Body { body } => self.statements(&body.statements),
+            Statement::Null => {}
+        }
+    }
+
+    /// Merges blocks that follow each other unconditionally, skips empty blocks, drops
+    /// unreachable ones and numbers the rest in order, the exit last.
+    fn finish(mut self) -> ControlFlowGraph<'a> {
+        let mut entry = 0;
+        let mut alive = vec![true; self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in 0..self.blocks.len() {
+                if !alive[id] || id == EXIT {
+                    continue;
+                }
+                let [Edge { target, kind: EdgeKind::Jump }] = self.blocks[id].successors[..] else {
+                    continue;
+                };
+                if target == id {
+                    continue;
+                }
+                let predecessors = (0..self.blocks.len())
+                    .filter(|p| alive[*p] && self.blocks[*p].successors.iter().any(|e| e.target == target))
+                    .count();
+                if self.blocks[id].elements.is_empty() {
+                    // an empty block only passes control on
+                    for block in self.blocks.iter_mut() {
+                        block.successors.iter_mut().filter(|e| e.target == id).for_each(|e| e.target = target);
+                    }
+                    if entry == id {
+                        entry = target;
+                    }
+                    self.blocks[id].successors.clear();
+                    alive[id] = false;
+                    changed = true;
+                } else if predecessors == 1 && target != EXIT && target != entry {
+                    let next = std::mem::take(&mut self.blocks[target]);
+                    self.blocks[id].elements.extend(next.elements);
+                    self.blocks[id].successors = next.successors;
+                    alive[target] = false;
+                    changed = true;
+                }
+            }
+        }
+
+        // keep what the entry reaches, in the order blocks were made, with the exit last
+        let mut reached = vec![false; self.blocks.len()];
+        let mut pending = vec![entry];
+        while let Some(id) = pending.pop() {
+            if !std::mem::replace(&mut reached[id], true) {
+                pending.extend(self.blocks[id].successors.iter().map(|e| e.target));
+            }
+        }
+        let mut order: Vec<BlockId> = (0..self.blocks.len()).filter(|id| reached[*id] && *id != EXIT).collect();
+        order.push(EXIT);
+        let mut numbers = vec![0; self.blocks.len()];
+        for (number, id) in order.iter().enumerate() {
+            numbers[*id] = number;
+        }
+        let blocks = order
+            .iter()
+            .map(|id| {
+                let mut block = std::mem::take(&mut self.blocks[*id]);
+                block.successors.iter_mut().for_each(|e| e.target = numbers[e.target]);
+                block
+            })
+            .collect();
+        ControlFlowGraph { blocks, entry: numbers[entry] }
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+
+use super::cfg::{BlockId, ControlFlowGraph, Element};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Facts flow from the entry along the edges, as for reaching definitions.
+    Forward,
+    /// Facts flow from the exit against the edges, as for live variables.
+    Backward,
+}
+
+/// A dataflow problem over a control-flow graph. Its facts form a lattice: `bottom` is what
+/// a block starts from before anything reaches it, and `join` merges the facts flowing in
+/// from several blocks, moving only up. Transfer functions must be monotone for `solve` to
+/// finish.
+pub trait Analysis<'a> {
+    type Fact: Clone + PartialEq;
+
+    const DIRECTION: Direction;
+
+    /// The fact at the entry of the body, or at its exit for a backward analysis.
+    fn boundary(&self) -> Self::Fact;
+
+    fn bottom(&self) -> Self::Fact;
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact);
+
+    /// Moves `fact` across `element`: from before it to after it, or backward from after
+    /// it to before it.
+    fn transfer(&self, element: &Element<'a>, fact: &mut Self::Fact);
+}
+
+/// The facts at every point of a graph: before each element of each block, and at the end
+/// of the block.
+#[derive(Debug)]
+pub struct Solution<F> {
+    points: Vec<Vec<F>>,
+}
+
+impl<F> Solution<F> {
+    /// The fact just before element `index` of `block`; `index` may be the number of
+    /// elements, for the end of the block.
+    pub fn at(&self, block: BlockId, index: usize) -> &F {
+        &self.points[block][index]
+    }
+
+    pub fn block_start(&self, block: BlockId) -> &F {
+        &self.points[block][0]
+    }
+
+    pub fn block_end(&self, block: BlockId) -> &F {
+        self.points[block].last().unwrap()
+    }
+}
+
+/// Solves `analysis` with a worklist, revisiting a block whenever the fact flowing into it
+/// changes.
+pub fn solve<'a, A: Analysis<'a>>(graph: &ControlFlowGraph<'a>, analysis: &A) -> Solution<A::Fact> {
+    let blocks = graph.blocks();
+    let forward = A::DIRECTION == Direction::Forward;
+    let (start, inputs): (BlockId, Vec<Vec<BlockId>>) = if forward {
+        (graph.entry(), (0..blocks.len()).map(|id| graph.predecessors(id)).collect())
+    } else {
+        (graph.exit(), blocks.iter().map(|b| b.successors.iter().map(|e| e.target).collect()).collect())
+    };
+    // where a block's fact flows once it has been moved across the block
+    let mut outputs = vec![Vec::new(); blocks.len()];
+    for (id, input) in inputs.iter().enumerate() {
+        input.iter().for_each(|from| outputs[*from].push(id));
+    }
+
+    // the fact flowing into each block, and out of it
+    let mut incoming = vec![analysis.bottom(); blocks.len()];
+    let mut outgoing = vec![analysis.bottom(); blocks.len()];
+    let mut pending: VecDeque<BlockId> = (0..blocks.len()).collect();
+    if !forward {
+        pending = pending.into_iter().rev().collect();
+    }
+    let mut queued = vec![true; blocks.len()];
+    while let Some(id) = pending.pop_front() {
+        queued[id] = false;
+        let mut fact = if id == start { analysis.boundary() } else { analysis.bottom() };
+        for input in &inputs[id] {
+            analysis.join(&mut fact, &outgoing[*input]);
+        }
+        incoming[id] = fact.clone();
+        let elements = &blocks[id].elements;
+        if forward {
+            elements.iter().for_each(|element| analysis.transfer(element, &mut fact));
+        } else {
+            elements.iter().rev().for_each(|element| analysis.transfer(element, &mut fact));
+        }
+        if fact != outgoing[id] {
+            outgoing[id] = fact;
+            for output in &outputs[id] {
+                if !std::mem::replace(&mut queued[*output], true) {
+                    pending.push_back(*output);
+                }
+            }
+        }
+    }
+
+    let points = blocks
+        .iter()
+        .zip(incoming)
+        .map(|(block, mut fact)| {
+            let mut points = vec![fact.clone()];
+            if forward {
+                for element in &block.elements {
+                    analysis.transfer(element, &mut fact);
+                    points.push(fact.clone());
+                }
+            } else {
+                for element in block.elements.iter().rev() {
+                    analysis.transfer(element, &mut fact);
+                    points.push(fact.clone());
+                }
+                points.reverse();
+            }
+            points
+        })
+        .collect();
+    Solution { points }
+}
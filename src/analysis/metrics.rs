@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::diagnostic::Diagnostic;
+use crate::parser::visit::{walk_factor, walk_statement, walk_statements, walk_term, Visitor};
+use crate::parser::*;
+use crate::semantic::{ScopeId, SemanticModel, SymbolKind};
+
+use super::callgraph::{CallGraph, Caller};
+
+/// Size and complexity figures for the body of a function or of the main program.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Metrics {
+    pub name: String,
+    /// Statements of every kind but empty ones and `begin ... end`, which only group others.
+    pub statements: usize,
+    /// How deeply `if`s, loops and `case`s nest: 1 for one that isn't inside another. An `if`
+    /// after `else` is at the level of the `if` it belongs to, as in a chain of `else if`s.
+    pub nesting: usize,
+    /// One more than the number of decisions: each `if`, loop and `case` clause, and each
+    /// `and` and `or`.
+    pub complexity: usize,
+    pub parameters: usize,
+    pub locals: usize,
+    /// The number of other bodies that call the function; the main program's is 0.
+    pub fan_in: usize,
+    /// The number of functions the body calls, itself included.
+    pub fan_out: usize,
+}
+
+/// Measures every function, in `Program::functions` order, then the main program, which is
+/// named `<main>`. The main program's locals are the global variables.
+pub fn measure(program: &Program, model: &SemanticModel) -> Vec<Metrics> {
+    let graph = CallGraph::build(program, model);
+    let bodies = program.functions().iter().enumerate().map(|(i, f)| (f.name(), &f.body, Caller::Function(i)));
+    bodies
+        .chain([("<main>", &program.body, Caller::Main)])
+        .map(|(name, body, caller)| {
+            let mut counter = Counter::default();
+            walk_statements(&mut counter, &body.statements);
+            let (scope, fan_in) = match caller {
+                Caller::Main => (ScopeId::Global, 0),
+                Caller::Function(index) => {
+                    let callers = graph.callers(index).into_iter().filter(|c| *c != caller);
+                    (ScopeId::Function(index), callers.count())
+                }
+            };
+            let count = |kind| model.symbols_in(scope).filter(|(_, s)| s.kind == kind).count();
+            Metrics {
+                name: name.to_string(),
+                statements: counter.statements,
+                nesting: counter.nesting,
+                complexity: counter.decisions + 1,
+                parameters: count(SymbolKind::Parameter),
+                locals: count(SymbolKind::Variable),
+                fan_in,
+                fan_out: graph.callees(caller).len(),
+            }
+        })
+        .collect()
+}
+
+/// The largest values that pass without a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    pub statements: usize,
+    pub nesting: usize,
+    pub complexity: usize,
+    pub parameters: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds { statements: 50, nesting: 4, complexity: 10, parameters: 5 }
+    }
+}
+
+impl Thresholds {
+    /// A warning for each figure in `metrics`, as returned by `measure`, above its threshold,
+    /// at the name of the function or program.
+    pub fn check(&self, program: &Program, metrics: &[Metrics]) -> Vec<Diagnostic> {
+        let functions = program.functions();
+        let names = functions.iter().map(|f| &f.name).chain([&program.name]);
+        let mut diagnostics = Vec::new();
+        for (index, (name, metrics)) in names.zip(metrics).enumerate() {
+            let body = match index < functions.len() {
+                true => format!("function '{}'", metrics.name),
+                false => "the main program".to_string(),
+            };
+            let figures = [
+                (metrics.statements, self.statements, format!("{} statements", metrics.statements)),
+                (metrics.nesting, self.nesting, format!("nesting depth {}", metrics.nesting)),
+                (metrics.complexity, self.complexity, format!("cyclomatic complexity {}", metrics.complexity)),
+                (metrics.parameters, self.parameters, format!("{} parameters", metrics.parameters)),
+            ];
+            for (value, threshold, figure) in figures {
+                if value > threshold {
+                    let message = format!("{} has {}, more than {}", body, figure, threshold);
+                    diagnostics.push(Diagnostic::warning(message, name.span));
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+const COLUMNS: [&str; 8] = ["function", "statements", "nesting", "complexity", "params", "locals", "fan-in", "fan-out"];
+
+/// The metrics as a table with a header, one row each, numbers aligned right.
+pub fn to_table(metrics: &[Metrics]) -> String {
+    let width = metrics.iter().map(|m| m.name.len()).chain([COLUMNS[0].len()]).max().unwrap_or_default();
+    let mut table = format!("{:<width$}", COLUMNS[0], width = width);
+    COLUMNS[1..].iter().for_each(|column| write!(table, "  {}", column).unwrap());
+    table.push('\n');
+    for m in metrics {
+        write!(table, "{:<width$}", m.name, width = width).unwrap();
+        let values = [m.statements, m.nesting, m.complexity, m.parameters, m.locals, m.fan_in, m.fan_out];
+        for (column, value) in COLUMNS[1..].iter().zip(values) {
+            write!(table, "  {:>width$}", value, width = column.len()).unwrap();
+        }
+        table.push('\n');
+    }
+    table
+}
+
+pub fn to_json(metrics: &[Metrics]) -> String {
+    serde_json::to_string_pretty(metrics).unwrap()
+}
+
+#[derive(Default)]
+struct Counter {
+    statements: usize,
+    depth: usize,
+    nesting: usize,
+    decisions: usize,
+}
+
+impl Visitor for Counter {
+    fn visit_statement(&mut self, statement: &Statement) {
+        let decisions = match statement {
+            Statement::Body { .. } | Statement::Null => return walk_statement(self, statement),
+            Statement::Assign { .. }
+            | Statement::Output { .. }
+            | Statement::Read { .. }
+            | Statement::Exit { .. }
+            | Statement::Return { .. } => {
+                self.statements += 1;
+                return walk_statement(self, statement);
+            }
+            Statement::Case { cases, .. } => cases.len(),
+            _ => 1,
+        };
+        self.statements += 1;
+        self.decisions += decisions;
+        self.depth += 1;
+        self.nesting = self.nesting.max(self.depth);
+        match statement {
+            Statement::If { cond, then, else_stmt: Some(else_stmt) } if matches!(**else_stmt, Statement::If { .. }) => {
+                self.visit_expression(cond);
+                self.visit_statement(then);
+                self.depth -= 1;
+                self.visit_statement(else_stmt);
+                self.depth += 1;
+            }
+            _ => walk_statement(self, statement),
+        }
+        self.depth -= 1;
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        if let Term::Or { .. } = term {
+            self.decisions += 1;
+        }
+        walk_term(self, term)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) {
+        if let Factor::And { .. } = factor {
+            self.decisions += 1;
+        }
+        walk_factor(self, factor)
+    }
+}
@@ -0,0 +1,9 @@
+//! Analyses of a whole program whose names have been resolved, for tools that want to
+//! know how it is put together rather than whether it is correct.
+
+pub mod callgraph;
+pub mod cfg;
+pub mod dataflow;
+pub mod metrics;
+pub mod variables;
+mod test;
@@ -0,0 +1,296 @@
+#[cfg(test)]
+mod tests {
+    use crate::analysis::callgraph::{CallGraph, Caller};
+    use crate::analysis::cfg::{ControlFlowGraph, Edge, EdgeKind};
+    use crate::analysis::metrics::{self, Metrics, Thresholds};
+    use crate::analysis::variables::{Access, Definition, VariableFlow};
+    use crate::diagnostic::Span;
+    use crate::lexer::Lexer;
+    use crate::parser::{Parser, Program};
+    use crate::semantic::{ScopeId, SemanticModel};
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false).parse().unwrap()
+    }
+
+    fn call_graph(source: &str) -> CallGraph {
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        CallGraph::build(&program, &model)
+    }
+
+    fn names(graph: &CallGraph, functions: &[usize]) -> Vec<String> {
+        functions.iter().map(|f| graph.name(*f).to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge_sort() {
+        let graph = call_graph(include_str!("./../../winzig_test_programs/winzig_12"));
+        assert_eq!(graph.len(), 8);
+        let merge_sort = 7;
+        assert_eq!(graph.name(merge_sort), "MergeSort");
+        assert_eq!(names(&graph, &graph.callees(Caller::Function(merge_sort))), ["Merge", "MergeSort"]);
+        assert_eq!(names(&graph, &graph.callees(Caller::Main)), ["Store", "Element", "MergeSort"]);
+        // 'Store' is called from 'Merge' and from the main body
+        assert_eq!(graph.callers(2), [Caller::Function(6), Caller::Main]);
+        assert_eq!(graph.calls(Caller::Function(merge_sort)).len(), 3);
+        assert_eq!(graph.calls(Caller::Function(merge_sort))[0].span.line, 132);
+
+        assert_eq!(graph.recursion(), [vec![merge_sort]]);
+        assert!(graph.unreachable().is_empty());
+    }
+
+    #[test]
+    fn test_recursion_and_unreachable_functions() {
+        let source = "program p:\n\
+                      function even(n : integer) : boolean; begin return (odd(n - 1)) end even;\n\
+                      function odd(n : integer) : boolean; begin return (even(n - 1)) end odd;\n\
+                      function f(n : integer) : integer; begin return (g(n)) end f;\n\
+                      function g(n : integer) : integer; begin return (h(n) + g(n)) end g;\n\
+                      function h(n : integer) : integer; begin return (n) end h;\n\
+                      begin output(h(1)); if even(2) then output(1) end p.";
+        let graph = call_graph(source);
+        assert_eq!(graph.recursion(), [vec![0, 1], vec![3]]);
+        assert_eq!(names(&graph, &graph.unreachable()), ["f", "g"]);
+
+        let dot = graph.to_dot("p");
+        assert!(dot.starts_with("digraph \"p\" {\n    \"<main>\" [shape=box];\n"));
+        assert!(dot.contains("    \"even\" [style=\"bold\"];\n"));
+        assert!(dot.contains("    \"f\" [style=\"dashed\"];\n    \"g\" [style=\"bold,dashed\"];\n    \"h\";\n"));
+        assert!(dot.contains("    \"<main>\" -> \"even\";\n    \"<main>\" -> \"h\";\n"));
+        assert!(dot.contains("    \"g\" -> \"g\";\n    \"g\" -> \"h\";\n}\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["main"], serde_json::json!(["even", "h"]));
+        assert_eq!(json["functions"][3], serde_json::json!({ "name": "g", "calls": ["g", "h"] }));
+        assert_eq!(json["recursive"], serde_json::json!([["even", "odd"], ["g"]]));
+        assert_eq!(json["unreachable"], serde_json::json!(["f", "g"]));
+    }
+
+    fn counts(graph: &ControlFlowGraph) -> (usize, usize) {
+        (graph.blocks().len(), graph.edge_count())
+    }
+
+    #[test]
+    fn test_sample_control_flow_graphs() {
+        let program = parse(include_str!("./../../winzig_test_programs/winzig_12"));
+        let merge = program.functions().iter().find(|f| f.name.name == "Merge").unwrap();
+        let graph = ControlFlowGraph::build(&merge.body);
+        assert_eq!(counts(&graph), (14, 18));
+        // every branch of the 'if' chain in the first 'while' meets at 'i := i + 1'
+        assert_eq!(graph.predecessors(4), [5, 7, 9, 10]);
+        assert_eq!(graph.predecessors(graph.exit()), [11]);
+        assert_eq!(counts(&ControlFlowGraph::build(&program.body)), (7, 8));
+
+        let program = parse(include_str!("./../../winzig_test_programs/winzig_07"));
+        let graph = ControlFlowGraph::build(&program.functions()[0].body);
+        assert_eq!(counts(&graph), (6, 7));
+        assert_eq!(graph.predecessors(graph.exit()), [1, 3, 4]);
+        assert_eq!(counts(&ControlFlowGraph::build(&program.body)), (4, 4));
+
+        // a 'repeat' loops back to its own block
+        let program = parse(include_str!("./../../winzig_test_programs/winzig_01"));
+        let graph = ControlFlowGraph::build(&program.body);
+        assert_eq!(counts(&graph), (2, 2));
+        assert_eq!(graph.block(0).successors[0], Edge { target: 0, kind: EdgeKind::False });
+    }
+
+    #[test]
+    fn test_loops_and_cases() {
+        let source = "program p:\n\
+                      var i : integer;\n\
+                      begin\n\
+                      loop read(i); if i = 0 then exit;\n\
+                      case i of 1: output(1); 2..3: output(2) otherwise output(3) end\n\
+                      pool;\n\
+                      output(i)\n\
+                      end p.";
+        let program = parse(source);
+        let graph = ControlFlowGraph::build(&program.body);
+        assert_eq!(counts(&graph), (7, 9));
+        let texts: Vec<String> = graph.block(0).elements.iter().map(|e| e.text()).collect();
+        assert_eq!(texts, ["read(i)", "i = 0"]);
+        // 'exit' leaves the loop, and each clause goes round it again
+        assert_eq!(graph.block(0).successors[0], Edge { target: 1, kind: EdgeKind::True });
+        let kinds: Vec<EdgeKind> = graph.block(2).successors.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [EdgeKind::Case(0), EdgeKind::Case(1), EdgeKind::Otherwise]);
+        assert_eq!(graph.predecessors(0), [3, 4, 5]);
+
+        let dot = graph.to_dot("p");
+        assert!(dot.starts_with("digraph \"p\" {\n    node [shape=box, fontname=monospace];\n"));
+        assert!(dot.contains("    b0 [label=\"read(i)\\li = 0\\l\"];\n"));
+        assert!(dot.contains("    b6 [label=\"exit\"];\n"));
+        assert!(dot.contains("    b2 -> b4 [label=\"case 2\"];\n    b2 -> b5 [label=\"otherwise\"];\n"));
+    }
+
+    #[test]
+    fn test_unreachable_blocks_are_dropped() {
+        let source = "program p:\n\
+                      function f(n : integer) : integer;\n\
+                      begin while n > 0 do begin return (n); n := n - 1 end; return (0) end f;\n\
+                      begin output(f(1)) end p.";
+        let program = parse(source);
+        let graph = ControlFlowGraph::build(&program.functions()[0].body);
+        // 'n := n - 1' follows the first 'return', so the loop never goes round
+        assert_eq!(counts(&graph), (4, 4));
+        assert!(graph.blocks().iter().flat_map(|b| &b.elements).all(|e| e.text() != "n := n - 1"));
+        assert_eq!(graph.predecessors(graph.exit()), [1, 2]);
+        // a body without branches is one block before the exit
+        assert_eq!(counts(&ControlFlowGraph::build(&program.body)), (2, 1));
+    }
+
+    /// The span of the name starting at the `nth` occurrence of `needle`.
+    fn span_of(source: &str, model: &SemanticModel, needle: &str, nth: usize) -> Span {
+        let offset = source.match_indices(needle).nth(nth).unwrap().0;
+        model.reference_at(offset).unwrap().span
+    }
+
+    #[test]
+    fn test_variable_flow() {
+        let source = "program p:\n\
+                      var i : integer;\n\
+                      function f(n : integer) : integer;\n\
+                      var x, y, z : integer;\n\
+                      begin\n\
+                      if n > 0 then x := 1;\n\
+                      y := x;\n\
+                      z := 1;\n\
+                      z := y + n;\n\
+                      n := 0;\n\
+                      return (z)\n\
+                      end f;\n\
+                      begin i := f(1); output(i) end p.";
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        let flow = VariableFlow::build(&program, &model, ScopeId::Function(0));
+        let lines = |definitions: Vec<Definition>| definitions.iter().map(|d| d.span.line).collect::<Vec<_>>();
+
+        // 'x' is assigned only when 'n > 0', and parameters on entry
+        let x = span_of(source, &model, "x;", 0);
+        assert_eq!(lines(flow.reaching_definitions(x)), [6]);
+        assert!(!flow.is_definitely_assigned(x));
+        let n = span_of(source, &model, "n;\n", 0);
+        assert_eq!(lines(flow.reaching_definitions(n)), [3]);
+        assert!(flow.is_definitely_assigned(n));
+
+        let names = |accesses: Vec<Access>| {
+            accesses.iter().map(|a| (model.symbol(a.symbol).name.as_str(), a.span.line)).collect::<Vec<_>>()
+        };
+        assert_eq!(names(flow.unassigned_reads()), [("x", 7)]);
+        assert_eq!(names(flow.dead_stores()), [("z", 8), ("n", 10)]);
+        assert!(flow.is_live_after(span_of(source, &model, "z := y", 0)));
+
+        // 'x' may still be unassigned when the body starts, so it is live there
+        let graph = flow.graph();
+        let live = flow.live().block_start(graph.entry());
+        assert_eq!(live.iter().map(|s| model.symbol(*s).name.as_str()).collect::<Vec<_>>(), ["n", "x"]);
+        assert!(flow.live().block_end(graph.exit()).is_empty());
+        assert_eq!(flow.definitions().len(), 6);
+
+        // 'i' in the main program is assigned before it is read
+        let flow = VariableFlow::build(&program, &model, ScopeId::Global);
+        assert!(flow.unassigned_reads().is_empty());
+        assert!(flow.dead_stores().is_empty());
+    }
+
+    #[test]
+    fn test_variable_flow_in_loops() {
+        let source = "program p:\n\
+                      var i, s, t : integer;\n\
+                      begin\n\
+                      s := 0; i := 0;\n\
+                      while i < 10 do begin s := s + i; t := s; i := i + 1 end;\n\
+                      output(s)\n\
+                      end p.";
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        let flow = VariableFlow::build(&program, &model, ScopeId::Global);
+        // the condition sees the first store and the one that goes round the loop
+        let condition = span_of(source, &model, "i < 10", 0);
+        let reaching: Vec<Span> = flow.reaching_definitions(condition).iter().map(|d| d.span).collect();
+        assert_eq!(reaching, [span_of(source, &model, "i := 0", 0), span_of(source, &model, "i := i + 1", 0)]);
+        assert!(flow.is_definitely_assigned(condition));
+        // 't' is stored into on every pass and never read
+        let t = span_of(source, &model, "t :=", 0);
+        assert_eq!(flow.dead_stores().iter().map(|a| a.span).collect::<Vec<_>>(), [t]);
+        assert!(flow.is_live_after(span_of(source, &model, "s := s", 0)));
+    }
+
+    #[test]
+    fn test_merge_sort_metrics() {
+        let program = parse(include_str!("./../../winzig_test_programs/winzig_12"));
+        let model = SemanticModel::build(&program);
+        let metrics = metrics::measure(&program, &model);
+        assert_eq!(metrics.len(), 9);
+        // the 'else if' chain inside the first 'for' is one level deeper than the 'for'
+        let merge = Metrics {
+            name: "Merge".to_string(),
+            statements: 18,
+            nesting: 2,
+            complexity: 6,
+            parameters: 3,
+            locals: 4,
+            fan_in: 1,
+            fan_out: 2,
+        };
+        assert_eq!(metrics[6], merge);
+        // 'MergeSort' calls itself, which counts towards its fan-out but not its fan-in
+        assert_eq!((metrics[7].fan_in, metrics[7].fan_out), (1, 2));
+        assert_eq!(metrics[8].name, "<main>");
+        assert_eq!((metrics[8].parameters, metrics[8].fan_in, metrics[8].fan_out), (0, 0, 3));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let source = "program p:\n\
+                      var n : integer;\n\
+                      function f(a, b : integer) : integer;\n\
+                      var x : integer;\n\
+                      begin\n\
+                      x := 0;\n\
+                      while (a > 0) and (b > 0) or (a = b) do\n\
+                      case a of 1: x := 1; 2: loop if x = 1 then exit pool otherwise x := 2 end;\n\
+                      return (x)\n\
+                      end f;\n\
+                      begin n := f(1, 2); output(f(n, n)) end p.";
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        let measured = metrics::measure(&program, &model);
+        // 'while', 'and', 'or', two 'case' clauses, 'loop' and 'if', plus one
+        let f = Metrics {
+            name: "f".to_string(),
+            statements: 9,
+            nesting: 4,
+            complexity: 8,
+            parameters: 2,
+            locals: 1,
+            fan_in: 1,
+            fan_out: 0,
+        };
+        assert_eq!(measured[0], f);
+        assert_eq!((measured[1].statements, measured[1].nesting, measured[1].complexity), (2, 0, 1));
+
+        assert_eq!(
+            metrics::to_table(&measured),
+            "function  statements  nesting  complexity  params  locals  fan-in  fan-out\n\
+             f                  9        4           8       2       1       1        0\n\
+             <main>             2        0           1       0       1       0        1\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&metrics::to_json(&measured)).unwrap();
+        assert_eq!(json[1]["name"], "<main>");
+        assert_eq!(json[0]["fan_in"], 1);
+
+        assert!(Thresholds::default().check(&program, &measured).is_empty());
+        let thresholds = Thresholds { statements: 2, nesting: 3, complexity: 10, parameters: 1 };
+        let warnings: Vec<String> = thresholds.check(&program, &measured).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            warnings,
+            [
+                "3:10: warning: function 'f' has 9 statements, more than 2",
+                "3:10: warning: function 'f' has nesting depth 4, more than 3",
+                "3:10: warning: function 'f' has 2 parameters, more than 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::diagnostic::Span;
+use crate::parser::visit::{walk_assignment, walk_expression, walk_statement, NameUsage, Visitor};
+use crate::parser::*;
+use crate::semantic::{ScopeId, SemanticModel, SymbolId, SymbolKind};
+
+use super::cfg::{BlockId, ControlFlowGraph, Element};
+use super::dataflow::{solve, Analysis, Direction, Solution};
+
+/// A use of a followed variable by an element of the graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub symbol: SymbolId,
+    pub span: Span,
+    pub usage: NameUsage,
+}
+
+impl Access {
+    /// Whether the access reads the variable; both sides of `:=:` are read, then stored.
+    pub fn reads(&self) -> bool {
+        matches!(self.usage, NameUsage::Value | NameUsage::Swap)
+    }
+
+    pub fn writes(&self) -> bool {
+        matches!(self.usage, NameUsage::Assign | NameUsage::Swap | NameUsage::Read)
+    }
+}
+
+/// A store into a variable: an assignment, `:=:` or `read`, or a parameter's value on entry,
+/// at the parameter's declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Definition {
+    pub symbol: SymbolId,
+    pub span: Span,
+}
+
+/// The variables whose values a body's flow decides: a function's parameters and locals,
+/// or the program's globals that no function uses, since a call could read or store those.
+struct Variables<'a> {
+    model: &'a SemanticModel,
+    followed: BTreeSet<SymbolId>,
+    parameters: BTreeSet<SymbolId>,
+}
+
+impl<'a> Variables<'a> {
+    fn new(model: &'a SemanticModel, scope: ScopeId) -> Self {
+        let used_by_functions: HashSet<SymbolId> =
+            model.references().iter().filter(|r| r.scope != ScopeId::Global).map(|r| r.symbol).collect();
+        let followed = model
+            .symbols_in(scope)
+            .filter(|(id, symbol)| match symbol.kind {
+                SymbolKind::Parameter => true,
+                SymbolKind::Variable => scope != ScopeId::Global || !used_by_functions.contains(id),
+                _ => false,
+            })
+            .map(|(id, _)| id)
+            .collect();
+        let parameters = model.symbols_in(scope).filter(|(_, s)| s.kind == SymbolKind::Parameter).map(|(id, _)| id);
+        Variables { model, followed, parameters: parameters.collect() }
+    }
+
+    /// The accesses of `element` to followed variables, in evaluation order.
+    fn accesses(&self, element: &Element) -> Vec<Access> {
+        let mut accesses = Accesses { variables: self, accesses: Vec::new() };
+        match element {
+            Element::Statement(statement) => walk_statement(&mut accesses, statement),
+            Element::Assignment(assignment) => walk_assignment(&mut accesses, assignment),
+            Element::Condition(expression) | Element::Selector(expression) => {
+                walk_expression(&mut accesses, expression)
+            }
+        }
+        accesses.accesses
+    }
+}
+
+struct Accesses<'a, 'b> {
+    variables: &'b Variables<'a>,
+    accesses: Vec<Access>,
+}
+
+impl Visitor for Accesses<'_, '_> {
+    fn visit_name(&mut self, name: &Identifier, usage: NameUsage) {
+        if let Some(symbol) = self.variables.model.symbol_at(name.span) {
+            if self.variables.followed.contains(&symbol) && usage != NameUsage::Call {
+                self.accesses.push(Access { symbol, span: name.span, usage });
+            }
+        }
+    }
+}
+
+/// The variables that may be read before they are next stored into.
+struct LiveVariables<'a, 'b>(&'b Variables<'a>);
+
+impl LiveVariables<'_, '_> {
+    /// Moves the live variables after `access` to before it.
+    fn access(&self, access: &Access, live: &mut BTreeSet<SymbolId>) {
+        if access.writes() {
+            live.remove(&access.symbol);
+        }
+        if access.reads() {
+            live.insert(access.symbol);
+        }
+    }
+}
+
+impl<'a> Analysis<'a> for LiveVariables<'a, '_> {
+    type Fact = BTreeSet<SymbolId>;
+
+    const DIRECTION: Direction = Direction::Backward;
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn bottom(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other)
+    }
+
+    fn transfer(&self, element: &Element<'a>, fact: &mut Self::Fact) {
+        self.0.accesses(element).iter().rev().for_each(|access| self.access(access, fact))
+    }
+}
+
+/// The definitions, by index, whose stores may still be in their variables.
+struct ReachingDefinitions<'a, 'b> {
+    variables: &'b Variables<'a>,
+    definitions: &'b [Definition],
+    // the definition made by each store, by the start of its name
+    stores: &'b HashMap<usize, usize>,
+}
+
+impl ReachingDefinitions<'_, '_> {
+    fn access(&self, access: &Access, reaching: &mut BTreeSet<usize>) {
+        if let Some(definition) = self.stores.get(&access.span.start) {
+            reaching.retain(|d| self.definitions[*d].symbol != access.symbol);
+            reaching.insert(*definition);
+        }
+    }
+}
+
+impl<'a> Analysis<'a> for ReachingDefinitions<'a, '_> {
+    type Fact = BTreeSet<usize>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    // the parameters' definitions come first
+    fn boundary(&self) -> Self::Fact {
+        (0..self.variables.parameters.len()).collect()
+    }
+
+    fn bottom(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other)
+    }
+
+    fn transfer(&self, element: &Element<'a>, fact: &mut Self::Fact) {
+        self.variables.accesses(element).iter().for_each(|access| self.access(access, fact))
+    }
+}
+
+/// The variables stored into on every path to a point.
+struct DefiniteAssignment<'a, 'b>(&'b Variables<'a>);
+
+impl DefiniteAssignment<'_, '_> {
+    fn access(&self, access: &Access, assigned: &mut BTreeSet<SymbolId>) {
+        if access.writes() {
+            assigned.insert(access.symbol);
+        }
+    }
+}
+
+impl<'a> Analysis<'a> for DefiniteAssignment<'a, '_> {
+    type Fact = BTreeSet<SymbolId>;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self) -> Self::Fact {
+        self.0.parameters.clone()
+    }
+
+    // nothing is known to be unassigned until a path says so
+    fn bottom(&self) -> Self::Fact {
+        self.0.followed.clone()
+    }
+
+    fn join(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.retain(|symbol| other.contains(symbol))
+    }
+
+    fn transfer(&self, element: &Element<'a>, fact: &mut Self::Fact) {
+        self.0.accesses(element).iter().for_each(|access| self.access(access, fact))
+    }
+}
+
+/// Live variables, reaching definitions and definite assignment for the body of a function
+/// or of the program, with the variables followed as for `Variables`. Queries take the span
+/// of a variable's name in the body, and answer for the point of that access.
+pub struct VariableFlow<'a> {
+    graph: ControlFlowGraph<'a>,
+    variables: Variables<'a>,
+    definitions: Vec<Definition>,
+    stores: HashMap<usize, usize>,
+    // where each access is: its block, element and place among the element's accesses
+    locations: HashMap<usize, (BlockId, usize, usize)>,
+    live: Solution<BTreeSet<SymbolId>>,
+    reaching: Solution<BTreeSet<usize>>,
+    assigned: Solution<BTreeSet<SymbolId>>,
+}
+
+impl<'a> VariableFlow<'a> {
+    pub fn build(program: &'a Program, model: &'a SemanticModel, scope: ScopeId) -> Self {
+        let body = match scope {
+            ScopeId::Global => &program.body,
+            ScopeId::Function(index) => &program.functions()[index].body,
+        };
+        let graph = ControlFlowGraph::build(body);
+        let variables = Variables::new(model, scope);
+
+        let parameters = variables.parameters.iter();
+        let mut definitions: Vec<Definition> =
+            parameters.map(|p| Definition { symbol: *p, span: model.symbol(*p).span.unwrap() }).collect();
+        let mut stores = HashMap::new();
+        let mut locations = HashMap::new();
+        for (id, block) in graph.blocks().iter().enumerate() {
+            for (index, element) in block.elements.iter().enumerate() {
+                for (place, access) in variables.accesses(element).into_iter().enumerate() {
+                    locations.insert(access.span.start, (id, index, place));
+                    if access.writes() {
+                        stores.insert(access.span.start, definitions.len());
+                        definitions.push(Definition { symbol: access.symbol, span: access.span });
+                    }
+                }
+            }
+        }
+
+        let live = solve(&graph, &LiveVariables(&variables));
+        let reaching = ReachingDefinitions { variables: &variables, definitions: &definitions, stores: &stores };
+        let reaching = solve(&graph, &reaching);
+        let assigned = solve(&graph, &DefiniteAssignment(&variables));
+        VariableFlow { graph, variables, definitions, stores, locations, live, reaching, assigned }
+    }
+
+    pub fn graph(&self) -> &ControlFlowGraph<'a> {
+        &self.graph
+    }
+
+    pub fn definitions(&self) -> &[Definition] {
+        &self.definitions
+    }
+
+    /// The live variables at each point of the graph.
+    pub fn live(&self) -> &Solution<BTreeSet<SymbolId>> {
+        &self.live
+    }
+
+    /// The reaching definitions, as indices into `definitions`, at each point of the graph.
+    pub fn reaching(&self) -> &Solution<BTreeSet<usize>> {
+        &self.reaching
+    }
+
+    /// The definitely assigned variables at each point of the graph.
+    pub fn assigned(&self) -> &Solution<BTreeSet<SymbolId>> {
+        &self.assigned
+    }
+
+    /// The access whose name is at `span`, if it is of a followed variable in reachable code.
+    pub fn access_at(&self, span: Span) -> Option<Access> {
+        let (block, index, place) = self.locations.get(&span.start)?;
+        Some(self.accesses(*block, *index)[*place])
+    }
+
+    /// The definitions of the variable at `span` that may reach it, in source order.
+    pub fn reaching_definitions(&self, span: Span) -> Vec<Definition> {
+        let Some((block, index, place)) = self.locations.get(&span.start).copied() else {
+            return Vec::new();
+        };
+        let accesses = self.accesses(block, index);
+        let analysis =
+            ReachingDefinitions { variables: &self.variables, definitions: &self.definitions, stores: &self.stores };
+        let mut reaching = self.reaching.at(block, index).clone();
+        accesses[..place].iter().for_each(|access| analysis.access(access, &mut reaching));
+        let symbol = accesses[place].symbol;
+        let mut definitions: Vec<Definition> =
+            reaching.into_iter().map(|d| self.definitions[d]).filter(|d| d.symbol == symbol).collect();
+        definitions.sort_by_key(|d| d.span.start);
+        definitions
+    }
+
+    /// Whether the variable at `span` has been stored into on every path to it.
+    pub fn is_definitely_assigned(&self, span: Span) -> bool {
+        let Some((block, index, place)) = self.locations.get(&span.start).copied() else {
+            return false;
+        };
+        let accesses = self.accesses(block, index);
+        let analysis = DefiniteAssignment(&self.variables);
+        let mut assigned = self.assigned.at(block, index).clone();
+        accesses[..place].iter().for_each(|access| analysis.access(access, &mut assigned));
+        assigned.contains(&accesses[place].symbol)
+    }
+
+    /// Whether the value of the variable at `span` may be read later.
+    pub fn is_live_after(&self, span: Span) -> bool {
+        let Some((block, index, place)) = self.locations.get(&span.start).copied() else {
+            return false;
+        };
+        let accesses = self.accesses(block, index);
+        let analysis = LiveVariables(&self.variables);
+        let mut live = self.live.at(block, index + 1).clone();
+        accesses[place + 1..].iter().rev().for_each(|access| analysis.access(access, &mut live));
+        live.contains(&accesses[place].symbol)
+    }
+
+    /// Reads of variables that aren't assigned on every path to them, in source order.
+    /// Parameters always are.
+    pub fn unassigned_reads(&self) -> Vec<Access> {
+        let mut reads: Vec<Access> =
+            self.all_accesses().filter(|a| a.reads() && !self.is_definitely_assigned(a.span)).collect();
+        reads.sort_by_key(|a| a.span.start);
+        reads
+    }
+
+    /// Stores with `:=` or `read` whose value is never read, in source order.
+    pub fn dead_stores(&self) -> Vec<Access> {
+        let stores = self.all_accesses().filter(|a| matches!(a.usage, NameUsage::Assign | NameUsage::Read));
+        let mut dead: Vec<Access> = stores.filter(|a| !self.is_live_after(a.span)).collect();
+        dead.sort_by_key(|a| a.span.start);
+        dead
+    }
+
+    fn accesses(&self, block: BlockId, index: usize) -> Vec<Access> {
+        self.variables.accesses(&self.graph.block(block).elements[index])
+    }
+
+    fn all_accesses(&self) -> impl Iterator<Item = Access> + '_ {
+        let elements = self.graph.blocks().iter().flat_map(|b| &b.elements);
+        elements.flat_map(|element| self.variables.accesses(element))
+    }
+}
@@ -0,0 +1,10 @@
+use lsp_server::Connection;
+use win_zig::lsp::{self, LspResult};
+
+/// Language server for WinZig over standard input and output.
+fn main() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    lsp::run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::{count, Diagnostic};
+use crate::parser::visit::{walk_primary, walk_statements, Visitor};
+use crate::parser::*;
+use crate::semantic::{SemanticModel, SymbolId, SymbolKind};
+
+use super::{bodies, describe};
+use super::types::expression_type;
+
+/// Checks every call against the function it names: that it is a function, and that the
+/// arguments match its parameters in number and type. Undeclared functions are reported
+/// by the model.
+pub fn check(program: &Program, model: &SemanticModel, diagnostics: &mut Vec<Diagnostic>) {
+    let functions = program.functions().iter().filter_map(|f| Some((model.symbol_at(f.name.span)?, f))).collect();
+    let mut calls = Calls { model, functions, diagnostics };
+    for body in bodies(program) {
+        walk_statements(&mut calls, &body.statements);
+    }
+}
+
+struct Calls<'a> {
+    model: &'a SemanticModel,
+    functions: HashMap<SymbolId, &'a Func>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Visitor for Calls<'_> {
+    fn visit_primary(&mut self, primary: &Primary) {
+        if let Primary::Call { name, exps } = primary {
+            self.call(name, exps);
+        }
+        walk_primary(self, primary)
+    }
+}
+
+impl Calls<'_> {
+    fn call(&mut self, name: &Identifier, arguments: &[Expression]) {
+        let Some(symbol) = self.model.symbol_at(name.span) else {
+            return;
+        };
+        let Some(func) = self.functions.get(&symbol).copied() else {
+            let kind = self.model.symbol(symbol).kind;
+            if kind == SymbolKind::Function {
+                return;
+            }
+            let message = format!("'{}' is {}, not a function", name.name, describe(kind));
+            self.diagnostics.push(Diagnostic::error(message, name.span));
+            return;
+        };
+
+        let params: Vec<&Identifier> = func.params.params.iter().flat_map(|p| &p.names).collect();
+        if params.len() != arguments.len() {
+            let message = format!(
+                "function '{}' takes {} but {} supplied",
+                name.name,
+                count(params.len(), "argument", "arguments"),
+                count(arguments.len(), "was", "were")
+            );
+            self.diagnostics.push(Diagnostic::error(message, name.span));
+            return;
+        }
+        for (param, argument) in params.into_iter().zip(arguments) {
+            let expected = self.model.symbol_at(param.span).and_then(|p| self.model.type_of(p));
+            let found = expression_type(self.model, argument);
+            if let (Some(expected), Some(found)) = (expected, found) {
+                if expected != found {
+                    let message = format!(
+                        "argument '{}' of '{}' expects {}, found {}",
+                        param.name,
+                        name.name,
+                        self.model.symbol(expected).name,
+                        self.model.symbol(found).name
+                    );
+                    self.diagnostics.push(Diagnostic::error(message, name.span));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::diagnostic::Diagnostic;
+use crate::parser::visit::{walk_statement, walk_statements, Visitor};
+use crate::parser::*;
+use crate::semantic::{ConstantValue, SemanticModel, SymbolId, SymbolKind};
+
+use super::types::expression_type;
+use super::{bodies, CheckOptions};
+
+/// Checks the labels of every `case`: that they are constants of the selector's type, and
+/// that no value is covered twice.
+pub fn check(program: &Program, model: &SemanticModel, options: &CheckOptions, diagnostics: &mut Vec<Diagnostic>) {
+    let mut cases = Cases { model, options, diagnostics };
+    for body in bodies(program) {
+        walk_statements(&mut cases, &body.statements);
+    }
+}
+
+/// The literals of the enumeration `selector` that no label of `clauses` covers, in
+/// declaration order. Labels in error cover nothing.
+pub(super) fn uncovered_literals(model: &SemanticModel, selector: SymbolId, clauses: &[CaseClause]) -> Vec<SymbolId> {
+    let bounds = |label: &CaseLabel| match &label.expression {
+        CaseExpression::Value(value) => constant(model, value).map(|v| (v, v)),
+        CaseExpression::Range(low, high) => Some((constant(model, low)?, constant(model, high)?)),
+    };
+    let covered: Vec<(i64, i64)> = clauses
+        .iter()
+        .flat_map(|c| &c.labels)
+        .filter_map(bounds)
+        .filter(|(low, high)| low.type_id() == selector && high.type_id() == selector)
+        .map(|(low, high)| (low.ordinal(), high.ordinal()))
+        .collect();
+    let is_covered = |ordinal: i64| covered.iter().any(|(low, high)| (*low..=*high).contains(&ordinal));
+    let literals = model.literals_of(selector).into_iter().enumerate();
+    literals.filter(|(ordinal, _)| !is_covered(*ordinal as i64)).map(|(_, literal)| literal).collect()
+}
+
+/// The value of a label, if it is a constant.
+fn constant(model: &SemanticModel, value: &ConstValue) -> Option<ConstantValue> {
+    match value {
+        ConstValue::Integer(i) => Some(ConstantValue::Integer(*i)),
+        ConstValue::Char(c) => Some(ConstantValue::Char(*c)),
+        ConstValue::Name(name) => model.symbol_at(name.span).and_then(|s| model.const_value(s)),
+    }
+}
+
+/// The values a label that checked out covers, `low..=high`.
+struct Covered {
+    low: ConstantValue,
+    high: ConstantValue,
+}
+
+struct Cases<'a> {
+    model: &'a SemanticModel,
+    options: &'a CheckOptions,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Visitor for Cases<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if let Statement::Case { expr, cases, otherwise } = statement {
+            self.case(expr, cases, otherwise.is_some());
+        }
+        walk_statement(self, statement)
+    }
+}
+
+impl Cases<'_> {
+    fn case(&mut self, selector: &Expression, clauses: &[CaseClause], has_otherwise: bool) {
+        let selector = expression_type(self.model, selector);
+        let mut covered: Vec<Covered> = Vec::new();
+        for label in clauses.iter().flat_map(|c| &c.labels) {
+            let Some((low, high)) = self.bounds(label) else {
+                continue;
+            };
+            // without a known selector type, the bounds of a range must still agree
+            let expected = selector.unwrap_or(low.type_id());
+            if let Some(bound) = [low, high].into_iter().find(|b| b.type_id() != expected) {
+                let message = format!(
+                    "case label of type {} does not match selector of type {}",
+                    self.type_name(bound.type_id()),
+                    self.type_name(expected)
+                );
+                self.diagnostics.push(Diagnostic::error(message, label.span));
+                continue;
+            }
+            if low.ordinal() > high.ordinal() {
+                let message = format!("empty range {}", self.label_text(low, high));
+                self.diagnostics.push(Diagnostic::warning(message, label.span));
+                continue;
+            }
+            let (first, last) = (low.ordinal(), high.ordinal());
+            let overlapping = covered.iter().find(|c| c.low.ordinal() <= last && first <= c.high.ordinal());
+            if let Some(other) = overlapping {
+                let message = if (other.low, other.high) == (low, high) {
+                    format!("duplicate case label {}", self.label_text(low, high))
+                } else {
+                    let other = self.label_text(other.low, other.high);
+                    format!("case label {} overlaps {}", self.label_text(low, high), other)
+                };
+                self.diagnostics.push(Diagnostic::error(message, label.span));
+                continue;
+            }
+            covered.push(Covered { low, high });
+        }
+
+        if self.options.exhaustive_case && !has_otherwise {
+            if let Some(selector) = selector {
+                self.exhaustive(selector, clauses);
+            }
+        }
+    }
+
+    fn exhaustive(&mut self, selector: SymbolId, clauses: &[CaseClause]) {
+        let missing = uncovered_literals(self.model, selector, clauses);
+        let missing: Vec<&str> = missing.iter().map(|l| self.model.symbol(*l).name.as_str()).collect();
+        let mut labels = clauses.iter().flat_map(|c| &c.labels).map(|l| l.span);
+        let (Some(first), last) = (labels.next(), labels.next_back()) else {
+            return;
+        };
+        if !missing.is_empty() {
+            let message = format!(
+                "case over {} has no 'otherwise' and doesn't cover {}",
+                self.type_name(selector),
+                missing.join(", ")
+            );
+            self.diagnostics.push(Diagnostic::warning(message, first.until(last.unwrap_or(first))));
+        }
+    }
+
+    fn bounds(&mut self, label: &CaseLabel) -> Option<(ConstantValue, ConstantValue)> {
+        match &label.expression {
+            CaseExpression::Value(value) => {
+                let value = self.value(value)?;
+                Some((value, value))
+            }
+            CaseExpression::Range(low, high) => {
+                let (low, high) = (self.value(low), self.value(high));
+                Some((low?, high?))
+            }
+        }
+    }
+
+    /// The value of a label. `None` when it isn't a constant, which is reported unless
+    /// the name is undeclared or the constant is in error.
+    fn value(&mut self, value: &ConstValue) -> Option<ConstantValue> {
+        if let ConstValue::Name(name) = value {
+            let symbol = self.model.symbol_at(name.span)?;
+            if !matches!(self.model.symbol(symbol).kind, SymbolKind::EnumLiteral | SymbolKind::Constant) {
+                let message = format!("case label '{}' is not a constant", name.name);
+                self.diagnostics.push(Diagnostic::error(message, name.span));
+                return None;
+            }
+        }
+        constant(self.model, value)
+    }
+
+    fn type_name(&self, ty: SymbolId) -> &str {
+        &self.model.symbol(ty).name
+    }
+
+    fn label_text(&self, low: ConstantValue, high: ConstantValue) -> String {
+        if low == high {
+            self.model.value_text(low)
+        } else {
+            format!("{}..{}", self.model.value_text(low), self.model.value_text(high))
+        }
+    }
+}
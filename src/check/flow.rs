@@ -0,0 +1,132 @@
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::visit::Visitor;
+use crate::parser::*;
+use crate::semantic::SemanticModel;
+
+use super::cases::uncovered_literals;
+use super::FirstSpan;
+use super::types::expression_type;
+
+/// Checks where control goes: that `exit` is inside a loop and `return` inside a function,
+/// that functions return a value on every path, and that no statement follows one that
+/// never completes.
+pub fn check(program: &Program, model: &SemanticModel, diagnostics: &mut Vec<Diagnostic>) {
+    for function in program.functions() {
+        let mut flow = Flow::new(model, true, diagnostics);
+        if flow.statements(&function.body.statements) {
+            let message = format!("function '{}' can reach its end without returning a value", function.name.name);
+            flow.diagnostics.push(Diagnostic::warning(message, function.end_name.span));
+        }
+    }
+    Flow::new(model, false, diagnostics).statements(&program.body.statements);
+}
+
+struct Flow<'a> {
+    model: &'a SemanticModel,
+    in_function: bool,
+    // the loops around the current statement, innermost last, and whether an 'exit' leaves each
+    loops: Vec<bool>,
+    // the last 'exit' or 'return' in the statement just checked
+    terminator: Option<(Span, &'static str)>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> Flow<'a> {
+    fn new(model: &'a SemanticModel, in_function: bool, diagnostics: &'a mut Vec<Diagnostic>) -> Self {
+        Flow { model, in_function, loops: Vec::new(), terminator: None, diagnostics }
+    }
+
+    /// Checks a statement list, returning whether it can complete normally.
+    fn statements(&mut self, statements: &[Statement]) -> bool {
+        let mut completes = true;
+        let mut reported = false;
+        for statement in statements {
+            if !completes && !reported && !matches!(statement, Statement::Null) {
+                self.unreachable(statement);
+                reported = true;
+            }
+            self.terminator = None;
+            completes &= self.statement(statement);
+        }
+        completes
+    }
+
+    /// Checks a statement, returning whether it can complete normally, i.e. without
+    /// leaving through 'exit' or 'return' or looping forever.
+    fn statement(&mut self, statement: &Statement) -> bool {
+        match statement {
+            Statement::Exit { span } => {
+                match self.loops.last_mut() {
+                    Some(exits) => *exits = true,
+                    None => self.diagnostics.push(Diagnostic::error("'exit' outside a loop".to_string(), *span)),
+                }
+                self.terminator = Some((*span, "exit"));
+                false
+            }
+            Statement::Return { span, .. } => {
+                if !self.in_function {
+                    let message = "'return' in the main program".to_string();
+                    self.diagnostics.push(Diagnostic::error(message, *span));
+                }
+                self.terminator = Some((*span, "return"));
+                false
+            }
+            Statement::If { then, else_stmt, .. } => {
+                let then = self.statement(then);
+                match else_stmt {
+                    Some(else_stmt) => self.statement(else_stmt) | then,
+                    None => true,
+                }
+            }
+            Statement::While { stmt, .. } => {
+                self.statement(stmt);
+                true
+            }
+            Statement::For { cond, stmt, .. } => {
+                self.statement(stmt);
+                matches!(cond, ForExp::Expression(_))
+            }
+            Statement::Repeat { stmts, .. } => self.statements(stmts),
+            Statement::Loop { stmts } => {
+                self.loops.push(false);
+                self.statements(stmts);
+                self.loops.pop().unwrap_or_default()
+            }
+            Statement::Case { expr, cases, otherwise } => {
+                // without 'otherwise', no clause runs unless the labels cover every literal
+                let exhaustive = match expression_type(self.model, expr) {
+                    Some(selector) => {
+                        !self.model.literals_of(selector).is_empty()
+                            && uncovered_literals(self.model, selector, cases).is_empty()
+                    }
+                    None => false,
+                };
+                let mut completes = otherwise.is_none() && !exhaustive;
+                for clause in cases {
+                    completes |= self.statement(&clause.statement);
+                }
+                if let Some(otherwise) = otherwise {
+                    completes |= self.statement(&otherwise.stmt);
+                }
+                completes
+            }
+            Statement::Body { body } => self.statements(&body.statements),
+            Statement::Assign { .. } | Statement::Output { .. } | Statement::Read { .. } | Statement::Null => true,
+        }
+    }
+
+    /// Reports `statement` at its first name or keyword, or at the 'exit' or 'return'
+    /// before it when it has neither, as in `output(1)`.
+    fn unreachable(&mut self, statement: &Statement) {
+        let mut first = FirstSpan(None);
+        first.visit_statement(statement);
+        match (first.0, self.terminator) {
+            (Some(span), _) => self.diagnostics.push(Diagnostic::warning("unreachable statement".to_string(), span)),
+            (None, Some((span, keyword))) => {
+                let message = format!("statements after this '{}' are unreachable", keyword);
+                self.diagnostics.push(Diagnostic::warning(message, span));
+            }
+            (None, None) => {}
+        }
+    }
+}
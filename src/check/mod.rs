@@ -0,0 +1,79 @@
+//! Checks of a program whose names have been resolved, for what the grammar and name
+//! resolution accept but the language doesn't allow.
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::visit::{walk_statement, NameUsage, Visitor};
+use crate::parser::{Body, Identifier, Program, Statement};
+use crate::semantic::{SemanticModel, SymbolKind};
+
+mod calls;
+mod cases;
+mod flow;
+mod targets;
+pub mod types;
+mod typing;
+mod test;
+
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// Warn about a `case` over an enumeration that has no `otherwise` and doesn't
+    /// cover every literal.
+    pub exhaustive_case: bool,
+}
+
+/// Errors and warnings for `program`, in source order. Names that don't resolve are
+/// reported by `model`, not here.
+pub fn check(program: &Program, model: &SemanticModel, options: &CheckOptions) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    calls::check(program, model, &mut diagnostics);
+    cases::check(program, model, options, &mut diagnostics);
+    flow::check(program, model, &mut diagnostics);
+    targets::check(program, model, &mut diagnostics);
+    typing::check(program, model, &mut diagnostics);
+    diagnostics.sort_by_key(|d| d.span.start);
+    diagnostics
+}
+
+/// The body of every function, then the main program's.
+fn bodies(program: &Program) -> impl Iterator<Item = &Body> {
+    program.functions().iter().map(|f| &f.body).chain(std::iter::once(&program.body))
+}
+
+/// What a symbol of `kind` is, as in "'n' is a variable".
+fn describe(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Program => "the program",
+        SymbolKind::Function => "a function",
+        SymbolKind::Parameter => "a parameter",
+        SymbolKind::Variable => "a variable",
+        SymbolKind::Constant => "a constant",
+        SymbolKind::EnumLiteral => "an enumeration literal",
+        SymbolKind::Type => "a type",
+    }
+}
+
+/// The span that starts first among the names, labels and keywords of a statement.
+struct FirstSpan(Option<Span>);
+
+impl FirstSpan {
+    fn add(&mut self, span: Span) {
+        if self.0.map_or(true, |first| span.start < first.start) {
+            self.0 = Some(span);
+        }
+    }
+}
+
+impl Visitor for FirstSpan {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Exit { span } | Statement::Return { span, .. } => self.add(*span),
+            Statement::Case { cases, .. } => cases.iter().flat_map(|c| &c.labels).for_each(|l| self.add(l.span)),
+            _ => {}
+        }
+        walk_statement(self, statement)
+    }
+
+    fn visit_name(&mut self, name: &Identifier, _usage: NameUsage) {
+        self.add(name.span)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::diagnostic::Diagnostic;
+use crate::parser::visit::{walk_statement, walk_statements, NameUsage, Visitor};
+use crate::parser::*;
+use crate::semantic::{SemanticModel, SymbolKind, CHAR, INTEGER};
+
+use super::bodies;
+
+/// Checks the names that statements store into: that the targets of `:=`, `:=:` and `read`
+/// are variables or parameters, that both sides of `:=:` have the same type, and that `read`
+/// only reads integers and characters.
+///
+/// A function's value is given with `return`; as in the interpreter, assigning to the
+/// function's name is an error.
+pub fn check(program: &Program, model: &SemanticModel, diagnostics: &mut Vec<Diagnostic>) {
+    let mut targets = Targets { model, diagnostics };
+    for body in bodies(program) {
+        walk_statements(&mut targets, &body.statements);
+    }
+}
+
+struct Targets<'a> {
+    model: &'a SemanticModel,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Visitor for Targets<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign { assignment } => self.swap(assignment),
+            Statement::For { init, update, .. } => {
+                for stat in [init, update] {
+                    if let ForStat::Assignment(assignment) = stat {
+                        self.swap(assignment);
+                    }
+                }
+            }
+            _ => {}
+        }
+        walk_statement(self, statement)
+    }
+
+    fn visit_name(&mut self, name: &Identifier, usage: NameUsage) {
+        let verb = match usage {
+            NameUsage::Assign => "assign to",
+            NameUsage::Swap => "swap",
+            NameUsage::Read => "read into",
+            NameUsage::Value | NameUsage::Call | NameUsage::Label => return,
+        };
+        let Some(symbol) = self.model.symbol_at(name.span) else {
+            return;
+        };
+        let kind = self.model.symbol(symbol).kind;
+        let what = match kind {
+            SymbolKind::Variable | SymbolKind::Parameter => None,
+            SymbolKind::Program => Some("program"),
+            SymbolKind::Function => Some("function"),
+            SymbolKind::Constant => Some("constant"),
+            SymbolKind::EnumLiteral => Some("enumeration literal"),
+            SymbolKind::Type => Some("type"),
+        };
+        if let Some(what) = what {
+            let mut message = format!("cannot {} {} '{}'", verb, what, name.name);
+            if kind == SymbolKind::Function && usage == NameUsage::Assign {
+                message.push_str("; use 'return' to give the function its value");
+            }
+            self.diagnostics.push(Diagnostic::error(message, name.span));
+            return;
+        }
+
+        if usage == NameUsage::Read {
+            match self.model.type_of(symbol) {
+                Some(INTEGER | CHAR) | None => {}
+                Some(ty) => {
+                    let ty = &self.model.symbol(ty).name;
+                    let message = format!("cannot read a value of type {} into '{}'", ty, name.name);
+                    self.diagnostics.push(Diagnostic::error(message, name.span));
+                }
+            }
+        }
+    }
+}
+
+impl Targets<'_> {
+    fn swap(&mut self, assignment: &Assignment) {
+        let Assignment::Swap { name1, name2 } = assignment else {
+            return;
+        };
+        let ty = |name: &Identifier| self.model.symbol_at(name.span).and_then(|s| self.model.type_of(s));
+        if let (Some(left), Some(right)) = (ty(name1), ty(name2)) {
+            if left != right {
+                let message = format!(
+                    "cannot swap '{}' of type {} with '{}' of type {}",
+                    name1.name,
+                    self.model.symbol(left).name,
+                    name2.name,
+                    self.model.symbol(right).name
+                );
+                self.diagnostics.push(Diagnostic::error(message, name1.span.until(name2.span)));
+            }
+        }
+    }
+}
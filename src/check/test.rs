@@ -0,0 +1,291 @@
+#[cfg(test)]
+mod tests {
+    use crate::check::{check, CheckOptions};
+    use crate::lexer::Lexer;
+    use crate::parser::{Parser, Program};
+    use crate::semantic::SemanticModel;
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false).parse().unwrap()
+    }
+
+    fn messages(source: &str, options: &CheckOptions) -> Vec<String> {
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        check(&program, &model, options).iter().map(|d| d.to_string()).collect()
+    }
+
+    /// A program declaring `low`, `high`, `color`, `c`, `n` and `x`, with `statement` as its body.
+    fn program(statement: &str) -> String {
+        format!(
+            "program p:\n\
+             const low = 1, high = low;\n\
+             type color = (red, green, blue);\n\
+             var c : color; n : integer; x : char;\n\
+             begin\n{}\nend p.",
+            statement
+        )
+    }
+
+    #[test]
+    fn test_case_labels() {
+        let source = program("case n of 1, 2..4: output(1); 3: output(2); 4..6: output(3); high: output(4) end");
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            [
+                "6:31: error: case label 3 overlaps 2..4",
+                "6:45: error: case label 4..6 overlaps 2..4",
+                "6:62: error: duplicate case label 1",
+            ]
+        );
+
+        let source = program("case n of 5..2: output(1); 'a': output(2); red..blue: output(3); c: output(4) end");
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            [
+                "6:11: warning: empty range 5..2",
+                "6:28: error: case label of type char does not match selector of type integer",
+                "6:44: error: case label of type color does not match selector of type integer",
+                "6:66: error: case label 'c' is not a constant",
+            ]
+        );
+
+        // constants are followed to enumeration literals
+        let source = "program p:\n\
+                      type color = (red, green, blue);\n\
+                      function f(c : color) : integer;\n\
+                      const first = red;\n\
+                      begin case c of first: return (1); red..green: return (2) otherwise return (3) end end f;\n\
+                      begin end p.";
+        assert_eq!(messages(source, &CheckOptions::default()), ["5:36: error: case label red..green overlaps red"]);
+        let source = program("case x of 'a'..'z': output(1); 'q': output(2) end");
+        assert_eq!(messages(&source, &CheckOptions::default()), ["6:32: error: case label 'q' overlaps 'a'..'z'"]);
+    }
+
+    #[test]
+    fn test_exhaustive_case() {
+        let exhaustive = CheckOptions { exhaustive_case: true };
+        let source = program("case c of red: output(1); blue: output(2) end");
+        assert!(messages(&source, &CheckOptions::default()).is_empty());
+        assert_eq!(
+            messages(&source, &exhaustive),
+            ["6:11: warning: case over color has no 'otherwise' and doesn't cover green"]
+        );
+
+        let source = program("case c of red: output(1); green..blue: output(2) end");
+        assert!(messages(&source, &exhaustive).is_empty());
+        let source = program("case c of red: output(1) otherwise output(2) end");
+        assert!(messages(&source, &exhaustive).is_empty());
+        let source = program("case n = 1 of true: output(1) end");
+        assert_eq!(
+            messages(&source, &exhaustive),
+            ["6:15: warning: case over boolean has no 'otherwise' and doesn't cover false"]
+        );
+        // integers are never covered, otherwise is up to the programmer
+        let source = program("case n of 1: output(1) end");
+        assert!(messages(&source, &exhaustive).is_empty());
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = program("loop if n > 1 then exit pool; while n > 0 do begin exit end");
+        assert_eq!(messages(&source, &CheckOptions::default()), ["6:52: error: 'exit' outside a loop"]);
+        let source = program("if n > 1 then return (1)");
+        assert_eq!(messages(&source, &CheckOptions::default()), ["6:15: error: 'return' in the main program"]);
+
+        // a function must return on every path, even one used for what it does
+        let source = "program p:\n\
+                      type color = (red, green);\n\
+                      function f(n : integer) : integer; begin output(n) end f;\n\
+                      function g(n : integer) : integer; begin if n > 0 then return (1) end g;\n\
+                      function h(c : color) : integer; begin case c of red: return (1); green: return (2) end end h;\n\
+                      function k(n : integer) : integer; begin loop if n > 0 then return (1) pool end k;\n\
+                      begin end p.";
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            [
+                "3:56: warning: function 'f' can reach its end without returning a value",
+                "4:71: warning: function 'g' can reach its end without returning a value"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_statements() {
+        let source = program("loop exit; n := 1; output(2) pool");
+        assert_eq!(messages(&source, &CheckOptions::default()), ["6:12: warning: unreachable statement"]);
+        let source = program("loop if n > 1 then exit else exit; output(2); ; n := 1 pool");
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            ["6:30: warning: statements after this 'exit' are unreachable"]
+        );
+        // an empty statement, as before 'end', isn't reported
+        let source = program("loop output(1); exit; pool");
+        assert!(messages(&source, &CheckOptions::default()).is_empty());
+        let source = program("loop output(1) pool; output(2)");
+        assert!(messages(&source, &CheckOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_calls() {
+        // 'f' calls 'g' before it is defined, and 'g' calls itself
+        let source = "program p:\n\
+                      var n : integer; x : char;\n\
+                      function f(a, b : integer; c : char) : integer; begin return (g(a)) end f;\n\
+                      function g(a : integer) : integer; begin return (g(a - 1)) end g;\n\
+                      begin\n\
+                      n := f(n, 1, x) + f(x, n = 1, 'a') + g(1, 2) + n(1) + integer(x) + h(1)\n\
+                      end p.";
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            [
+                "6:19: error: argument 'a' of 'f' expects integer, found char",
+                "6:19: error: argument 'b' of 'f' expects integer, found boolean",
+                "6:38: error: function 'g' takes 1 argument but 2 were supplied",
+                "6:48: error: 'n' is a variable, not a function",
+                "6:55: error: 'integer' is a type, not a function",
+            ]
+        );
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        let messages: Vec<String> = model.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, ["6:68: error: undeclared function 'h'"]);
+    }
+
+    #[test]
+    fn test_assignment_targets() {
+        let source = program("low := 1; red :=: c; color := c; read(n, x, c); for (high := 1; n < 2; ) output(n)");
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            [
+                "6:1: error: cannot assign to constant 'low'",
+                "6:11: error: cannot swap enumeration literal 'red'",
+                "6:22: error: cannot assign to type 'color'",
+                "6:45: error: cannot read a value of type color into 'c'",
+                "6:54: error: cannot assign to constant 'high'",
+            ]
+        );
+        let source = program("n :=: x; c :=: c; x :=: x");
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            ["6:1: error: cannot swap 'n' of type integer with 'x' of type char"]
+        );
+
+        // a function's value is given with 'return', never by assigning to its name
+        let source = "program p:\n\
+                      function f(n : integer) : integer; begin f := n; return (n) end f;\n\
+                      begin end p.";
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            ["2:42: error: cannot assign to function 'f'; use 'return' to give the function its value"]
+        );
+    }
+
+    #[test]
+    fn test_types() {
+        let source = program(
+            "n := x + 1; if n then output(-x, not n, chr(x)); \
+             while c = 1 do n := ord(c) * (1 - x); x := n; repeat until n or true",
+        );
+        assert_eq!(
+            messages(&source, &CheckOptions::default()),
+            [
+                "6:6: error: arithmetic expects integer, found char",
+                "6:16: error: a condition expects boolean, found integer",
+                "6:31: error: '-' expects integer, found char",
+                "6:38: error: 'not' expects boolean, found integer",
+                "6:45: error: 'chr' expects integer, found char",
+                "6:56: error: cannot compare color with integer",
+                "6:84: error: arithmetic expects integer, found char",
+                "6:88: error: cannot assign integer to 'x' of type char",
+                "6:109: error: 'or' expects boolean, found integer",
+            ]
+        );
+
+        // literals have no positions, so errors in them are at their statement's first name, or else
+        // at the program's
+        let source = "program p:\n\
+                      const low = 1;\n\
+                      type color = (red);\n\
+                      var v : low;\n\
+                      function f(n : integer) : char; begin output(1 + 'a', color); return (n + red) end f;\n\
+                      begin if 1 then output(1) end p.";
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            [
+                "1:9: error: a condition expects boolean, found integer",
+                "4:9: error: 'low' is a constant, not a type",
+                "5:55: error: arithmetic expects integer, found char",
+                "5:55: error: 'color' is a type, not a value",
+                "5:63: error: 'return' expects char, found integer",
+                "5:75: error: arithmetic expects integer, found color",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample_programs() {
+        for source in [
+            include_str!("./../../winzig_test_programs/winzig_02"),
+            include_str!("./../../winzig_test_programs/winzig_03"),
+        ] {
+            assert!(messages(source, &CheckOptions::default()).is_empty());
+        }
+        let source = include_str!("./../../winzig_test_programs/winzig_15");
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            ["16:5: warning: function 'GetNext' can reach its end without returning a value"]
+        );
+        // the functions called with 'd := f(x)' never return, and cases over integers can fall through
+        let source = include_str!("./../../winzig_test_programs/winzig_12");
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            [
+                "25:5: warning: function 'StoreA' can reach its end without returning a value",
+                "41:5: warning: function 'StoreT' can reach its end without returning a value",
+                "48:5: warning: function 'Store' can reach its end without returning a value",
+                "65:5: warning: function 'ElementA' can reach its end without returning a value",
+                "81:5: warning: function 'ElementT' can reach its end without returning a value",
+                "122:5: warning: function 'Merge' can reach its end without returning a value",
+                "137:5: warning: function 'MergeSort' can reach its end without returning a value",
+            ]
+        );
+        // 'n := n + 1' steps an enumeration with arithmetic
+        let source = include_str!("./../../winzig_test_programs/winzig_04");
+        assert_eq!(
+            messages(source, &CheckOptions::default()),
+            [
+                "29:32: error: cannot assign integer to 'n' of type Number",
+                "29:35: error: arithmetic expects integer, found Number",
+            ]
+        );
+        // no other sample has errors, those calling functions with 'd := f(x)' included
+        for source in [
+            include_str!("./../../winzig_test_programs/winzig_01"),
+            include_str!("./../../winzig_test_programs/winzig_02"),
+            include_str!("./../../winzig_test_programs/winzig_03"),
+            include_str!("./../../winzig_test_programs/winzig_05"),
+            include_str!("./../../winzig_test_programs/winzig_06"),
+            include_str!("./../../winzig_test_programs/winzig_07"),
+            include_str!("./../../winzig_test_programs/winzig_08"),
+            include_str!("./../../winzig_test_programs/winzig_09"),
+            include_str!("./../../winzig_test_programs/winzig_10"),
+            include_str!("./../../winzig_test_programs/winzig_11"),
+            include_str!("./../../winzig_test_programs/winzig_12"),
+            include_str!("./../../winzig_test_programs/winzig_13"),
+            include_str!("./../../winzig_test_programs/winzig_14"),
+            include_str!("./../../winzig_test_programs/winzig_15"),
+        ] {
+            let program = parse(source);
+            let model = SemanticModel::build(&program);
+            assert!(model.diagnostics().is_empty());
+            assert!(check(&program, &model, &CheckOptions::default()).iter().all(|d| !d.is_error()));
+        }
+        let source = include_str!("./../../winzig_test_programs/winzig_11");
+        let warnings = messages(source, &CheckOptions { exhaustive_case: true });
+        let warnings: Vec<&String> = warnings.iter().filter(|w| w.contains("case over")).collect();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].ends_with("case over Range has no 'otherwise' and doesn't cover LessThan100, Above500"));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::parser::*;
+use crate::semantic::{SemanticModel, SymbolId, BOOLEAN, CHAR, INTEGER};
+
+/// The type of `expression`, as the symbol of a type, or `None` when it can't be known,
+/// e.g. because a name is undeclared. Operators give the type of their result whatever
+/// their operands are.
+pub fn expression_type(model: &SemanticModel, expression: &Expression) -> Option<SymbolId> {
+    match expression {
+        Expression::Le { .. }
+        | Expression::Lt { .. }
+        | Expression::Ge { .. }
+        | Expression::Gt { .. }
+        | Expression::Eq { .. }
+        | Expression::Ne { .. } => Some(BOOLEAN),
+        Expression::Term(term) => term_type(model, term),
+    }
+}
+
+pub fn term_type(model: &SemanticModel, term: &Term) -> Option<SymbolId> {
+    match term {
+        Term::Add { .. } | Term::Subtract { .. } => Some(INTEGER),
+        Term::Or { .. } => Some(BOOLEAN),
+        Term::Factor(factor) => factor_type(model, factor),
+    }
+}
+
+pub fn factor_type(model: &SemanticModel, factor: &Factor) -> Option<SymbolId> {
+    match factor {
+        Factor::Multiply { .. } | Factor::Divide { .. } | Factor::Mod { .. } => Some(INTEGER),
+        Factor::And { .. } => Some(BOOLEAN),
+        Factor::Primary(primary) => primary_type(model, primary),
+    }
+}
+
+pub fn primary_type(model: &SemanticModel, primary: &Primary) -> Option<SymbolId> {
+    match primary {
+        Primary::Negate { .. } | Primary::Integer(_) | Primary::Ord { .. } => Some(INTEGER),
+        Primary::Not { .. } | Primary::Eof => Some(BOOLEAN),
+        Primary::Char(_) | Primary::Chr { .. } => Some(CHAR),
+        Primary::Name(name) | Primary::Call { name, .. } => model.symbol_at(name.span).and_then(|s| model.type_of(s)),
+        Primary::Expression(exp) => expression_type(model, exp),
+        Primary::Succ { exp } | Primary::Pred { exp } => expression_type(model, exp),
+    }
+}
@@ -0,0 +1,231 @@
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::visit::{
+    walk_expression, walk_factor, walk_primary, walk_statement, walk_statements, walk_term, NameUsage, Visitor,
+};
+use crate::parser::*;
+use crate::semantic::{SemanticModel, SymbolId, SymbolKind, BOOLEAN, INTEGER};
+
+use super::types::{expression_type, factor_type, primary_type, term_type};
+use super::{describe, FirstSpan};
+
+/// Checks that values have the types the places they are used in expect: that declarations
+/// name types, that the names read as values are values, that arithmetic and `-` are given
+/// integers and `and`, `or`, `not` and conditions booleans, that both sides of a comparison
+/// have the same type, and that `:=` and `return` give the type of the variable or function.
+/// Values whose type can't be known, e.g. because a name is undeclared, are not checked.
+///
+/// Expressions have no positions, so errors are at the first name of the operand in error,
+/// or else at the first name or keyword of its statement, or else at the name of the
+/// function or program.
+pub fn check(program: &Program, model: &SemanticModel, diagnostics: &mut Vec<Diagnostic>) {
+    let mut typing = Typing { model, return_type: None, statement: None, fallback: program.name.span, diagnostics };
+    typing.declarations(&program.dclns);
+    for function in program.functions() {
+        for var in &function.params.params {
+            typing.type_name(&var.typename);
+        }
+        typing.type_name(&function.return_type);
+        typing.declarations(&function.dclns);
+        typing.return_type = model.symbol_at(function.name.span).and_then(|f| model.type_of(f));
+        typing.fallback = function.name.span;
+        walk_statements(&mut typing, &function.body.statements);
+    }
+    typing.return_type = None;
+    typing.fallback = program.name.span;
+    walk_statements(&mut typing, &program.body.statements);
+}
+
+struct Typing<'a> {
+    model: &'a SemanticModel,
+    // of the function being checked, `None` in the program's body
+    return_type: Option<SymbolId>,
+    // the first name or keyword of the statement being checked
+    statement: Option<Span>,
+    fallback: Span,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Visitor for Typing<'_> {
+    fn visit_statement(&mut self, statement: &Statement) {
+        let mut first = FirstSpan(None);
+        first.visit_statement(statement);
+        let outer = std::mem::replace(&mut self.statement, first.0);
+        match statement {
+            Statement::Assign { assignment } => self.assignment(assignment),
+            Statement::If { cond, .. } | Statement::While { cond, .. } | Statement::Repeat { cond, .. } => {
+                self.condition(cond)
+            }
+            Statement::For { init, cond, update, .. } => {
+                for stat in [init, update] {
+                    if let ForStat::Assignment(assignment) = stat {
+                        self.assignment(assignment);
+                    }
+                }
+                if let ForExp::Expression(cond) = cond {
+                    self.condition(cond);
+                }
+            }
+            Statement::Return { exp, span } => self.returned(exp, *span),
+            _ => {}
+        }
+        walk_statement(self, statement);
+        self.statement = outer;
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Le { left, right }
+            | Expression::Lt { left, right }
+            | Expression::Ge { left, right }
+            | Expression::Gt { left, right }
+            | Expression::Eq { left, right }
+            | Expression::Ne { left, right } => {
+                let types = (term_type(self.model, left), term_type(self.model, right));
+                if let (Some(left_type), Some(right_type)) = types {
+                    if left_type != right_type {
+                        let message = format!("cannot compare {} with {}", self.name(left_type), self.name(right_type));
+                        self.report(message, |first| first.visit_term(left));
+                    }
+                }
+            }
+            Expression::Term(_) => {}
+        }
+        walk_expression(self, expression)
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        let (left, right, expected, operator) = match term {
+            Term::Add { left, right } | Term::Subtract { left, right } => (left, right, INTEGER, "arithmetic"),
+            Term::Or { left, right } => (left, right, BOOLEAN, "'or'"),
+            Term::Factor(_) => return walk_term(self, term),
+        };
+        self.expect(term_type(self.model, left), expected, operator, |first| first.visit_term(left));
+        self.expect(factor_type(self.model, right), expected, operator, |first| first.visit_factor(right));
+        walk_term(self, term)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) {
+        let (left, right, expected, operator) = match factor {
+            Factor::Multiply { left, right } | Factor::Divide { left, right } | Factor::Mod { left, right } => {
+                (left, right, INTEGER, "arithmetic")
+            }
+            Factor::And { left, right } => (left, right, BOOLEAN, "'and'"),
+            Factor::Primary(_) => return walk_factor(self, factor),
+        };
+        self.expect(factor_type(self.model, left), expected, operator, |first| first.visit_factor(left));
+        self.expect(primary_type(self.model, right), expected, operator, |first| first.visit_primary(right));
+        walk_factor(self, factor)
+    }
+
+    fn visit_primary(&mut self, primary: &Primary) {
+        match primary {
+            Primary::Negate { primary: operand } => {
+                self.expect(primary_type(self.model, operand), INTEGER, "'-'", |first| first.visit_primary(operand))
+            }
+            Primary::Not { primary: operand } => {
+                self.expect(primary_type(self.model, operand), BOOLEAN, "'not'", |first| first.visit_primary(operand))
+            }
+            Primary::Chr { exp } => {
+                self.expect(expression_type(self.model, exp), INTEGER, "'chr'", |first| first.visit_expression(exp))
+            }
+            _ => {}
+        }
+        walk_primary(self, primary)
+    }
+
+    fn visit_name(&mut self, name: &Identifier, usage: NameUsage) {
+        if usage != NameUsage::Value {
+            return;
+        }
+        let Some(symbol) = self.model.symbol_at(name.span) else {
+            return;
+        };
+        match self.model.symbol(symbol).kind {
+            SymbolKind::Variable | SymbolKind::Parameter | SymbolKind::Constant | SymbolKind::EnumLiteral => {}
+            kind => {
+                let message = format!("'{}' is {}, not a value", name.name, describe(kind));
+                self.diagnostics.push(Diagnostic::error(message, name.span));
+            }
+        }
+    }
+}
+
+impl Typing<'_> {
+    fn declarations(&mut self, dclns: &Dclns) {
+        for var in &dclns.vars {
+            self.type_name(&var.typename);
+        }
+    }
+
+    /// Checks that the name a variable, parameter or function is declared with is a type.
+    fn type_name(&mut self, name: &Identifier) {
+        let Some(symbol) = self.model.symbol_at(name.span) else {
+            return;
+        };
+        let kind = self.model.symbol(symbol).kind;
+        if kind != SymbolKind::Type {
+            let message = format!("'{}' is {}, not a type", name.name, describe(kind));
+            self.diagnostics.push(Diagnostic::error(message, name.span));
+        }
+    }
+
+    fn assignment(&mut self, assignment: &Assignment) {
+        let Assignment::Assignment { name, exp } = assignment else {
+            return;
+        };
+        // storing into what isn't a variable is reported with the targets
+        let target = self.model.symbol_at(name.span).filter(|s| {
+            matches!(self.model.symbol(*s).kind, SymbolKind::Variable | SymbolKind::Parameter)
+        });
+        let types = (target.and_then(|s| self.model.type_of(s)), expression_type(self.model, exp));
+        if let (Some(expected), Some(found)) = types {
+            if expected != found {
+                let message =
+                    format!("cannot assign {} to '{}' of type {}", self.name(found), name.name, self.name(expected));
+                self.diagnostics.push(Diagnostic::error(message, name.span));
+            }
+        }
+    }
+
+    fn condition(&mut self, cond: &Expression) {
+        self.expect(expression_type(self.model, cond), BOOLEAN, "a condition", |first| first.visit_expression(cond));
+    }
+
+    fn returned(&mut self, exp: &Expression, span: Span) {
+        let types = (self.return_type, expression_type(self.model, exp));
+        if let (Some(expected), Some(found)) = types {
+            if expected != found {
+                let message = format!("'return' expects {}, found {}", self.name(expected), self.name(found));
+                self.diagnostics.push(Diagnostic::error(message, span));
+            }
+        }
+    }
+
+    fn expect(
+        &mut self,
+        found: Option<SymbolId>,
+        expected: SymbolId,
+        context: &str,
+        operand: impl FnOnce(&mut FirstSpan),
+    ) {
+        match found {
+            Some(found) if found != expected => {
+                let message = format!("{} expects {}, found {}", context, self.name(expected), self.name(found));
+                self.report(message, operand);
+            }
+            _ => {}
+        }
+    }
+
+    /// Reports an error at the first name of an operand, found by `operand`.
+    fn report(&mut self, message: String, operand: impl FnOnce(&mut FirstSpan)) {
+        let mut first = FirstSpan(None);
+        operand(&mut first);
+        let span = first.0.or(self.statement).unwrap_or(self.fallback);
+        self.diagnostics.push(Diagnostic::error(message, span));
+    }
+
+    fn name(&self, ty: SymbolId) -> &str {
+        &self.model.symbol(ty).name
+    }
+}
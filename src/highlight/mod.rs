@@ -0,0 +1,149 @@
+use crate::lexer::{SpannedToken, Token};
+use crate::semantic::{SemanticModel, SymbolKind};
+
+mod test;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightClass {
+    Keyword,
+    Operator,
+    Integer,
+    Char,
+    String,
+    Comment,
+    Identifier,
+    Error,
+
+    // semantic classes, only used once names are resolved
+    Function,
+    EnumLiteral,
+    Constant,
+    Type,
+}
+
+impl HighlightClass {
+    pub fn css_class(&self) -> &'static str {
+        match self {
+            HighlightClass::Keyword => "wz-keyword",
+            HighlightClass::Operator => "wz-operator",
+            HighlightClass::Integer => "wz-integer",
+            HighlightClass::Char => "wz-char",
+            HighlightClass::String => "wz-string",
+            HighlightClass::Comment => "wz-comment",
+            HighlightClass::Identifier => "wz-identifier",
+            HighlightClass::Error => "wz-error",
+            HighlightClass::Function => "wz-function",
+            HighlightClass::EnumLiteral => "wz-enum-literal",
+            HighlightClass::Constant => "wz-constant",
+            HighlightClass::Type => "wz-type",
+        }
+    }
+
+    pub fn ansi_code(&self) -> &'static str {
+        match self {
+            HighlightClass::Keyword => "1;34",
+            HighlightClass::Operator => "36",
+            HighlightClass::Integer => "35",
+            HighlightClass::Char => "32",
+            HighlightClass::String => "32",
+            HighlightClass::Comment => "90",
+            HighlightClass::Identifier => "0",
+            HighlightClass::Error => "4;31",
+            HighlightClass::Function => "33",
+            HighlightClass::EnumLiteral => "1;35",
+            HighlightClass::Constant => "1;36",
+            HighlightClass::Type => "1;32",
+        }
+    }
+}
+
+const STYLE: &str = "\
+body { background: #fdfdfd; }
+pre.winzig { font-family: monospace; font-size: 14px; color: #222; }
+.wz-keyword { color: #0033b3; font-weight: bold; }
+.wz-operator { color: #00627a; }
+.wz-integer { color: #1750eb; }
+.wz-char, .wz-string { color: #067d17; }
+.wz-comment { color: #8c8c8c; font-style: italic; }
+.wz-error { color: #d00; text-decoration: underline wavy; }
+.wz-function { color: #8a6d00; }
+.wz-enum-literal { color: #871094; font-style: italic; }
+.wz-constant { color: #871094; font-weight: bold; }
+.wz-type { color: #008080; }
+";
+
+/// Semantic class of what a name declares or refers to: functions, enumeration literals,
+/// constants and types. Variables, parameters and the program name have none.
+fn semantic_class(kind: SymbolKind) -> Option<HighlightClass> {
+    match kind {
+        SymbolKind::Function => Some(HighlightClass::Function),
+        SymbolKind::EnumLiteral => Some(HighlightClass::EnumLiteral),
+        SymbolKind::Constant => Some(HighlightClass::Constant),
+        SymbolKind::Type => Some(HighlightClass::Type),
+        SymbolKind::Program | SymbolKind::Variable | SymbolKind::Parameter => None,
+    }
+}
+
+/// Class of a token, or `None` for whitespace, newlines and punctuation. With a `model`,
+/// each identifier is classed by the symbol it resolves to in its scope.
+pub fn classify(token: &SpannedToken, model: Option<&SemanticModel>) -> Option<HighlightClass> {
+    match &token.token {
+        Token::Identifier(_) => Some(
+            model
+                .and_then(|model| model.symbol_at(token.span).and_then(|s| semantic_class(model.symbol(s).kind)))
+                .unwrap_or(HighlightClass::Identifier),
+        ),
+        Token::Keyword(_) => Some(HighlightClass::Keyword),
+        Token::Operator(_) => Some(HighlightClass::Operator),
+        Token::Integer(_) => Some(HighlightClass::Integer),
+        Token::Char(_) => Some(HighlightClass::Char),
+        Token::String(_) => Some(HighlightClass::String),
+        Token::Comment(_, _) => Some(HighlightClass::Comment),
+        Token::Error(_) => Some(HighlightClass::Error),
+        _ => None,
+    }
+}
+
+/// Renders the source as a standalone HTML page, one `<span>` per classified token.
+pub fn to_html(tokens: &[SpannedToken], model: Option<&SemanticModel>, title: &str) -> String {
+    let mut code = String::new();
+    for t in tokens {
+        let text = escape_html(&t.lexeme);
+        match classify(t, model) {
+            Some(class) => code.push_str(&format!("<span class=\"{}\">{}</span>", class.css_class(), text)),
+            None => code.push_str(&text),
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<pre class=\"winzig\"><code>{}</code></pre>\n</body>\n</html>\n",
+        escape_html(title),
+        STYLE,
+        code
+    )
+}
+
+/// Renders the source with ANSI colour escapes for terminal output.
+pub fn to_ansi(tokens: &[SpannedToken], model: Option<&SemanticModel>) -> String {
+    let mut out = String::new();
+    for t in tokens {
+        match classify(t, model) {
+            Some(HighlightClass::Identifier) | None => out.push_str(&t.lexeme),
+            Some(class) => {
+                // colour each line separately so multi-line comments survive paging
+                let lines: Vec<String> = t.lexeme
+                    .split('\n')
+                    .map(|line| format!("\x1b[{}m{}\x1b[0m", class.ansi_code(), line))
+                    .collect();
+                out.push_str(&lines.join("\n"));
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
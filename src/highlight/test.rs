@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use crate::highlight::{classify, to_ansi, to_html, HighlightClass};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::semantic::SemanticModel;
+
+    #[test]
+    fn test_html() {
+        let mut lexer = Lexer::new("if a < 'x' then output(\"<&>\") # hi".to_string());
+        let tokens = lexer.tokenize();
+        let html = to_html(&tokens, None, "test");
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>test</title>"));
+        assert!(html.contains(
+            "<code><span class=\"wz-keyword\">if</span> <span class=\"wz-identifier\">a</span> \
+             <span class=\"wz-operator\">&lt;</span> <span class=\"wz-char\">'x'</span> \
+             <span class=\"wz-keyword\">then</span> <span class=\"wz-keyword\">output</span>(\
+             <span class=\"wz-string\">&quot;&lt;&amp;&gt;&quot;</span>) <span class=\"wz-comment\"># hi</span></code>"
+        ));
+    }
+
+    #[test]
+    fn test_ansi() {
+        let mut lexer = Lexer::new("x := 1 {a\nb}".to_string());
+        let tokens = lexer.tokenize();
+        let ansi = to_ansi(&tokens, None);
+
+        assert_eq!(ansi, "x \x1b[36m:=\x1b[0m \x1b[35m1\x1b[0m \x1b[90m{a\x1b[0m\n\x1b[90mb}\x1b[0m");
+    }
+
+    #[test]
+    fn test_semantic_classes() {
+        let source = include_str!("./../../winzig_test_programs/winzig_10");
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize();
+        let model = SemanticModel::build(&Parser::with_spans(tokens.clone(), false).parse().unwrap());
+        let class = |name: &str| {
+            let token = tokens.iter().find(|t| t.lexeme == name).unwrap();
+            classify(token, Some(&model)).unwrap()
+        };
+        assert_eq!(class("print"), HighlightClass::Function);
+        assert_eq!(class("red"), HighlightClass::EnumLiteral);
+        assert_eq!(class("color"), HighlightClass::Type);
+        assert_eq!(class("C"), HighlightClass::Identifier);
+
+        let html = to_html(&tokens, Some(&model), "winzig_10");
+        assert!(html.contains("<span class=\"wz-identifier\">C</span> <span class=\"wz-operator\">:=</span> \
+                               <span class=\"wz-function\">print</span>(<span class=\"wz-enum-literal\">red</span>)"));
+    }
+
+    #[test]
+    fn test_semantic_classes_follow_scope() {
+        // the parameter 'color' hides the type of the same name
+        let source = "program p: type color = (red, blue); function f(color : integer) : integer; \
+                      begin return (color) end f; begin output(f(1)) end p.";
+        let mut lexer = Lexer::new(source.to_string());
+        let tokens = lexer.tokenize();
+        let model = SemanticModel::build(&Parser::with_spans(tokens.clone(), false).parse().unwrap());
+        let classes: Vec<HighlightClass> = tokens
+            .iter()
+            .filter(|t| t.lexeme == "color")
+            .map(|t| classify(t, Some(&model)).unwrap())
+            .collect();
+        assert_eq!(classes, [HighlightClass::Type, HighlightClass::Identifier, HighlightClass::Identifier]);
+    }
+}
@@ -0,0 +1,317 @@
+use std::ops::Range;
+
+use crate::lexer::{Keyword, LexerConfig, Operator, SpannedToken, Token};
+use crate::parser::{Func, Parser, Program};
+use crate::semantic::{ScopeId, SemanticModel, SymbolKind};
+
+use super::tokens_before;
+
+const KEYWORDS: [Keyword; 33] = [
+    Keyword::Program,
+    Keyword::Const,
+    Keyword::Type,
+    Keyword::Var,
+    Keyword::Function,
+    Keyword::Begin,
+    Keyword::End,
+    Keyword::Output,
+    Keyword::If,
+    Keyword::Then,
+    Keyword::Else,
+    Keyword::While,
+    Keyword::Do,
+    Keyword::Repeat,
+    Keyword::Until,
+    Keyword::For,
+    Keyword::Loop,
+    Keyword::Pool,
+    Keyword::Case,
+    Keyword::Of,
+    Keyword::Otherwise,
+    Keyword::Read,
+    Keyword::Exit,
+    Keyword::Return,
+    Keyword::Not,
+    Keyword::And,
+    Keyword::Or,
+    Keyword::Mod,
+    Keyword::Eof,
+    Keyword::Succ,
+    Keyword::Pred,
+    Keyword::Chr,
+    Keyword::Ord,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Variable,
+    Parameter,
+    Constant,
+    EnumLiteral,
+    Type,
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// The declaration of a name, e.g. `n : integer`.
+    pub detail: Option<String>,
+}
+
+/// Keywords and names that may be written at `offset`. The word being typed is not used
+/// as a filter, editors do their own (fuzzy) matching. Names come from `model`, which
+/// may be stale: the text is usually incomplete while completions are requested.
+pub fn completions(
+    text: &str,
+    offset: usize,
+    model: Option<&SemanticModel>,
+    config: &LexerConfig,
+) -> Vec<Completion> {
+    let Some((tokens, _)) = tokens_before(text, offset, config) else {
+        return Vec::new();
+    };
+    // What may follow is what the parser looked for at the end of the prefix; it looked for
+    // nothing there if the prefix already contains an error
+    let names = name_context(&tokens).kinds();
+    let scope = enclosing_function(&tokens);
+    let mut parser = Parser::with_spans(tokens, false);
+    let expected = match parser.parse() {
+        Ok(_) => Vec::new(),
+        Err(_) => parser.expected(),
+    };
+    let valid = |token: Token| expected.contains(&token);
+
+    let mut completions = Vec::new();
+    if let Some(model) = model.filter(|_| valid(Token::Identifier(String::new()))) {
+        let scope = scope.and_then(|f| model.function_scope(&f)).unwrap_or(ScopeId::Global);
+        for id in model.visible_symbols(scope) {
+            let symbol = model.symbol(id);
+            if let Some(kind) = completion_kind(symbol.kind).filter(|k| names.contains(k)) {
+                completions.push(Completion { label: symbol.name.clone(), kind, detail: Some(symbol.detail.clone()) });
+            }
+        }
+    }
+    for keyword in KEYWORDS {
+        if valid(Token::Keyword(keyword.clone())) {
+            completions.push(Completion { label: keyword.as_str().to_string(), kind: CompletionKind::Keyword, detail: None });
+        }
+    }
+    completions
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHelp {
+    /// The function header, as in `function Merge(low, middle, high : integer) : integer`.
+    pub label: String,
+    pub parameters: Vec<ParameterInfo>,
+    /// Index into `parameters` of the argument at the cursor; may be past the end when
+    /// too many arguments are written.
+    pub active_parameter: usize,
+    pub documentation: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterInfo {
+    pub name: String,
+    pub type_name: String,
+    /// Where the name appears in the signature label.
+    pub range: Range<usize>,
+}
+
+/// The function whose argument list the cursor is in, for a call `name(...` that is
+/// still being written. `program` may be stale.
+pub fn signature_help(text: &str, offset: usize, program: &Program, config: &LexerConfig) -> Option<SignatureHelp> {
+    let (tokens, _) = tokens_before(text, offset, config)?;
+    let mut depth = 0;
+    let mut commas = 0;
+    for (i, t) in tokens.iter().enumerate().rev() {
+        match &t.token {
+            Token::RightParen => depth += 1,
+            Token::LeftParen if depth > 0 => depth -= 1,
+            Token::LeftParen => {
+                let callee = i.checked_sub(1).map(|j| &tokens[j].token);
+                if let Some(Token::Identifier(name)) = callee {
+                    if let Some(func) = program.functions().iter().find(|f| f.name.key == *name) {
+                        return Some(signature(func, commas));
+                    }
+                }
+                // an argument of some outer call, or a parenthesized expression
+                commas = 0;
+            }
+            Token::Comma if depth == 0 => commas += 1,
+            Token::Semicolon => return None,
+            Token::Keyword(keyword) if !is_expression_keyword(keyword) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn signature(func: &Func, active_parameter: usize) -> SignatureHelp {
+    let mut label = format!("function {}(", func.name.name);
+    let mut parameters = Vec::new();
+    for (i, var) in func.params.params.iter().enumerate() {
+        if i > 0 {
+            label.push_str("; ");
+        }
+        for (j, name) in var.names.iter().enumerate() {
+            if j > 0 {
+                label.push_str(", ");
+            }
+            let start = label.len();
+            label.push_str(&name.name);
+            parameters.push(ParameterInfo {
+                name: name.name.clone(),
+                type_name: var.typename.name.clone(),
+                range: start..label.len(),
+            });
+        }
+        label.push_str(" : ");
+        label.push_str(&var.typename.name);
+    }
+    label.push_str(") : ");
+    label.push_str(&func.return_type.name);
+    SignatureHelp { label, parameters, active_parameter, documentation: func.doc.clone() }
+}
+
+fn is_expression_keyword(keyword: &Keyword) -> bool {
+    matches!(
+        keyword,
+        Keyword::Not
+            | Keyword::And
+            | Keyword::Or
+            | Keyword::Mod
+            | Keyword::Eof
+            | Keyword::Succ
+            | Keyword::Pred
+            | Keyword::Chr
+            | Keyword::Ord
+    )
+}
+
+/// What kind of name may be written next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NameContext {
+    /// A name being declared, nothing to complete.
+    Declaration,
+    Type,
+    /// A `const` value or `case` label.
+    Constant,
+    /// The target of an assignment, swap or `read`.
+    Variable,
+    /// An operand in an expression.
+    Value,
+}
+
+impl NameContext {
+    fn kinds(&self) -> &'static [CompletionKind] {
+        match self {
+            NameContext::Declaration => &[],
+            NameContext::Type => &[CompletionKind::Type],
+            NameContext::Constant => &[CompletionKind::Constant, CompletionKind::EnumLiteral],
+            NameContext::Variable => &[CompletionKind::Variable, CompletionKind::Parameter],
+            NameContext::Value => &[
+                CompletionKind::Variable,
+                CompletionKind::Parameter,
+                CompletionKind::Constant,
+                CompletionKind::EnumLiteral,
+                CompletionKind::Function,
+            ],
+        }
+    }
+}
+
+fn name_context(tokens: &[SpannedToken]) -> NameContext {
+    // blocks opened by 'begin', 'case', 'repeat' and 'loop' that are still open
+    let mut blocks = Vec::new();
+    // positions of the '(' that are still open
+    let mut parens = Vec::new();
+    for (i, t) in tokens.iter().enumerate() {
+        match &t.token {
+            Token::Keyword(keyword @ (Keyword::Begin | Keyword::Case | Keyword::Repeat | Keyword::Loop)) => {
+                blocks.push(keyword.clone())
+            }
+            Token::Keyword(Keyword::End | Keyword::Until | Keyword::Pool) => {
+                blocks.pop();
+            }
+            Token::LeftParen => parens.push(i),
+            Token::RightParen => {
+                parens.pop();
+            }
+            _ => {}
+        }
+    }
+    let previous = tokens.last().map(|t| &t.token);
+    let before_paren = parens.last().and_then(|i| i.checked_sub(1)).map(|i| &tokens[i].token);
+
+    if blocks.is_empty() {
+        return match previous {
+            Some(Token::Colon) => NameContext::Type,
+            Some(Token::Operator(Operator::Equal)) => NameContext::Constant,
+            _ => NameContext::Declaration,
+        };
+    }
+    match previous {
+        Some(Token::LeftParen | Token::Comma) if before_paren == Some(&Token::Keyword(Keyword::Read)) => {
+            NameContext::Variable
+        }
+        Some(Token::LeftParen) if before_paren == Some(&Token::Keyword(Keyword::For)) => NameContext::Variable,
+        Some(Token::Keyword(Keyword::Of) | Token::Dots) => NameContext::Constant,
+        Some(Token::Comma) if parens.is_empty() => NameContext::Constant,
+        Some(Token::Semicolon) if parens.is_empty() && blocks.last() == Some(&Keyword::Case) => NameContext::Constant,
+        Some(Token::Semicolon) if !parens.is_empty() => NameContext::Value,
+        Some(Token::Operator(Operator::Swap)) => NameContext::Variable,
+        Some(
+            Token::Semicolon
+            | Token::Colon
+            | Token::Keyword(
+                Keyword::Begin
+                | Keyword::Then
+                | Keyword::Else
+                | Keyword::Do
+                | Keyword::Repeat
+                | Keyword::Loop
+                | Keyword::Otherwise,
+            ),
+        ) => NameContext::Variable,
+        _ => NameContext::Value,
+    }
+}
+
+/// The function whose header or body the tokens end in.
+fn enclosing_function(tokens: &[SpannedToken]) -> Option<String> {
+    let mut function = None;
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).map(|t| &t.token);
+        match (&t.token, next) {
+            (Token::Keyword(Keyword::Function), Some(Token::Identifier(name))) => function = Some(name.clone()),
+            (Token::Keyword(Keyword::Begin | Keyword::Case | Keyword::Repeat | Keyword::Loop), _) => depth += 1,
+            (Token::Keyword(Keyword::Until | Keyword::Pool), _) => depth -= 1,
+            (Token::Keyword(Keyword::End), next) => {
+                depth -= 1;
+                // 'end f' closes the body of f
+                if depth == 0 && matches!(next, Some(Token::Identifier(name)) if Some(name) == function.as_ref()) {
+                    function = None;
+                }
+            }
+            _ => {}
+        }
+    }
+    function
+}
+
+fn completion_kind(kind: SymbolKind) -> Option<CompletionKind> {
+    match kind {
+        SymbolKind::Program => None,
+        SymbolKind::Function => Some(CompletionKind::Function),
+        SymbolKind::Parameter => Some(CompletionKind::Parameter),
+        SymbolKind::Variable => Some(CompletionKind::Variable),
+        SymbolKind::Constant => Some(CompletionKind::Constant),
+        SymbolKind::EnumLiteral => Some(CompletionKind::EnumLiteral),
+        SymbolKind::Type => Some(CompletionKind::Type),
+    }
+}
@@ -0,0 +1,75 @@
+use crate::diagnostic::Span;
+use crate::lexer::{Keyword, Lexer, LexerConfig, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldKind {
+    /// `begin ... end`
+    Block,
+    /// `case ... end`
+    Case,
+    /// `repeat ... until`
+    Repeat,
+    /// `loop ... pool`
+    Loop,
+    /// `function ... end Name`
+    Function,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoldingRange {
+    /// From the opening keyword to the closing keyword, or to the name after `end` for functions.
+    pub span: Span,
+    pub kind: FoldKind,
+}
+
+/// The regions that can be folded, found from the keywords alone so that they are
+/// available while the text doesn't parse. Unmatched keywords are skipped. Ranges are
+/// ordered by where they end.
+pub fn folding_ranges(text: &str, config: &LexerConfig) -> Vec<FoldingRange> {
+    let mut lexer = Lexer::with_config(text.to_string(), config.clone());
+    let tokens: Vec<_> = lexer.tokenize().into_iter().filter(|t| !t.token.is_trivia()).collect();
+    let mut ranges = Vec::new();
+    let mut open: Vec<(FoldKind, Span)> = Vec::new();
+    let mut function: Option<Span> = None;
+    for (i, t) in tokens.iter().enumerate() {
+        let fold = match &t.token {
+            Token::Keyword(Keyword::Function) if open.is_empty() => {
+                function = Some(t.span);
+                None
+            }
+            Token::Keyword(Keyword::Begin) => Some((FoldKind::Block, true)),
+            Token::Keyword(Keyword::Case) => Some((FoldKind::Case, true)),
+            Token::Keyword(Keyword::Repeat) => Some((FoldKind::Repeat, true)),
+            Token::Keyword(Keyword::Loop) => Some((FoldKind::Loop, true)),
+            Token::Keyword(Keyword::End) => match open.last() {
+                Some((kind @ (FoldKind::Block | FoldKind::Case), _)) => Some((*kind, false)),
+                _ => None,
+            },
+            Token::Keyword(Keyword::Until) => Some((FoldKind::Repeat, false)),
+            Token::Keyword(Keyword::Pool) => Some((FoldKind::Loop, false)),
+            _ => None,
+        };
+        let Some((kind, opens)) = fold else {
+            continue;
+        };
+        if opens {
+            open.push((kind, t.span));
+            continue;
+        }
+        if open.last().map(|(k, _)| *k) != Some(kind) {
+            continue;
+        }
+        let (_, start) = open.pop().unwrap();
+        ranges.push(FoldingRange { span: start.until(t.span), kind });
+        // the end of a function body is followed by the function's name
+        if open.is_empty() && kind == FoldKind::Block {
+            if let (Some(start), Some(name)) = (function, tokens.get(i + 1)) {
+                if matches!(name.token, Token::Identifier(_)) {
+                    ranges.push(FoldingRange { span: start.until(name.span), kind: FoldKind::Function });
+                }
+                function = None;
+            }
+        }
+    }
+    ranges
+}
@@ -0,0 +1,76 @@
+//! Editor features over source text that is usually in the middle of being edited, and
+//! so often doesn't parse: they look at the tokens before the cursor and take names
+//! from the last program that did parse.
+
+use crate::diagnostic::Span;
+use crate::lexer::{CommentKind, Lexer, LexerConfig, SpannedToken, Token};
+
+pub mod completion;
+pub mod folding;
+pub mod outline;
+pub mod rename;
+pub mod semantic_tokens;
+mod test;
+
+/// Replacement of the text in `span`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub new_text: String,
+}
+
+/// Applies edits that don't overlap, in any order.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|e| e.span.start);
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    for edit in edits {
+        result.push_str(&text[copied..edit.span.start]);
+        result.push_str(&edit.new_text);
+        copied = edit.span.end;
+    }
+    result.push_str(&text[copied..]);
+    result
+}
+
+/// Byte offset of a 1-based line and column, counted in characters as in `Span`.
+pub fn offset_at(text: &str, line: usize, column: usize) -> Option<usize> {
+    let start = if line == 1 {
+        0
+    } else {
+        text.match_indices('\n').nth(line.checked_sub(2)?)?.0 + 1
+    };
+    let line_text = text[start..].split('\n').next().unwrap_or("");
+    match line_text.char_indices().nth(column.checked_sub(1)?) {
+        Some((i, _)) => Some(start + i),
+        None if column - 1 == line_text.chars().count() => Some(start + line_text.len()),
+        None => None,
+    }
+}
+
+/// The significant tokens before `offset`, and the identifier or keyword being typed at
+/// `offset`, if any. `None` when the cursor is inside a comment or a literal.
+fn tokens_before(
+    text: &str,
+    offset: usize,
+    config: &LexerConfig,
+) -> Option<(Vec<SpannedToken>, Option<SpannedToken>)> {
+    let offset = offset.min(text.len());
+    let mut lexer = Lexer::with_config(text[..offset].to_string(), config.clone());
+    let mut tokens = lexer.tokenize();
+    let mut is_word = false;
+    if let Some(last) = tokens.last().filter(|t| t.span.end == offset) {
+        let closed = |quote| last.lexeme.len() > 1 && last.lexeme.ends_with(quote);
+        match last.token {
+            Token::Comment(CommentKind::Block, _) if !closed('}') => return None,
+            Token::Comment(CommentKind::Line | CommentKind::DocLine, _) => return None,
+            Token::String(_) if !closed('"') => return None,
+            Token::Identifier(_) | Token::Keyword(_) => is_word = true,
+            _ => {}
+        }
+    }
+    let partial = if is_word { tokens.pop() } else { None };
+    tokens.retain(|t| !t.token.is_trivia() && !matches!(t.token, Token::Error(_)));
+    Some((tokens, partial))
+}
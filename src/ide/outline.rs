@@ -0,0 +1,63 @@
+use crate::diagnostic::Span;
+use crate::parser::{Consts, Dclns, Identifier, Program, Types};
+use crate::semantic::{function_signature, SymbolKind};
+
+/// An entry of the outline of a program, with the entries declared inside it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+    /// The whole declaration, e.g. from a function's name to the name after its `end`.
+    pub span: Span,
+    /// The declaring identifier.
+    pub name_span: Span,
+    pub children: Vec<OutlineItem>,
+}
+
+/// The program, with its constants, types, variables and functions, and for each
+/// function its parameters and local declarations.
+pub fn outline(program: &Program) -> OutlineItem {
+    let mut children = declarations(&program.consts, &program.types, &program.dclns);
+    for func in program.functions() {
+        let mut locals = Vec::new();
+        for var in &func.params.params {
+            for name in &var.names {
+                let detail = format!("parameter : {}", var.typename.name);
+                locals.push(item(name, SymbolKind::Parameter, detail, name.span, Vec::new()));
+            }
+        }
+        locals.extend(declarations(&func.consts, &func.types, &func.dclns));
+        let span = func.name.span.until(func.end_name.span);
+        children.push(item(&func.name, SymbolKind::Function, function_signature(func), span, locals));
+    }
+    let span = program.name.span.until(program.end_name.span);
+    item(&program.name, SymbolKind::Program, "program".to_string(), span, children)
+}
+
+fn declarations(consts: &Consts, types: &Types, dclns: &Dclns) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    for c in &consts.consts {
+        items.push(item(&c.name, SymbolKind::Constant, "const".to_string(), c.name.span, Vec::new()));
+    }
+    for t in &types.types {
+        let literals = t
+            .lit_list
+            .names
+            .iter()
+            .map(|l| item(l, SymbolKind::EnumLiteral, t.name.name.clone(), l.span, Vec::new()))
+            .collect();
+        let span = t.name.span.until(t.lit_list.names.last().map_or(t.name.span, |l| l.span));
+        items.push(item(&t.name, SymbolKind::Type, "type".to_string(), span, literals));
+    }
+    for var in &dclns.vars {
+        for name in &var.names {
+            items.push(item(name, SymbolKind::Variable, var.typename.name.clone(), name.span, Vec::new()));
+        }
+    }
+    items
+}
+
+fn item(name: &Identifier, kind: SymbolKind, detail: String, span: Span, children: Vec<OutlineItem>) -> OutlineItem {
+    OutlineItem { name: name.name.clone(), kind, detail, span, name_span: name.span, children }
+}
@@ -0,0 +1,85 @@
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{Lexer, LexerConfig, SpannedToken, Token};
+use crate::semantic::{ScopeId, SemanticModel};
+
+use super::TextEdit;
+
+/// Edits renaming the symbol at `offset` to `new_name` everywhere it is declared or
+/// used, including `end` names. Fails when the new name is already declared in the
+/// same scope, would shadow or be shadowed by another declaration, or is used without
+/// being declared where the symbol is visible.
+pub fn rename(
+    model: &SemanticModel,
+    offset: usize,
+    new_name: &str,
+    config: &LexerConfig,
+) -> Result<Vec<TextEdit>, Diagnostic> {
+    let Some(reference) = model.reference_at(offset) else {
+        return Err(Diagnostic::error("no symbol to rename here".to_string(), Span::default()));
+    };
+    let id = reference.symbol;
+    let symbol = model.symbol(id);
+    let Some(declaration) = symbol.span else {
+        let message = format!("cannot rename predeclared '{}'", symbol.name);
+        return Err(Diagnostic::error(message, reference.span));
+    };
+    // the text is written as given, but compared as the lexer reads it, folded to lowercase
+    // when the source is case-insensitive
+    let text = new_name;
+    let key = check_name(new_name, reference.span, config)?;
+    let new_name = key.as_str();
+    let edits = || model.references_to(id).map(|r| TextEdit { span: r.span, new_text: text.to_string() }).collect();
+    // only the spelling changes, not what the name refers to; references spelled differently from the
+    // declaration take the new spelling as well
+    if new_name == symbol.key {
+        return Ok(edits());
+    }
+
+    if model.declared_in(symbol.scope, new_name).is_some() {
+        let message = format!("'{}' is already declared in this scope", new_name);
+        return Err(Diagnostic::error(message, declaration));
+    }
+    match symbol.scope {
+        ScopeId::Function(_) => {
+            if model.declared_in(ScopeId::Global, new_name).is_some() {
+                let message = format!("'{}' would shadow the global '{}'", new_name, new_name);
+                return Err(Diagnostic::error(message, declaration));
+            }
+        }
+        ScopeId::Global => {
+            let local = model.symbols().iter().enumerate().find(|(other, s)| {
+                s.scope != ScopeId::Global && model.declared_in(s.scope, new_name) == Some(*other)
+            });
+            if let Some((_, local)) = local {
+                let function = model.function_of(local.scope).map_or("", |f| model.symbol(f).name.as_str());
+                let message = format!("'{}' would be shadowed by the local '{}' in '{}'", new_name, new_name, function);
+                return Err(Diagnostic::error(message, declaration));
+            }
+        }
+    }
+
+    // undeclared uses of the new name would start referring to the symbol
+    let captured = model
+        .unresolved()
+        .iter()
+        .find(|u| u.key == new_name && (symbol.scope == ScopeId::Global || u.scope == symbol.scope));
+    if let Some(unresolved) = captured {
+        let message = format!("renaming '{}' to '{}' would change what this refers to", symbol.name, new_name);
+        return Err(Diagnostic::error(message, unresolved.span));
+    }
+
+    Ok(edits())
+}
+
+/// The name as the lexer reads it, if it is an identifier.
+fn check_name(name: &str, span: Span, config: &LexerConfig) -> Result<String, Diagnostic> {
+    let mut lexer = Lexer::with_config(name.to_string(), config.clone());
+    let tokens = lexer.tokenize();
+    match tokens.as_slice() {
+        [SpannedToken { token: Token::Identifier(name), .. }] => Ok(name.clone()),
+        [token] if matches!(token.token, Token::Keyword(_)) => {
+            Err(Diagnostic::error(format!("'{}' is a keyword", name), span))
+        }
+        _ => Err(Diagnostic::error(format!("'{}' is not a valid name", name), span)),
+    }
+}
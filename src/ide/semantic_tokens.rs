@@ -0,0 +1,20 @@
+use crate::diagnostic::Span;
+use crate::semantic::{SemanticModel, SymbolKind};
+
+/// A resolved identifier and what it names.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SemanticToken {
+    pub span: Span,
+    pub kind: SymbolKind,
+    pub is_declaration: bool,
+}
+
+/// Every identifier that resolves to a declaration, in source order. Undeclared names
+/// are left out.
+pub fn semantic_tokens(model: &SemanticModel) -> Vec<SemanticToken> {
+    model
+        .references()
+        .iter()
+        .map(|r| SemanticToken { span: r.span, kind: model.symbol(r.symbol).kind, is_declaration: r.is_declaration })
+        .collect()
+}
@@ -0,0 +1,308 @@
+#[cfg(test)]
+mod tests {
+    use crate::ide::completion::{completions, signature_help, CompletionKind};
+    use crate::ide::folding::{folding_ranges, FoldKind};
+    use crate::ide::outline::outline;
+    use crate::ide::rename::rename;
+    use crate::ide::semantic_tokens::semantic_tokens;
+    use crate::ide::{apply_edits, offset_at};
+    use crate::lexer::{Lexer, LexerConfig};
+    use crate::parser::{Parser, Program};
+    use crate::semantic::{function_signature, SemanticModel, SymbolKind};
+
+    const MERGE: &str = include_str!("./../../winzig_test_programs/winzig_12");
+    const FACTORS: &str = include_str!("./../../winzig_test_programs/winzig_01");
+    const CASES: &str = include_str!("./../../winzig_test_programs/winzig_11");
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false).parse().unwrap()
+    }
+
+    /// Offset just after the `nth` occurrence of `needle`.
+    fn after(text: &str, needle: &str, nth: usize) -> usize {
+        text.match_indices(needle).nth(nth).unwrap().0 + needle.len()
+    }
+
+    fn labels(text: &str, offset: usize, model: Option<&SemanticModel>, kind: CompletionKind) -> Vec<String> {
+        let items = completions(text, offset, model, &LexerConfig::default());
+        items.into_iter().filter(|c| c.kind == kind).map(|c| c.label).collect()
+    }
+
+    #[test]
+    fn test_keywords_follow_the_grammar() {
+        let config = LexerConfig::default();
+        let text = "program p: ";
+        let keywords = labels(text, text.len(), None, CompletionKind::Keyword);
+        assert_eq!(keywords, ["const", "type", "var", "function", "begin"]);
+
+        let text = "program p: var x : integer; begin if x = 1 ";
+        let keywords = labels(text, text.len(), None, CompletionKind::Keyword);
+        assert_eq!(keywords, ["then", "and", "or", "mod"]);
+
+        let text = "program p: begin repeat x := 1; ";
+        let keywords = labels(text, text.len(), None, CompletionKind::Keyword);
+        assert!(keywords.contains(&"until".to_string()));
+        assert!(keywords.contains(&"output".to_string()));
+        assert!(!keywords.contains(&"end".to_string()));
+
+        // a syntax error before the cursor leaves nothing to offer
+        let text = "program p: begin output(1 end; ";
+        assert!(completions(text, text.len(), None, &config).is_empty());
+        // nor is there anything inside a comment
+        let text = "program p: # begin";
+        assert!(completions(text, text.len(), None, &config).is_empty());
+    }
+
+    #[test]
+    fn test_names_in_scope() {
+        let config = LexerConfig::default();
+        let model = SemanticModel::build(&parse(MERGE));
+
+        // an operand inside Merge: its parameters and locals, globals and functions
+        let offset = after(MERGE, "nexthigh := ", 0);
+        let items = completions(MERGE, offset, Some(&model), &config);
+        let low = items.iter().find(|c| c.label == "low").unwrap();
+        assert_eq!(low.kind, CompletionKind::Parameter);
+        assert_eq!(low.detail.as_deref(), Some("(parameter) low : integer"));
+        assert!(items.iter().any(|c| c.label == "Merge" && c.kind == CompletionKind::Function));
+        assert!(items.iter().any(|c| c.label == "true" && c.kind == CompletionKind::EnumLiteral));
+        // the local 'i' hides the global one
+        let i: Vec<_> = items.iter().filter(|c| c.label == "i").collect();
+        assert_eq!(i.len(), 1);
+        assert_eq!(i[0].detail.as_deref(), Some("i : integer"));
+        assert!(items.iter().any(|c| c.label == "succ"));
+        assert!(!items.iter().any(|c| c.label == "integer" || c.label == "begin"));
+
+        // the word being typed doesn't change what is offered
+        let typing = format!("{}mid", &MERGE[..offset]);
+        assert_eq!(completions(&typing, typing.len(), Some(&model), &config), items);
+
+        // the start of a statement takes an assignment target
+        let offset = after(MERGE, "nexthigh := middle+1;", 0);
+        let variables = labels(MERGE, offset, Some(&model), CompletionKind::Variable);
+        assert!(variables.contains(&"nextlow".to_string()));
+        assert!(labels(MERGE, offset, Some(&model), CompletionKind::Function).is_empty());
+
+        // in MergeSort, 'nextlow' is out of scope
+        let offset = after(MERGE, "middle := ", 0);
+        assert!(!labels(MERGE, offset, Some(&model), CompletionKind::Variable).contains(&"nextlow".to_string()));
+        let parameters = labels(MERGE, offset, Some(&model), CompletionKind::Parameter);
+        assert_eq!(parameters, ["start", "stop"]);
+    }
+
+    #[test]
+    fn test_types_and_declarations() {
+        let config = LexerConfig::default();
+        let text = "program p: type color = (red, green); var c : ";
+        let model = SemanticModel::build(&parse("program p: type color = (red, green); begin end p."));
+        let items = completions(text, text.len(), Some(&model), &config);
+        let names: Vec<&str> = items.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(names, ["integer", "char", "boolean", "color"]);
+
+        // a new name is being declared
+        let text = "program p: type color = (red, green); var ";
+        assert!(completions(text, text.len(), Some(&model), &config).iter().all(|c| c.kind == CompletionKind::Keyword));
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let config = LexerConfig::default();
+        let program = parse(MERGE);
+        let merge = program.functions().iter().find(|f| f.name.name == "Merge").unwrap();
+
+        let offset = after(MERGE, "d:=Merge ( start, middle, ", 0);
+        let help = signature_help(MERGE, offset, &program, &config).unwrap();
+        assert_eq!(help.label, function_signature(merge));
+        assert_eq!(help.active_parameter, 2);
+        let names: Vec<&str> = help.parameters.iter().map(|p| &help.label[p.range.clone()]).collect();
+        assert_eq!(names, ["low", "middle", "high"]);
+        assert_eq!(help.parameters[0].type_name, "integer");
+
+        // the innermost call, until its argument list is closed
+        let offset = after(MERGE, "d:=Store(temp,i,Element(a,", 0);
+        let help = signature_help(MERGE, offset, &program, &config).unwrap();
+        assert!(help.label.starts_with("function Element("));
+        assert_eq!(help.active_parameter, 1);
+        let offset = after(MERGE, "d:=Store(temp,i,Element(a,nexthigh)", 0);
+        let help = signature_help(MERGE, offset, &program, &config).unwrap();
+        assert!(help.label.starts_with("function Store("));
+        assert_eq!(help.active_parameter, 2);
+
+        let offset = after(MERGE, "nexthigh := ", 0);
+        assert_eq!(signature_help(MERGE, offset, &program, &config), None);
+        let offset = after(MERGE, "nexthigh := succ(", 0);
+        assert_eq!(signature_help(MERGE, offset, &program, &config), None);
+    }
+
+    fn rename_error(source: &str, at: &str, new_name: &str) -> String {
+        let config = LexerConfig::default();
+        let model = SemanticModel::build(&parse(source));
+        rename(&model, source.find(at).unwrap(), new_name, &config).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_rename() {
+        let config = LexerConfig::default();
+        let model = SemanticModel::build(&parse(MERGE));
+
+        // from the call: the declaration, the call and 'end Merge'
+        let offset = after(MERGE, "d:=Merge (", 0) - "Merge (".len();
+        let edits = rename(&model, offset, "Combine", &config).unwrap();
+        assert_eq!(edits.len(), 3);
+        let renamed = apply_edits(MERGE, &edits);
+        assert_eq!(renamed, MERGE.replace("Merge (", "Combine (").replace("end Merge;", "end Combine;"));
+        let program = parse(&renamed);
+        assert!(program.functions().iter().any(|f| f.name.name == "Combine"));
+
+        // the local 'i' of Merge, not the global one
+        let offset = offset_at(MERGE, 94, 2).unwrap();
+        assert_eq!(&MERGE[offset..offset + 1], "i");
+        let edits = rename(&model, offset, "k", &config).unwrap();
+        let renamed = apply_edits(MERGE, &edits);
+        assert!(renamed.contains("for (k := 1; k<=last; k:=k+1)"));
+        assert!(renamed.contains("for (i:=1; i<=6; i:=i+1)"));
+        assert_eq!(SemanticModel::build(&parse(&renamed)).diagnostics().len(), model.diagnostics().len());
+    }
+
+    #[test]
+    fn test_rename_conflicts() {
+        let source = "program p:\n\
+                      var g, h : integer;\n\
+                      function f(n : integer) : integer;\nvar m : integer;\nbegin m := n + u; return (m) end f;\n\
+                      begin g := f(h) end p.";
+        assert_eq!(rename_error(source, "h :", "g"), "2:8: error: 'g' is already declared in this scope");
+        assert_eq!(rename_error(source, "m :", "g"), "4:5: error: 'g' would shadow the global 'g'");
+        assert_eq!(rename_error(source, "h :", "m"), "2:8: error: 'm' would be shadowed by the local 'm' in 'f'");
+        assert_eq!(rename_error(source, "m :", "u"), "5:16: error: renaming 'm' to 'u' would change what this refers to");
+        assert_eq!(rename_error(source, "integer", "int"), "2:12: error: cannot rename predeclared 'integer'");
+        assert_eq!(rename_error(source, "g,", "begin"), "2:5: error: 'begin' is a keyword");
+        assert_eq!(rename_error(source, "g,", "1g"), "2:5: error: '1g' is not a valid name");
+        assert_eq!(rename_error(source, "var", "x"), "0:0: error: no symbol to rename here");
+
+        // names are compared as a case-insensitive source reads them
+        let config = LexerConfig { case_insensitive: true, ..Default::default() };
+        let source = "PROGRAM p: VAR g, h : Integer; BEGIN g := H END p.";
+        let mut lexer = Lexer::with_config(source.to_string(), config.clone());
+        let model = SemanticModel::build(&Parser::with_spans(lexer.tokenize(), false).parse().unwrap());
+        let error = |new_name| rename(&model, source.find("h").unwrap(), new_name, &config).unwrap_err().to_string();
+        assert_eq!(error("Begin"), "1:19: error: 'Begin' is a keyword");
+        assert_eq!(error("G"), "1:19: error: 'g' is already declared in this scope");
+        let edits = rename(&model, source.find("h").unwrap(), "Total", &config).unwrap();
+        assert_eq!(apply_edits(source, &edits), "PROGRAM p: VAR g, Total : Integer; BEGIN g := Total END p.");
+        // only the spelling changes, every reference takes the new one
+        let edits = rename(&model, source.find("h").unwrap(), "h", &config).unwrap();
+        assert_eq!(apply_edits(source, &edits), "PROGRAM p: VAR g, h : Integer; BEGIN g := h END p.");
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let model = SemanticModel::build(&parse(MERGE));
+        let tokens = semantic_tokens(&model);
+        assert!(tokens.windows(2).all(|w| w[0].span.start < w[1].span.start));
+        let kind_at = |line, column| {
+            let offset = offset_at(MERGE, line, column).unwrap();
+            let token = tokens.iter().find(|t| t.span.start == offset).unwrap();
+            (token.kind, token.is_declaration)
+        };
+        assert_eq!(kind_at(1, 9), (SymbolKind::Program, true));
+        assert_eq!(kind_at(4, 2), (SymbolKind::Type, true));
+        assert_eq!(kind_at(4, 12), (SymbolKind::EnumLiteral, true));
+        assert_eq!(kind_at(92, 10), (SymbolKind::Function, true));
+        assert_eq!(kind_at(92, 18), (SymbolKind::Parameter, true));
+        assert_eq!(kind_at(94, 2), (SymbolKind::Variable, true));
+        assert_eq!(kind_at(97, 11), (SymbolKind::Parameter, false));
+        assert_eq!(kind_at(122, 5), (SymbolKind::Function, false));
+        // 'integer' is predeclared, but still a type
+        assert_eq!(kind_at(92, 38), (SymbolKind::Type, false));
+
+        // every identifier is classified, 'd' as the variable it is declared as
+        assert!(model.unresolved().is_empty());
+        let discards: Vec<_> = tokens.iter().filter(|t| &MERGE[t.span.start..t.span.end] == "d").collect();
+        assert_eq!(discards.len(), 13);
+        assert!(discards.iter().all(|t| t.kind == SymbolKind::Variable));
+        assert_eq!(discards.iter().filter(|t| t.is_declaration).count(), 1);
+
+        let source = "program p: const c = 1; begin output(c) end p.";
+        let model = SemanticModel::build(&parse(source));
+        let kinds: Vec<_> = semantic_tokens(&model).iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [SymbolKind::Program, SymbolKind::Constant, SymbolKind::Constant, SymbolKind::Program]);
+    }
+
+    #[test]
+    fn test_folding_ranges() {
+        let config = LexerConfig::default();
+        let count = |text, kind| folding_ranges(text, &config).iter().filter(|f| f.kind == kind).count();
+        assert_eq!(count(MERGE, FoldKind::Function), 8);
+        assert_eq!(count(MERGE, FoldKind::Block), MERGE.matches("begin").count());
+        assert_eq!(count(MERGE, FoldKind::Case), MERGE.matches("case").count());
+        assert_eq!(count(FACTORS, FoldKind::Repeat), 1);
+        // including the nested ones
+        assert_eq!(count(CASES, FoldKind::Case), 7);
+
+        let folds = folding_ranges(MERGE, &config);
+        let merge = folds.into_iter().find(|f| MERGE[f.span.start..].starts_with("function Merge")).unwrap();
+        assert_eq!(merge.kind, FoldKind::Function);
+        assert!(MERGE[merge.span.start..merge.span.end].ends_with("end Merge"));
+
+        let text = "program p: begin loop repeat exit until true pool end p.";
+        let folds = folding_ranges(text, &config);
+        let folds: Vec<_> = folds.iter().map(|f| (f.kind, &text[f.span.start..f.span.end])).collect();
+        assert_eq!(
+            folds,
+            [
+                (FoldKind::Repeat, "repeat exit until"),
+                (FoldKind::Loop, "loop repeat exit until true pool"),
+                (FoldKind::Block, "begin loop repeat exit until true pool end"),
+            ]
+        );
+
+        // the folds that are closed survive a syntax error
+        let text = "program p: begin repeat x := 1 until x = ; end p.";
+        assert_eq!(count(text, FoldKind::Repeat), 1);
+        assert_eq!(count(text, FoldKind::Block), 1);
+        let text = "program p: begin pool end p.";
+        assert_eq!(folding_ranges(text, &config).len(), 1);
+
+        let config = LexerConfig { case_insensitive: true, ..Default::default() };
+        assert_eq!(folding_ranges("PROGRAM p: BEGIN REPEAT UNTIL true END p.", &config).len(), 2);
+    }
+
+    #[test]
+    fn test_outline() {
+        let program = outline(&parse(MERGE));
+        assert_eq!((program.name.as_str(), program.kind), ("MergeSortTest", SymbolKind::Program));
+        assert_eq!(
+            &MERGE[program.span.start..program.span.end],
+            MERGE.trim_end().trim_start_matches("program ").trim_end_matches('.')
+        );
+        let kinds = |kind| program.children.iter().filter(|c| c.kind == kind).count();
+        assert_eq!(kinds(SymbolKind::Type), 1);
+        assert_eq!(kinds(SymbolKind::Variable), 24);
+        assert_eq!(kinds(SymbolKind::Function), 8);
+
+        let array = &program.children[0];
+        let literals: Vec<(&str, &str)> = array.children.iter().map(|c| (c.name.as_str(), c.detail.as_str())).collect();
+        assert_eq!(literals, [("a", "Array"), ("temp", "Array")]);
+
+        let merge = program.children.iter().find(|c| c.name == "Merge").unwrap();
+        assert_eq!(merge.detail, "function Merge(low, middle, high : integer) : integer");
+        assert_eq!(&MERGE[merge.name_span.start..merge.name_span.end], "Merge");
+        assert!(MERGE[merge.span.start..merge.span.end].ends_with("end Merge"));
+        let locals: Vec<(&str, SymbolKind)> = merge.children.iter().map(|c| (c.name.as_str(), c.kind)).collect();
+        assert_eq!(
+            locals,
+            [
+                ("low", SymbolKind::Parameter),
+                ("middle", SymbolKind::Parameter),
+                ("high", SymbolKind::Parameter),
+                ("i", SymbolKind::Variable),
+                ("last", SymbolKind::Variable),
+                ("nextlow", SymbolKind::Variable),
+                ("nexthigh", SymbolKind::Variable),
+            ]
+        );
+        assert_eq!(merge.children[0].detail, "parameter : integer");
+        assert!(merge.children.iter().all(|c| c.children.is_empty()));
+    }
+}
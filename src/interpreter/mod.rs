@@ -0,0 +1,673 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::WordSize;
+use crate::parser::*;
+
+mod test;
+
+/// Result of evaluating WinZig code. The errors are those only running it finds, such as
+/// overflow or division by zero; they carry no position, as expressions have none, except
+/// that exceeding the call depth is reported at the call.
+pub type EvalResult<T> = Result<T, Diagnostic>;
+
+// Deep enough for the recursive sample programs, shallow enough not to overflow the host stack.
+const MAX_CALL_DEPTH: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub struct EnumType {
+    pub name: String,
+    pub literals: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Integer,
+    Char,
+    Boolean,
+    Enum(Rc<EnumType>),
+}
+
+impl Type {
+    fn default_value(&self) -> Value {
+        match self {
+            Type::Integer => Value::Integer(0),
+            Type::Char => Value::Char('\0'),
+            Type::Boolean => Value::Boolean(false),
+            Type::Enum(e) => Value::Enum(e.clone(), 0),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Integer => write!(f, "integer"),
+            Type::Char => write!(f, "char"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Enum(e) => write!(f, "{}", e.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Char(char),
+    Boolean(bool),
+    Enum(Rc<EnumType>, usize),
+}
+
+impl Value {
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Integer(_) => Type::Integer,
+            Value::Char(_) => Type::Char,
+            Value::Boolean(_) => Type::Boolean,
+            Value::Enum(e, _) => Type::Enum(e.clone()),
+        }
+    }
+
+    /// Position of the value in its type, used for comparisons and `ord`.
+    fn ordinal(&self) -> i64 {
+        match self {
+            Value::Integer(i) => *i,
+            Value::Char(c) => *c as i64,
+            Value::Boolean(b) => *b as i64,
+            Value::Enum(_, ordinal) => *ordinal as i64,
+        }
+    }
+}
+
+/// Formats values the way `output` prints them.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Enum(e, ordinal) => write!(f, "{}", e.literals[*ordinal]),
+        }
+    }
+}
+
+/// Where `output` writes and `read` reads.
+pub trait Io {
+    fn write_line(&mut self, line: &str);
+    fn read_line(&mut self) -> Option<String>;
+}
+
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+/// In-memory `Io`, for tests and embedding.
+#[derive(Default)]
+pub struct BufferIo {
+    pub input: VecDeque<String>,
+    pub output: Vec<String>,
+}
+
+impl BufferIo {
+    pub fn with_input(input: &str) -> Self {
+        Self {
+            input: input.lines().map(|l| l.to_string()).collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Io for BufferIo {
+    fn write_line(&mut self, line: &str) {
+        self.output.push(line.to_string());
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    // constants and enumeration literals
+    consts: HashMap<String, Value>,
+    types: HashMap<String, Type>,
+    // a variable's type is the type of its current value
+    vars: HashMap<String, Value>,
+}
+
+enum Flow {
+    Normal,
+    Exit,
+    Return(Value),
+}
+
+fn error(message: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(message, Span::default())
+}
+
+fn error_at(name: &Identifier, message: impl Into<String>) -> Diagnostic {
+    Diagnostic::error(message, name.span)
+}
+
+/// Runs code that the semantic model and `check` accept without errors, so whose names are
+/// declared and whose values have the types they are used as.
+pub struct Interpreter<I: Io = StdIo> {
+    io: I,
+    globals: Scope,
+    // one scope per active call; a function sees only its own scope and the globals
+    frames: Vec<Scope>,
+    functions: HashMap<String, Rc<Func>>,
+    // unread characters of the current input line
+    input: VecDeque<char>,
+    // bounds every integer computed or read
+    word_size: WordSize,
+}
+
+impl<I: Io> Interpreter<I> {
+    pub fn new(io: I) -> Self {
+        Self::with_word_size(io, WordSize::default())
+    }
+
+    /// An interpreter whose integers are those of a `word_size` word, as with the lexer's
+    /// `--word-size`.
+    pub fn with_word_size(io: I, word_size: WordSize) -> Self {
+        let mut globals = Scope::default();
+        globals.types.insert("integer".to_string(), Type::Integer);
+        globals.types.insert("char".to_string(), Type::Char);
+        globals.types.insert("boolean".to_string(), Type::Boolean);
+        globals.consts.insert("true".to_string(), Value::Boolean(true));
+        globals.consts.insert("false".to_string(), Value::Boolean(false));
+        Self {
+            io,
+            globals,
+            frames: Vec::new(),
+            functions: HashMap::new(),
+            input: VecDeque::new(),
+            word_size,
+        }
+    }
+
+    pub fn io(&self) -> &I {
+        &self.io
+    }
+
+    pub fn io_mut(&mut self) -> &mut I {
+        &mut self.io
+    }
+
+    /// Runs a whole program: declares its globals and functions, then executes its body.
+    pub fn run(&mut self, program: Program) -> EvalResult<()> {
+        self.declare_sections(&program.consts, &program.types, &program.dclns);
+        for func in program.sub_progs.sub_progs {
+            self.define_function(func);
+        }
+        self.execute(&program.body.statements)
+    }
+
+    /// Adds constants, types and variables to the global environment, replacing earlier ones.
+    pub fn declare(&mut self, declarations: &Declarations) {
+        self.declare_sections(&declarations.consts, &declarations.types, &declarations.dclns)
+    }
+
+    /// Adds a function to the global environment, replacing one with the same name.
+    pub fn define_function(&mut self, func: Func) {
+        self.functions.insert(func.name.key.clone(), Rc::new(func));
+    }
+
+    /// Executes statements in the global environment.
+    pub fn execute(&mut self, statements: &[Statement]) -> EvalResult<()> {
+        // 'exit' and 'return' are only where they have something to leave
+        self.statements(statements)?;
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, expression: &Expression) -> EvalResult<Value> {
+        match expression {
+            Expression::Le { left, right } => self.compare(left, right, Ordering::is_le),
+            Expression::Lt { left, right } => self.compare(left, right, Ordering::is_lt),
+            Expression::Ge { left, right } => self.compare(left, right, Ordering::is_ge),
+            Expression::Gt { left, right } => self.compare(left, right, Ordering::is_gt),
+            Expression::Eq { left, right } => self.compare(left, right, Ordering::is_eq),
+            Expression::Ne { left, right } => self.compare(left, right, Ordering::is_ne),
+            Expression::Term(term) => self.term(term),
+        }
+    }
+
+    fn compare(&mut self, left: &Term, right: &Term, test: fn(Ordering) -> bool) -> EvalResult<Value> {
+        let left = self.term(left)?;
+        let right = self.term(right)?;
+        Ok(Value::Boolean(test(left.ordinal().cmp(&right.ordinal()))))
+    }
+
+    fn term(&mut self, term: &Term) -> EvalResult<Value> {
+        match term {
+            Term::Add { left, right } => {
+                let (left, right) = (self.term(left)?, self.factor(right)?);
+                self.arithmetic(left, right, i64::checked_add)
+            }
+            Term::Subtract { left, right } => {
+                let (left, right) = (self.term(left)?, self.factor(right)?);
+                self.arithmetic(left, right, i64::checked_sub)
+            }
+            Term::Or { left, right } => {
+                let (left, right) = (self.term(left)?, self.factor(right)?);
+                Ok(Value::Boolean(boolean(&left) | boolean(&right)))
+            }
+            Term::Factor(factor) => self.factor(factor),
+        }
+    }
+
+    fn factor(&mut self, factor: &Factor) -> EvalResult<Value> {
+        match factor {
+            Factor::Multiply { left, right } => {
+                let (left, right) = (self.factor(left)?, self.primary(right)?);
+                self.arithmetic(left, right, i64::checked_mul)
+            }
+            Factor::Divide { left, right } => {
+                let (left, right) = (self.factor(left)?, self.primary(right)?);
+                check_divisor(&right)?;
+                self.arithmetic(left, right, i64::checked_div)
+            }
+            Factor::Mod { left, right } => {
+                let (left, right) = (self.factor(left)?, self.primary(right)?);
+                check_divisor(&right)?;
+                self.arithmetic(left, right, i64::checked_rem)
+            }
+            Factor::And { left, right } => {
+                let (left, right) = (self.factor(left)?, self.primary(right)?);
+                Ok(Value::Boolean(boolean(&left) & boolean(&right)))
+            }
+            Factor::Primary(primary) => self.primary(primary),
+        }
+    }
+
+    fn primary(&mut self, primary: &Primary) -> EvalResult<Value> {
+        match primary {
+            Primary::Negate { primary } => {
+                let value = self.primary(primary)?;
+                self.arithmetic(Value::Integer(0), value, i64::checked_sub)
+            }
+            Primary::Not { primary } => {
+                let value = self.primary(primary)?;
+                Ok(Value::Boolean(!boolean(&value)))
+            }
+            Primary::Eof => Ok(Value::Boolean(self.at_eof())),
+            Primary::Name(name) => Ok(self.value(name)),
+            Primary::Integer(i) => Ok(Value::Integer(*i)),
+            Primary::Char(c) => Ok(Value::Char(*c)),
+            Primary::Call { name, exps } => self.call(name, exps),
+            Primary::Expression(exp) => self.evaluate(exp),
+            Primary::Succ { exp } => {
+                let value = self.evaluate(exp)?;
+                step(value, 1, "successor", self.word_size)
+            }
+            Primary::Pred { exp } => {
+                let value = self.evaluate(exp)?;
+                step(value, -1, "predecessor", self.word_size)
+            }
+            Primary::Chr { exp } => {
+                let i = self.evaluate(exp)?.ordinal();
+                let c = u32::try_from(i).ok().and_then(char::from_u32);
+                c.map(Value::Char).ok_or_else(|| error(format!("chr({}) is not a character", i)))
+            }
+            Primary::Ord { exp } => Ok(Value::Integer(self.evaluate(exp)?.ordinal())),
+        }
+    }
+
+    fn call(&mut self, name: &Identifier, exps: &[Expression]) -> EvalResult<Value> {
+        let func = self.function(name);
+        let mut args = Vec::new();
+        for exp in exps {
+            args.push(self.evaluate(exp)?);
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            let message = format!("call depth limit of {} exceeded in '{}'", MAX_CALL_DEPTH, func.name.name);
+            return Err(error_at(name, message));
+        }
+
+        // parameter and return types are resolved before the function's own types are declared
+        let mut frame = Scope::default();
+        let params = func.params.params.iter().flat_map(|var| &var.names);
+        for (param, arg) in params.zip(args) {
+            frame.vars.insert(param.key.clone(), arg);
+        }
+        let return_type = self.global_type(&func.return_type);
+
+        self.frames.push(frame);
+        let result = self.invoke(&func, &return_type);
+        self.frames.pop();
+        result
+    }
+
+    fn invoke(&mut self, func: &Func, return_type: &Type) -> EvalResult<Value> {
+        self.declare_sections(&func.consts, &func.types, &func.dclns);
+        match self.statements(&func.body.statements)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal | Flow::Exit => Ok(return_type.default_value()),
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> EvalResult<Flow> {
+        for statement in statements {
+            match self.statement(statement)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn statement(&mut self, statement: &Statement) -> EvalResult<Flow> {
+        match statement {
+            Statement::Assign { assignment } => self.assignment(assignment)?,
+            Statement::Output { expressions } => {
+                for expression in expressions {
+                    let line = match expression {
+                        OutExp::Integer { exp } => self.evaluate(exp)?.to_string(),
+                        OutExp::String { value } => value.clone(),
+                    };
+                    self.io.write_line(&line);
+                }
+            }
+            Statement::If { cond, then, else_stmt } => {
+                if self.condition(cond)? {
+                    return self.statement(then);
+                } else if let Some(else_stmt) = else_stmt {
+                    return self.statement(else_stmt);
+                }
+            }
+            Statement::While { cond, stmt } => {
+                while self.condition(cond)? {
+                    match self.statement(stmt)? {
+                        Flow::Normal => {}
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Repeat { stmts, cond } => loop {
+                match self.statements(stmts)? {
+                    Flow::Normal => {}
+                    flow => return Ok(flow),
+                }
+                if self.condition(cond)? {
+                    break;
+                }
+            },
+            Statement::For { init, cond, update, stmt } => {
+                self.for_stat(init)?;
+                loop {
+                    if let ForExp::Expression(cond) = cond {
+                        if !self.condition(cond)? {
+                            break;
+                        }
+                    }
+                    match self.statement(stmt)? {
+                        Flow::Normal => {}
+                        flow => return Ok(flow),
+                    }
+                    self.for_stat(update)?;
+                }
+            }
+            Statement::Loop { stmts } => loop {
+                match self.statements(stmts)? {
+                    Flow::Normal => {}
+                    Flow::Exit => break,
+                    flow => return Ok(flow),
+                }
+            },
+            Statement::Case { expr, cases, otherwise } => {
+                let selector = self.evaluate(expr)?;
+                for clause in cases {
+                    for label in &clause.labels {
+                        if self.matches(&selector, &label.expression) {
+                            return self.statement(&clause.statement);
+                        }
+                    }
+                }
+                if let Some(otherwise) = otherwise {
+                    return self.statement(&otherwise.stmt);
+                }
+            }
+            Statement::Read { names } => {
+                // only integers and characters are read
+                for name in names {
+                    let value = match self.value(name) {
+                        Value::Char(_) => Value::Char(self.read_char()?),
+                        _ => Value::Integer(self.read_integer()?),
+                    };
+                    self.assign(name, value);
+                }
+            }
+            Statement::Exit { .. } => return Ok(Flow::Exit),
+            Statement::Return { exp, .. } => return Ok(Flow::Return(self.evaluate(exp)?)),
+            Statement::Body { body } => return self.statements(&body.statements),
+            Statement::Null => {}
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_stat(&mut self, stat: &ForStat) -> EvalResult<()> {
+        match stat {
+            ForStat::Assignment(assignment) => self.assignment(assignment),
+            ForStat::Null => Ok(()),
+        }
+    }
+
+    fn assignment(&mut self, assignment: &Assignment) -> EvalResult<()> {
+        match assignment {
+            Assignment::Assignment { name, exp } => {
+                let value = self.evaluate(exp)?;
+                self.assign(name, value);
+            }
+            Assignment::Swap { name1, name2 } => {
+                let (value1, value2) = (self.value(name1), self.value(name2));
+                self.assign(name1, value2);
+                self.assign(name2, value1);
+            }
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self, cond: &Expression) -> EvalResult<bool> {
+        Ok(boolean(&self.evaluate(cond)?))
+    }
+
+    fn matches(&self, selector: &Value, label: &CaseExpression) -> bool {
+        let (low, high) = match label {
+            CaseExpression::Value(value) => {
+                let value = self.const_value(value);
+                (value.clone(), value)
+            }
+            CaseExpression::Range(low, high) => (self.const_value(low), self.const_value(high)),
+        };
+        low.ordinal() <= selector.ordinal() && selector.ordinal() <= high.ordinal()
+    }
+
+    fn declare_sections(&mut self, consts: &Consts, types: &Types, dclns: &Dclns) {
+        for c in &consts.consts {
+            let value = self.const_value(&c.value);
+            self.scope_mut().consts.insert(c.name.key.clone(), value);
+        }
+        for t in &types.types {
+            let enum_type = Rc::new(EnumType {
+                name: t.name.name.clone(),
+                literals: t.lit_list.names.iter().map(|n| n.name.clone()).collect(),
+            });
+            let scope = self.scope_mut();
+            for (ordinal, literal) in t.lit_list.names.iter().enumerate() {
+                scope.consts.insert(literal.key.clone(), Value::Enum(enum_type.clone(), ordinal));
+            }
+            scope.types.insert(t.name.key.clone(), Type::Enum(enum_type));
+        }
+        for var in &dclns.vars {
+            let value = self.type_named(&var.typename).default_value();
+            for name in &var.names {
+                self.scope_mut().vars.insert(name.key.clone(), value.clone());
+            }
+        }
+    }
+
+    fn const_value(&self, value: &ConstValue) -> Value {
+        match value {
+            ConstValue::Integer(i) => Value::Integer(*i),
+            ConstValue::Char(c) => Value::Char(*c),
+            ConstValue::Name(name) => self.value(name),
+        }
+    }
+
+    fn arithmetic(&self, left: Value, right: Value, op: fn(i64, i64) -> Option<i64>) -> EvalResult<Value> {
+        self.integer(op(left.ordinal(), right.ordinal()))
+    }
+
+    /// The result of an integer operation, if it fits the word.
+    fn integer(&self, value: Option<i64>) -> EvalResult<Value> {
+        match value.filter(|v| self.word_size.contains(*v)) {
+            Some(value) => Ok(Value::Integer(value)),
+            None => Err(error(format!("integer overflow for {}-bit word", self.word_size.bits()))),
+        }
+    }
+
+    fn scope_mut(&mut self) -> &mut Scope {
+        match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => &mut self.globals,
+        }
+    }
+
+    /// Visible scopes, innermost first.
+    fn scopes(&self) -> impl Iterator<Item = &Scope> {
+        self.frames.last().into_iter().chain(std::iter::once(&self.globals))
+    }
+
+    /// The value of a variable, parameter, constant or literal.
+    fn value(&self, name: &Identifier) -> Value {
+        let value = self.scopes().find_map(|s| s.vars.get(&name.key).or_else(|| s.consts.get(&name.key)));
+        value.cloned().unwrap_or_else(|| unresolved(name))
+    }
+
+    fn type_named(&self, name: &Identifier) -> Type {
+        self.scopes().find_map(|s| s.types.get(&name.key)).cloned().unwrap_or_else(|| unresolved(name))
+    }
+
+    // parameter and return types are those of the globals
+    fn global_type(&self, name: &Identifier) -> Type {
+        self.globals.types.get(&name.key).cloned().unwrap_or_else(|| unresolved(name))
+    }
+
+    fn function(&self, name: &Identifier) -> Rc<Func> {
+        self.functions.get(&name.key).cloned().unwrap_or_else(|| unresolved(name))
+    }
+
+    fn assign(&mut self, name: &Identifier, value: Value) {
+        let scope = match self.frames.last_mut() {
+            Some(frame) if frame.vars.contains_key(&name.key) => frame,
+            _ => &mut self.globals,
+        };
+        scope.vars.insert(name.key.clone(), value);
+    }
+
+    // Input is read a line at a time and consumed a character at a time.
+    fn fill_input(&mut self) -> bool {
+        while self.input.is_empty() {
+            match self.io.read_line() {
+                Some(line) => {
+                    self.input.extend(line.chars());
+                    self.input.push_back('\n');
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.fill_input() && self.input.front().map_or(false, |c| c.is_whitespace()) {
+            self.input.pop_front();
+        }
+    }
+
+    fn at_eof(&mut self) -> bool {
+        self.skip_whitespace();
+        !self.fill_input()
+    }
+
+    fn read_integer(&mut self) -> EvalResult<i64> {
+        self.skip_whitespace();
+        let mut text = String::new();
+        if let Some(sign) = self.input.front().filter(|c| **c == '-' || **c == '+') {
+            text.push(*sign);
+            self.input.pop_front();
+        }
+        while let Some(digit) = self.input.front().filter(|c| c.is_ascii_digit()) {
+            text.push(*digit);
+            self.input.pop_front();
+        }
+        if text.is_empty() && !self.fill_input() {
+            return Err(error("read past the end of the input"));
+        }
+        let expected = || error(format!("expected an integer in the input, found '{}'", text));
+        let value: i64 = text.parse().map_err(|_| expected())?;
+        if !self.word_size.contains(value) {
+            let bits = self.word_size.bits();
+            return Err(error(format!("integer in the input out of range for {}-bit word: {}", bits, text)));
+        }
+        Ok(value)
+    }
+
+    // Like integers, characters are separated by whitespace.
+    fn read_char(&mut self) -> EvalResult<char> {
+        self.skip_whitespace();
+        self.input.pop_front().ok_or_else(|| error("read past the end of the input"))
+    }
+}
+
+// names are resolved before running, see `Interpreter`
+fn unresolved(name: &Identifier) -> ! {
+    panic!("'{}' was not resolved before running", name.name)
+}
+
+fn check_divisor(divisor: &Value) -> EvalResult<()> {
+    if *divisor == Value::Integer(0) {
+        return Err(error("division by zero"));
+    }
+    Ok(())
+}
+
+fn boolean(value: &Value) -> bool {
+    *value == Value::Boolean(true)
+}
+
+/// `succ` and `pred`: the next or previous value of the same type.
+fn step(value: Value, delta: i64, what: &str, word_size: WordSize) -> EvalResult<Value> {
+    let no_such = || error(format!("{} has no {}", value, what));
+    let stepped = match &value {
+        Value::Integer(i) => i.checked_add(delta).filter(|i| word_size.contains(*i)).map(Value::Integer),
+        Value::Char(c) => u32::try_from(*c as i64 + delta).ok().and_then(char::from_u32).map(Value::Char),
+        Value::Boolean(b) => match (*b, delta) {
+            (false, 1) => Some(Value::Boolean(true)),
+            (true, -1) => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        Value::Enum(e, ordinal) => usize::try_from(*ordinal as i64 + delta)
+            .ok()
+            .filter(|o| *o < e.literals.len())
+            .map(|o| Value::Enum(e.clone(), o)),
+    };
+    stepped.ok_or_else(no_such)
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Span;
+    use crate::interpreter::{BufferIo, Interpreter, Value};
+    use crate::lexer::{Lexer, WordSize};
+    use crate::parser::Parser;
+
+    fn parser(source: &str) -> Parser {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false)
+    }
+
+    fn run(source: &str, input: &str) -> Vec<String> {
+        let program = parser(source).parse().unwrap();
+        let mut interpreter = Interpreter::new(BufferIo::with_input(input));
+        interpreter.run(program).unwrap();
+        interpreter.io().output.clone()
+    }
+
+    #[test]
+    fn test_run_samples() {
+        assert_eq!(run(include_str!("./../../winzig_test_programs/winzig_05"), "3"), ["27", "1"]);
+        assert_eq!(
+            run(include_str!("./../../winzig_test_programs/winzig_07"), ""),
+            ["1", "1", "2", "3", "5", "8", "13"]
+        );
+        assert_eq!(run(include_str!("./../../winzig_test_programs/winzig_10"), ""), ["1", "0"]);
+    }
+
+    #[test]
+    fn test_merge_sort() {
+        // calls are made for what they do with 'd := f(x)', 'd' being a global
+        let output = run(include_str!("./../../winzig_test_programs/winzig_12"), "5 3 9\n1 7 2\n");
+        assert_eq!(output, ["1", "2", "3", "5", "7", "9"]);
+    }
+
+    #[test]
+    fn test_calculator() {
+        let output = run(include_str!("./../../winzig_test_programs/winzig_15"), "2 + 3 * 4 .");
+        assert_eq!(output, ["14"]);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut interpreter = Interpreter::new(BufferIo::default());
+        let declarations = parser("const limit = 10; type color = (red, green, blue); var c : color; x : char;")
+            .parse_declarations()
+            .unwrap();
+        interpreter.declare(&declarations);
+
+        let mut evaluate = |source: &str| interpreter.evaluate(&parser(source).parse_expression().unwrap());
+        assert_eq!(evaluate("limit * 2 - 7 mod 4").unwrap(), Value::Integer(17));
+        assert_eq!(evaluate("-limit / 3").unwrap(), Value::Integer(-3));
+        assert_eq!(evaluate("ord(succ('a'))").unwrap(), Value::Integer(98));
+        assert_eq!(evaluate("(red < blue) and not (c <> red)").unwrap(), Value::Boolean(true));
+        assert_eq!(evaluate("succ(green)").unwrap().to_string(), "blue");
+        assert_eq!(evaluate("chr(65)").unwrap(), Value::Char('A'));
+
+        assert_eq!(evaluate("succ(blue)").unwrap_err().message, "blue has no successor");
+        assert_eq!(evaluate("1 / (limit - 10)").unwrap_err().message, "division by zero");
+        assert_eq!(evaluate("chr(-1)").unwrap_err().message, "chr(-1) is not a character");
+    }
+
+    #[test]
+    fn test_statements() {
+        let mut interpreter = Interpreter::new(BufferIo::with_input("12 x"));
+        let declarations = parser("var i, n : integer; c : char;").parse_declarations().unwrap();
+        interpreter.declare(&declarations);
+
+        let statements = parser(
+            "read(n, c); \
+             loop i := i + 1; if i * i > n then exit pool; \
+             case i of 1..3: output(\"small\"); 4, 5: output(\"medium\") otherwise output(\"large\") end; \
+             output(c, eof)",
+        )
+        .parse_statements()
+        .unwrap();
+        interpreter.execute(&statements).unwrap();
+        assert_eq!(interpreter.io().output, ["medium", "x", "true"]);
+
+        let error = interpreter.execute(&parser("read(n)").parse_statements().unwrap()).unwrap_err();
+        assert_eq!(error.message, "read past the end of the input");
+    }
+
+    #[test]
+    fn test_word_size() {
+        let mut interpreter = Interpreter::with_word_size(BufferIo::with_input("40000"), WordSize::W16);
+        let mut evaluate = |source: &str| interpreter.evaluate(&parser(source).parse_expression().unwrap());
+        assert_eq!(evaluate("32767 - 1 + 1").unwrap(), Value::Integer(32767));
+        assert_eq!(evaluate("32767 + 1").unwrap_err().message, "integer overflow for 16-bit word");
+        assert_eq!(evaluate("-32767 - 1").unwrap(), Value::Integer(-32768));
+        assert_eq!(evaluate("-32768").unwrap(), Value::Integer(-32768));
+        assert_eq!(evaluate("200 * 200").unwrap_err().message, "integer overflow for 16-bit word");
+        assert_eq!(evaluate("succ(32767)").unwrap_err().message, "32767 has no successor");
+
+        let declarations = parser("var n : integer;").parse_declarations().unwrap();
+        interpreter.declare(&declarations);
+        let error = interpreter.execute(&parser("read(n)").parse_statements().unwrap()).unwrap_err();
+        assert_eq!(error.message, "integer in the input out of range for 16-bit word: 40000");
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // with the stack of a main thread, which the REPL runs on, rather than a test thread's
+        let thread = std::thread::Builder::new().stack_size(8 << 20).spawn(|| {
+            let mut interpreter = Interpreter::new(BufferIo::default());
+            let source = "function f(n : integer) : integer; begin return (f(n + 1)) end f;";
+            interpreter.define_function(parser(source).parse_function().unwrap());
+            interpreter.evaluate(&parser("1 + f(0)").parse_expression().unwrap()).unwrap_err()
+        });
+        let error = thread.unwrap().join().unwrap();
+        assert_eq!(error.message, "call depth limit of 256 exceeded in 'f'");
+        // at the call that went too deep
+        assert_eq!(error.span, Span::new(49, 50, 1, 50));
+    }
+}
@@ -2,7 +2,10 @@
 
 use std::str::FromStr;
 
+use crate::diagnostic::{Diagnostic, Span};
+
 mod test;
+pub mod printer;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
@@ -24,9 +27,23 @@ pub enum Operator {
 
 impl Operator {
     fn is_operator_starting_char(c: char) -> bool {
-        match c {
-            '+' | '-' | '*' | '/' | '<' | '>' | '=' | ':' => true,
-            _ => false,
+        matches!(c, '+' | '-' | '*' | '/' | '<' | '>' | '=' | ':')
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::LessThan => "<",
+            Operator::LessEqual => "<=",
+            Operator::NotEqual => "<>",
+            Operator::GreaterThan => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::Equal => "=",
+            Operator::Assignment => ":=",
+            Operator::Swap => ":=:",
         }
     }
 }
@@ -110,6 +127,44 @@ impl Keyword {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Keyword::Program => "program",
+            Keyword::Var => "var",
+            Keyword::Const => "const",
+            Keyword::Type => "type",
+            Keyword::Function => "function",
+            Keyword::Return => "return",
+            Keyword::Begin => "begin",
+            Keyword::End => "end",
+            Keyword::Output => "output",
+            Keyword::If => "if",
+            Keyword::Then => "then",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Do => "do",
+            Keyword::Case => "case",
+            Keyword::Of => "of",
+            Keyword::Otherwise => "otherwise",
+            Keyword::Repeat => "repeat",
+            Keyword::For => "for",
+            Keyword::Until => "until",
+            Keyword::Loop => "loop",
+            Keyword::Pool => "pool",
+            Keyword::Exit => "exit",
+            Keyword::Mod => "mod",
+            Keyword::And => "and",
+            Keyword::Or => "or",
+            Keyword::Not => "not",
+            Keyword::Read => "read",
+            Keyword::Succ => "succ",
+            Keyword::Pred => "pred",
+            Keyword::Chr => "chr",
+            Keyword::Ord => "ord",
+            Keyword::Eof => "eof",
+        }
+    }
 }
 
 impl FromStr for Keyword {
@@ -124,6 +179,16 @@ impl FromStr for Keyword {
 }
 
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentKind {
+    /// `# ...` up to the end of the line
+    Line,
+    /// `## ...` up to the end of the line, documents a following `function` or `program`
+    DocLine,
+    /// `{ ... }`, documents a following `function` or `program`
+    Block,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Identifier(String),
@@ -132,8 +197,10 @@ pub enum Token {
     Integer(i64),
     Char(char),
     String(String),
-    Comment,
+    Comment(CommentKind, String),
     Whitespace,
+    /// A run of characters that cannot start any token.
+    Error(String),
 
     Newline,
     Dot,
@@ -145,52 +212,213 @@ pub enum Token {
     RightParen,
 }
 
+impl Token {
+    /// Name of the token class as used in the course's token listings: literals and
+    /// identifiers as `<identifier>`, `<integer>`, ..., keywords and symbols by spelling.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Token::Identifier(_) => "<identifier>",
+            Token::Keyword(keyword) => keyword.as_str(),
+            Token::Operator(operator) => operator.as_str(),
+            Token::Integer(_) => "<integer>",
+            Token::Char(_) => "<char>",
+            Token::String(_) => "<string>",
+            Token::Comment(_, _) => "<comment>",
+            Token::Whitespace => "<whitespace>",
+            Token::Error(_) => "<error>",
+            Token::Newline => "<newline>",
+            Token::Dot => ".",
+            Token::Dots => "..",
+            Token::Colon => ":",
+            Token::Semicolon => ";",
+            Token::Comma => ",",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+        }
+    }
+
+    /// Whitespace, newlines and comments, which carry no meaning for the parser.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace | Token::Newline | Token::Comment(_, _))
+    }
+}
+
 impl PartialEq<Token> for &Token {
     fn eq(&self, other: &Token) -> bool {
         *self == other
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    pub lexeme: String,
+}
+
+/// Width of the target machine word, which bounds the value of integer literals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WordSize {
+    W16,
+    W32,
+    #[default]
+    W64,
+}
+
+impl WordSize {
+    pub fn from_bits(bits: u32) -> Option<WordSize> {
+        match bits {
+            16 => Some(WordSize::W16),
+            32 => Some(WordSize::W32),
+            64 => Some(WordSize::W64),
+            _ => None,
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            WordSize::W16 => 16,
+            WordSize::W32 => 32,
+            WordSize::W64 => 64,
+        }
+    }
+
+    pub fn max_value(&self) -> i64 {
+        match self {
+            WordSize::W16 => i16::MAX as i64,
+            WordSize::W32 => i32::MAX as i64,
+            WordSize::W64 => i64::MAX,
+        }
+    }
+
+    pub fn min_value(&self) -> i64 {
+        match self {
+            WordSize::W16 => i16::MIN as i64,
+            WordSize::W32 => i32::MIN as i64,
+            WordSize::W64 => i64::MIN,
+        }
+    }
+
+    /// Whether `value` is an integer of this word.
+    pub fn contains(&self, value: i64) -> bool {
+        (self.min_value()..=self.max_value()).contains(&value)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LexerConfig {
+    pub word_size: WordSize,
+    /// Let `{ ... }` comments nest, so a region containing comments can be commented out.
+    pub nested_comments: bool,
+    /// Match keywords regardless of case and fold names to lowercase, as in Pascal-style
+    /// sources written with `BEGIN` and `End`. Identifier tokens hold the folded name, by
+    /// which names are compared; the lexeme keeps the spelling.
+    pub case_insensitive: bool,
+}
+
+impl LexerConfig {
+    /// The key by which `name` is compared with other names, see `case_insensitive`.
+    pub fn key(&self, name: &str) -> String {
+        if self.case_insensitive {
+            name.to_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
 pub struct Lexer {
     source_text: String,
+    config: LexerConfig,
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize,
+    start_line: usize,
+    start_column: usize,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer {
     pub fn new(source_text: String) -> Self {
+        Self::with_config(source_text, LexerConfig::default())
+    }
+
+    pub fn with_config(source_text: String, config: LexerConfig) -> Self {
         Self {
             source_text,
+            config,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            diagnostics: Vec::new(),
         }
     }
 
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn lex(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
+        self.tokenize().into_iter().map(|t| t.token).collect()
+    }
 
-        while !self.is_at_end() {
-            self.commit();
-            let token = self.scan_token();
+    /// Like `lex`, but keeps the span and the raw source text of every token.
+    pub fn tokenize(&mut self) -> Vec<SpannedToken> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token() {
             tokens.push(token);
         }
-
         tokens
     }
 
+    /// A lexer that starts at byte `offset` of `source_text`, on line `line`, to lex part of a
+    /// file with `next_token`. `offset` must be where some token of the whole file starts.
+    pub fn resume(source_text: String, config: LexerConfig, offset: usize, line: usize) -> Self {
+        let line_start = source_text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            current: offset,
+            line,
+            line_start,
+            ..Self::with_config(source_text, config)
+        }
+    }
+
+    /// The next token, or `None` at the end of the text.
+    pub fn next_token(&mut self) -> Option<SpannedToken> {
+        if self.is_at_end() {
+            return None;
+        }
+        self.commit();
+        let token = self.scan_token();
+        Some(SpannedToken {
+            token,
+            span: self.span(),
+            lexeme: self.selection(),
+        })
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source_text.len()
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        self.source_text.chars().nth(self.current - 1).unwrap()
+        let c = self.peek();
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.line_start = self.current;
+        }
+        c
     }
 
     fn commit(&mut self) {
         self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.source_text[self.line_start..self.start].chars().count() + 1;
     }
 
     fn rollback(&mut self) {
@@ -202,11 +430,11 @@ impl Lexer {
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source_text.chars().nth(self.current).unwrap()
-        }
+        self.source_text[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current, self.start_line, self.start_column)
     }
 
     fn scan_token(&mut self) -> Token {
@@ -234,7 +462,28 @@ impl Lexer {
         if let Some(token) = self.special_token() {
             return token;
         }
-        panic!("Unexpected character: {}", self.peek())
+        self.error()
+    }
+
+    fn starts_token(c: char) -> bool {
+        c.is_alphanumeric()
+            || Operator::is_operator_starting_char(c)
+            || matches!(c, '_' | '\'' | '"' | '#' | '{' | ' ' | '\t' | '\x0C' | '\x0B' | '\n' | ';' | ',' | '(' | ')' | '.')
+    }
+
+    fn error(&mut self) -> Token {
+        self.advance();
+        while !self.is_at_end() && !Self::starts_token(self.peek()) {
+            self.advance();
+        }
+        let text = self.selection();
+        let message = if text.chars().count() == 1 {
+            format!("unexpected character '{}'", text)
+        } else {
+            format!("unexpected characters '{}'", text)
+        };
+        self.diagnostics.push(Diagnostic::error(message, self.span()));
+        Token::Error(text)
     }
 
     fn identifier_or_keyword(&mut self) -> Option<Token> {
@@ -249,10 +498,11 @@ impl Lexer {
         }
         let text = self.selection();
 
-        if let Ok(keyword) = Keyword::from_str(&text) {
+        let key = self.config.key(&text);
+        if let Ok(keyword) = Keyword::from_str(&key) {
             return Some(Token::Keyword(keyword));
         }
-        Some(Token::Identifier(text))
+        Some(Token::Identifier(key))
     }
 
     fn operator(&mut self) -> Option<Token> {
@@ -307,69 +557,149 @@ impl Lexer {
     }
 
     fn integer(&mut self) -> Option<Token> {
-        if !self.peek().is_digit(10) {
+        if !self.peek().is_ascii_digit() {
             return None
         }
 
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
         let text = self.selection();
-        Some(Token::Integer(text.parse().unwrap()))
+        let max = self.config.word_size.max_value();
+        match text.parse::<i64>() {
+            // a literal may be negated, so the magnitude of the smallest value is let through
+            // and the parser reports it where it isn't, as in 32768 for a 16-bit word
+            Ok(value) if self.config.word_size.contains(-value) => Some(Token::Integer(value)),
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    format!("integer literal out of range for {}-bit word: {}", self.config.word_size.bits(), text),
+                    self.span(),
+                ));
+                Some(Token::Integer(max))
+            }
+        }
     }
 
+    // Char -> ''' any character ''' ;
+    // a single quote inside the literal is written twice, as in ''''.
     fn char(&mut self) -> Option<Token> {
         if self.peek() != '\'' {
             return None
         }
         self.advance();
 
-        let c = self.advance();
-
-        if self.peek() != '\'' {
-            panic!("Expected closing ' for char literal");
+        let value = match self.quoted_literal('\'') {
+            Some(value) => value,
+            None => {
+                self.diagnostics.push(Diagnostic::error("unterminated character literal", self.span()));
+                return Some(Token::Char('\0'));
+            }
+        };
+
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Token::Char(c)),
+            (None, _) => {
+                self.diagnostics.push(Diagnostic::error("empty character literal", self.span()));
+                Some(Token::Char('\0'))
+            }
+            (Some(c), Some(_)) => {
+                self.diagnostics.push(Diagnostic::error(
+                    "character literal must contain exactly one character",
+                    self.span(),
+                ));
+                Some(Token::Char(c))
+            }
         }
-        self.advance();
-
-        Some(Token::Char(c))
     }
 
+    // String -> '"' any characters '"' ;
+    // a double quote inside the literal is written twice, as in "say ""hi""".
     fn string(&mut self) -> Option<Token> {
         if self.peek() != '"' {
             return None
         }
         self.advance();
 
-        while self.peek() != '"' {
-            if self.advance() == '\n' {
-                panic!("Unexpected newline in string literal");
+        match self.quoted_literal('"') {
+            Some(value) => Some(Token::String(value)),
+            None => {
+                self.diagnostics.push(Diagnostic::error("unterminated string literal", self.span()));
+                Some(Token::String(self.selection()[1..].to_string()))
             }
         }
-        self.advance();
+    }
 
-        let text = self.selection();
-        Some(Token::String(text))
+    /// Consumes the rest of a quoted literal whose opening quote has been read and returns
+    /// its decoded value, or `None` if the line or the input ends before the closing quote.
+    fn quoted_literal(&mut self, quote: char) -> Option<String> {
+        let mut value = String::new();
+        loop {
+            let c = self.peek();
+            if c == '\n' || self.is_at_end() {
+                return None;
+            }
+            self.advance();
+            if c == quote {
+                if self.peek() != quote {
+                    return Some(value);
+                }
+                self.advance();
+            }
+            value.push(c);
+        }
     }
 
     fn comment(&mut self) -> Option<Token> {
         match self.peek() {
             '#' => {
+                self.advance();
+                let kind = if self.peek() == '#' {
+                    self.advance();
+                    CommentKind::DocLine
+                } else {
+                    CommentKind::Line
+                };
+                let text_start = self.current;
                 while self.peek() != '\n' && !self.is_at_end() {
                     self.advance();
                 }
-                Some(Token::Comment)
+                let text = self.source_text[text_start..self.current].to_string();
+                Some(Token::Comment(kind, text))
             },
             '{' => {
-                while self.peek() != '}' && !self.is_at_end() {
-                    if self.advance() == '\n' {
-                        self.line += 1;
+                self.advance();
+                let text_start = self.current;
+                let (line, line_start) = (self.line, self.line_start);
+                let mut depth = 1;
+                while !self.is_at_end() {
+                    match self.peek() {
+                        '{' if self.config.nested_comments => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
                     }
+                    self.advance();
                 }
                 if self.is_at_end() {
-                    panic!("Unexpected end of file in block comment");
+                    // report the opening brace and resume lexing after its line,
+                    // so the rest of the file is still checked
+                    let open = Span::new(self.start, self.start + 1, self.start_line, self.start_column);
+                    self.diagnostics.push(Diagnostic::error("unterminated block comment", open));
+                    self.current = text_start;
+                    self.line = line;
+                    self.line_start = line_start;
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                    let text = self.source_text[text_start..self.current].to_string();
+                    return Some(Token::Comment(CommentKind::Block, text));
                 }
+                let text = self.source_text[text_start..self.current].to_string();
                 self.advance();
-                Some(Token::Comment)
+                Some(Token::Comment(CommentKind::Block, text))
             },
             _ => None,
         }
@@ -391,7 +721,6 @@ impl Lexer {
         match self.peek() {
             '\n' => {
                 self.advance();
-                self.line += 1;
                 Some(Token::Newline)
             },
             ';' => {
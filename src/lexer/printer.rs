@@ -0,0 +1,24 @@
+use crate::lexer::SpannedToken;
+
+/// Formats tokens one per line as `line:column  category  lexeme`, the layout of the
+/// token listings in the WinZigC course materials. Trivia is left out unless asked for.
+pub fn token_listing(tokens: &[SpannedToken], include_trivia: bool) -> Vec<String> {
+    tokens
+        .iter()
+        .filter(|t| include_trivia || !t.token.is_trivia())
+        .map(|t| {
+            let position = format!("{}:{}", t.span.line, t.span.column);
+            format!("{:<8}{:<16}{}", position, t.token.category(), escape_lexeme(&t.lexeme))
+        })
+        .collect()
+}
+
+// keep every token on its own line
+fn escape_lexeme(lexeme: &str) -> String {
+    lexeme
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\x0B', "\\v")
+        .replace('\x0C', "\\f")
+}
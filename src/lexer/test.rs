@@ -1,7 +1,9 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::{Keyword, Lexer, Operator, Token};
+    use crate::lexer::{CommentKind, Keyword, Lexer, LexerConfig, Operator, SpannedToken, Token, WordSize};
+    use crate::diagnostic::Span;
+    use crate::lexer::printer::token_listing;
 
     #[test]
     fn test_lex_keyword() {
@@ -35,14 +37,14 @@ mod tests {
     fn test_lex_string() {
         let mut lexer = Lexer::new("\"hello world\"".to_string());
         let tokens = lexer.lex();
-        assert_eq!(tokens, vec![Token::String("\"hello world\"".to_string())]);
+        assert_eq!(tokens, vec![Token::String("hello world".to_string())]);
     }
 
     #[test]
     fn test_lex_comment() {
         let mut lexer = Lexer::new("# this is a comment".to_string());
         let tokens = lexer.lex();
-        assert_eq!(tokens, vec![Token::Comment]);
+        assert_eq!(tokens, vec![Token::Comment(CommentKind::Line, " this is a comment".to_string())]);
     }
 
     #[test]
@@ -80,8 +82,225 @@ mod tests {
                 Token::Integer(10),
                 Token::Semicolon,
                 Token::Whitespace,
-                Token::Comment
+                Token::Comment(CommentKind::Line, " comment".to_string())
             ]
         );
     }
+
+    #[test]
+    fn test_lex_integer_out_of_range() {
+        let mut lexer = Lexer::new("a := 99999999999999999999;".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(tokens[4], Token::Integer(i64::MAX));
+        assert_eq!(lexer.diagnostics().len(), 1);
+        assert!(lexer.diagnostics()[0].message.contains("out of range"));
+        assert_eq!(lexer.diagnostics()[0].span, Span::new(5, 25, 1, 6));
+    }
+
+    #[test]
+    fn test_lex_integer_word_size() {
+        let config = LexerConfig { word_size: WordSize::W16, ..LexerConfig::default() };
+        let mut lexer = Lexer::with_config("32767\n32768\n32769".to_string(), config);
+        let tokens = lexer.lex();
+        // the magnitude of the smallest value may be negated, which is the parser's to tell
+        assert_eq!(
+            tokens,
+            vec![Token::Integer(32767), Token::Newline, Token::Integer(32768), Token::Newline, Token::Integer(32767)]
+        );
+        assert_eq!(lexer.diagnostics().len(), 1);
+        assert_eq!(lexer.diagnostics()[0].span, Span::new(12, 17, 3, 1));
+
+        // which doesn't fit in 64 bits
+        let mut lexer = Lexer::new("9223372036854775808".to_string());
+        assert_eq!(lexer.lex(), [Token::Integer(i64::MAX)]);
+        assert_eq!(lexer.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn test_lex_string_escape() {
+        let mut lexer = Lexer::new("\"say \"\"hi\"\"\"".to_string());
+        let tokens = lexer.tokenize();
+        assert_eq!(
+            tokens,
+            vec![SpannedToken {
+                token: Token::String("say \"hi\"".to_string()),
+                span: Span::new(0, 12, 1, 1),
+                lexeme: "\"say \"\"hi\"\"\"".to_string(),
+            }]
+        );
+        assert!(lexer.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_lex_char() {
+        let mut lexer = Lexer::new("'a' ''''".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(tokens, vec![Token::Char('a'), Token::Whitespace, Token::Char('\'')]);
+        assert!(lexer.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_lex_bad_literals() {
+        let mut lexer = Lexer::new("'' 'ab' '\n\"abc\n".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Char('\0'),
+                Token::Whitespace,
+                Token::Char('a'),
+                Token::Whitespace,
+                Token::Char('\0'),
+                Token::Newline,
+                Token::String("abc".to_string()),
+                Token::Newline,
+            ]
+        );
+        let messages: Vec<&str> = lexer.diagnostics().iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "empty character literal",
+                "character literal must contain exactly one character",
+                "unterminated character literal",
+                "unterminated string literal",
+            ]
+        );
+        assert_eq!(lexer.diagnostics()[3].span, Span::new(10, 14, 2, 1));
+    }
+
+    #[test]
+    fn test_lexemes_round_trip() {
+        let source = include_str!("./../../winzig_test_programs/winzig_15");
+        let mut lexer = Lexer::new(source.to_string());
+        let text: String = lexer.tokenize().into_iter().map(|t| t.lexeme).collect();
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn test_lex_comment_kinds() {
+        let mut lexer = Lexer::new("## doc\n{ block\n text }".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Comment(CommentKind::DocLine, " doc".to_string()),
+                Token::Newline,
+                Token::Comment(CommentKind::Block, " block\n text ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_nested_comment() {
+        let mut lexer = Lexer::new("{ a { b } c".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Token::Comment(CommentKind::Block, " a { b ".to_string()));
+        assert_eq!(tokens[2], Token::Identifier("c".to_string()));
+
+        let source = "{ a { b } c } x";
+        let config = LexerConfig { nested_comments: true, ..LexerConfig::default() };
+        let mut lexer = Lexer::with_config(source.to_string(), config);
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Comment(CommentKind::Block, " a { b } c ".to_string()),
+                Token::Whitespace,
+                Token::Identifier("x".to_string()),
+            ]
+        );
+        assert!(lexer.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_lex_unterminated_comment() {
+        let mut lexer = Lexer::new("a { b\nc := 99999999999999999999".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(tokens[2], Token::Comment(CommentKind::Block, " b".to_string()));
+        assert_eq!(tokens[4], Token::Identifier("c".to_string()));
+        let diagnostics = lexer.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "unterminated block comment");
+        assert_eq!(diagnostics[0].span, Span::new(2, 3, 1, 3));
+        assert_eq!(diagnostics[1].span.line, 2);
+    }
+
+    #[test]
+    fn test_lex_error_tokens() {
+        let mut lexer = Lexer::new("a @$ b\n} ?".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Identifier("a".to_string()),
+                Token::Whitespace,
+                Token::Error("@$".to_string()),
+                Token::Whitespace,
+                Token::Identifier("b".to_string()),
+                Token::Newline,
+                Token::Error("}".to_string()),
+                Token::Whitespace,
+                Token::Error("?".to_string()),
+            ]
+        );
+        let diagnostics = lexer.diagnostics();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].message, "unexpected characters '@$'");
+        assert_eq!(diagnostics[0].span, Span::new(2, 4, 1, 3));
+        assert_eq!(diagnostics[1].message, "unexpected character '}'");
+        assert_eq!(diagnostics[1].span, Span::new(7, 8, 2, 1));
+    }
+
+    #[test]
+    fn test_token_listing() {
+        let mut lexer = Lexer::new("program p:\n  x := 'a'; # done".to_string());
+        let tokens = lexer.tokenize();
+        assert_eq!(
+            token_listing(&tokens, false),
+            vec![
+                "1:1     program         program",
+                "1:9     <identifier>    p",
+                "1:10    :               :",
+                "2:3     <identifier>    x",
+                "2:5     :=              :=",
+                "2:8     <char>          'a'",
+                "2:11    ;               ;",
+            ]
+        );
+        let listing = token_listing(&tokens, true);
+        assert_eq!(listing.len(), 14);
+        assert_eq!(listing[4], "1:11    <newline>       \\n");
+        assert_eq!(listing[13], "2:13    <comment>       # done");
+    }
+
+    #[test]
+    fn test_lex_keyword_case() {
+        let mut lexer = Lexer::new("BEGIN Begin_1 end".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Identifier("BEGIN".to_string()),
+                Token::Whitespace,
+                Token::Identifier("Begin_1".to_string()),
+                Token::Whitespace,
+                Token::Keyword(Keyword::End),
+            ]
+        );
+        // which the keyword-case lint reports once names are resolved
+        assert!(lexer.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_lex_case_insensitive() {
+        let config = LexerConfig { case_insensitive: true, ..LexerConfig::default() };
+        let mut lexer = Lexer::with_config("BEGIN MyVar End".to_string(), config);
+        let tokens = lexer.tokenize();
+        assert_eq!(tokens[0].token, Token::Keyword(Keyword::Begin));
+        assert_eq!(tokens[2].token, Token::Identifier("myvar".to_string()));
+        assert_eq!(tokens[2].lexeme, "MyVar");
+        assert_eq!(tokens[4].token, Token::Keyword(Keyword::End));
+        assert!(lexer.diagnostics().is_empty());
+    }
 }
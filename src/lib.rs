@@ -0,0 +1,12 @@
+pub mod analysis;
+pub mod check;
+pub mod diagnostic;
+pub mod highlight;
+pub mod ide;
+pub mod interpreter;
+pub mod lexer;
+pub mod lint;
+pub mod lsp;
+pub mod parser;
+pub mod repl;
+pub mod semantic;
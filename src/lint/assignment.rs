@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+
+use crate::analysis::variables::VariableFlow;
+use crate::parser::visit::{walk_statement, walk_statements, Visitor};
+use crate::parser::*;
+use crate::semantic::{ScopeId, SemanticModel, SymbolKind};
+
+use super::{Lint, Lints};
+
+/// Reports variables read where they may not have been assigned yet, once each, and stores
+/// whose values are never read, following every path through each body. Globals that a
+/// function uses aren't followed, since a call could assign or read them. A store of a
+/// call's result, as in `d := Hanoi(1, 3, 2, n)`, is how a function is called for what it
+/// does, and isn't reported.
+pub(super) fn check(program: &Program, model: &SemanticModel, lints: &mut Lints) {
+    let mut calls = CallStores(HashSet::new());
+    program.functions().iter().for_each(|f| walk_statements(&mut calls, &f.body.statements));
+    walk_statements(&mut calls, &program.body.statements);
+
+    let scopes = (0..program.functions().len()).map(ScopeId::Function).chain([ScopeId::Global]);
+    for scope in scopes {
+        let flow = VariableFlow::build(program, model, scope);
+        let mut reported = HashSet::new();
+        for read in flow.unassigned_reads() {
+            let symbol = model.symbol(read.symbol);
+            if symbol.kind != SymbolKind::Variable || !reported.insert(read.symbol) {
+                continue;
+            }
+            let message = match flow.reaching_definitions(read.span).is_empty() {
+                true => format!("variable '{}' is read before it is assigned", symbol.name),
+                false => format!("variable '{}' may be read before it is assigned", symbol.name),
+            };
+            lints.report(Lint::ReadBeforeAssignment, message, read.span);
+        }
+        for store in flow.dead_stores().into_iter().filter(|s| !calls.0.contains(&s.span.start)) {
+            let message = format!("the value stored in '{}' is never read", model.symbol(store.symbol).name);
+            lints.report(Lint::DeadStore, message, store.span);
+        }
+    }
+}
+
+/// The starts of the names assigned the result of a call and nothing more.
+struct CallStores(HashSet<usize>);
+
+impl Visitor for CallStores {
+    fn visit_statement(&mut self, statement: &Statement) {
+        if let Statement::Assign { assignment: Assignment::Assignment { name, exp } } = statement {
+            if is_call(exp) {
+                self.0.insert(name.span.start);
+            }
+        }
+        walk_statement(self, statement)
+    }
+}
+
+fn is_call(expression: &Expression) -> bool {
+    match expression {
+        Expression::Term(term) => matches!(**term, Term::Factor(Factor::Primary(Primary::Call { .. }))),
+        _ => false,
+    }
+}
@@ -0,0 +1,18 @@
+use std::str::FromStr;
+
+use crate::lexer::Keyword;
+use crate::semantic::{SemanticModel, Unresolved};
+
+use super::{Lint, Lints};
+
+/// Reports undeclared names spelled like a keyword in another case, such as `Eof` or `ORD`,
+/// which were probably meant as the keyword. A name the program declares, like a function
+/// called `Ord` or the literals of `type mark = (Begin, End)`, resolves and is left alone.
+pub(super) fn check(model: &SemanticModel, lints: &mut Lints) {
+    for Unresolved { name, span, .. } in model.unresolved() {
+        if let Ok(keyword) = Keyword::from_str(&name.to_lowercase()) {
+            let message = format!("'{}' is an identifier, not a keyword; did you mean '{}'?", name, keyword.as_str());
+            lints.report(Lint::KeywordCase, message, *span);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Lints: warnings about code that is valid but probably not what was meant. Each lint has
+//! an id, such as `unused-variable`, by which it can be allowed, or made an error with `deny`.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::Program;
+use crate::semantic::{SemanticModel, SymbolKind};
+
+mod assignment;
+mod keyword_case;
+mod shadowing;
+mod test;
+mod unused;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnusedConstant,
+    UnusedType,
+    UnusedFunction,
+    ReadBeforeAssignment,
+    DeadStore,
+    Shadowing,
+    KeywordCase,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UnusedConstant,
+        Lint::UnusedType,
+        Lint::UnusedFunction,
+        Lint::ReadBeforeAssignment,
+        Lint::DeadStore,
+        Lint::Shadowing,
+        Lint::KeywordCase,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedConstant => "unused-constant",
+            Lint::UnusedType => "unused-type",
+            Lint::UnusedFunction => "unused-function",
+            Lint::ReadBeforeAssignment => "read-before-assignment",
+            Lint::DeadStore => "dead-store",
+            Lint::Shadowing => "shadowing",
+            Lint::KeywordCase => "keyword-case",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.id() == id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The level of each lint; lints that aren't set warn.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<Lint, Level>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    lints: HashMap<String, Level>,
+}
+
+impl LintConfig {
+    /// Reads a config file with a `[lints]` table of ids and levels:
+    ///
+    /// ```toml
+    /// [lints]
+    /// unused-parameter = "allow"
+    /// read-before-assignment = "deny"
+    /// ```
+    pub fn from_toml(text: &str) -> Result<LintConfig, String> {
+        let file: ConfigFile = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        let mut config = LintConfig::default();
+        for (id, level) in file.lints {
+            let lint = Lint::from_id(&id).ok_or_else(|| format!("unknown lint '{}'", id))?;
+            config.set(lint, level);
+        }
+        Ok(config)
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+}
+
+/// The lints `config` doesn't allow, in source order, as warnings or, when denied, errors.
+pub fn lint(program: &Program, model: &SemanticModel, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut lints = Lints { config, diagnostics: Vec::new() };
+    unused::check(program, model, &mut lints);
+    assignment::check(program, model, &mut lints);
+    shadowing::check(model, &mut lints);
+    keyword_case::check(model, &mut lints);
+    lints.diagnostics.sort_by_key(|d| d.span.start);
+    lints.diagnostics
+}
+
+struct Lints<'a> {
+    config: &'a LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Lints<'_> {
+    fn report(&mut self, lint: Lint, message: String, span: Span) {
+        let diagnostic = match self.config.level(lint) {
+            Level::Allow => return,
+            Level::Warn => Diagnostic::warning(message, span),
+            Level::Deny => Diagnostic::error(message, span),
+        };
+        self.diagnostics.push(diagnostic.with_code(lint.id()));
+    }
+}
+
+fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Program => "program",
+        SymbolKind::Function => "function",
+        SymbolKind::Parameter => "parameter",
+        SymbolKind::Variable => "variable",
+        SymbolKind::Constant => "constant",
+        SymbolKind::EnumLiteral => "enumeration literal",
+        SymbolKind::Type => "type",
+    }
+}
@@ -0,0 +1,22 @@
+use crate::semantic::{ScopeId, SemanticModel};
+
+use super::{kind_name, Lint, Lints};
+
+/// Reports declarations in a function that hide a global declaration of the same name,
+/// such as a parameter named like a global variable or a local variable named like an
+/// enumeration literal.
+pub(super) fn check(model: &SemanticModel, lints: &mut Lints) {
+    for symbol in model.symbols() {
+        let (ScopeId::Function(_), Some(span)) = (symbol.scope, symbol.span) else {
+            continue;
+        };
+        let Some(global) = model.declared_in(ScopeId::Global, &symbol.key) else {
+            continue;
+        };
+        let global = model.symbol(global);
+        let what = if global.span.is_some() { "global" } else { "predeclared" };
+        let (kind, shadowed) = (kind_name(symbol.kind), kind_name(global.kind));
+        let message = format!("{} '{}' shadows {} {} '{}'", kind, symbol.name, what, shadowed, global.name);
+        lints.report(Lint::Shadowing, message, span);
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer::Lexer;
+    use crate::lint::{lint, Level, Lint, LintConfig};
+    use crate::parser::Parser;
+    use crate::semantic::SemanticModel;
+
+    fn messages(source: &str, config: &LintConfig) -> Vec<String> {
+        let mut lexer = Lexer::new(source.to_string());
+        let program = Parser::with_spans(lexer.tokenize(), false).parse().unwrap();
+        let model = SemanticModel::build(&program);
+        lint(&program, &model, config).iter().map(|d| d.to_string()).collect()
+    }
+
+    /// A config that allows every lint but `lint`.
+    fn only(lint: Lint) -> LintConfig {
+        let mut config = LintConfig::default();
+        Lint::ALL.into_iter().filter(|l| *l != lint).for_each(|l| config.set(l, Level::Allow));
+        config
+    }
+
+    #[test]
+    fn test_unused() {
+        let source = "program p:\n\
+                      const low = 1, high = 2;\n\
+                      type color = (red, green); shape = (square);\n\
+                      var n, m : integer;\n\
+                      function f(a, b : integer) : integer; begin return (f(a)) end f;\n\
+                      function g(c : integer) : integer; begin return (c + high) end g;\n\
+                      begin n := g(red); output(n) end p.";
+        assert_eq!(
+            messages(source, &LintConfig::default()),
+            [
+                "2:7: warning: constant 'low' is never used [unused-constant]",
+                "3:28: warning: type 'shape' is never used [unused-type]",
+                "4:8: warning: variable 'm' is never used [unused-variable]",
+                "5:10: warning: function 'f' is never used [unused-function]",
+                "5:15: warning: parameter 'b' is never used [unused-parameter]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_before_assignment() {
+        let source = "program p:\n\
+                      var n, m, k, g : integer;\n\
+                      function f(a : integer) : integer;\n\
+                      var x, y : integer;\n\
+                      begin x := x + 1; y := 1; output(y); g := 1; return (a) end f;\n\
+                      begin output(n); m := 1; output(m); k :=: m; output(g) end p.";
+        assert_eq!(
+            messages(source, &only(Lint::ReadBeforeAssignment)),
+            [
+                "5:12: warning: variable 'x' is read before it is assigned [read-before-assignment]",
+                "6:14: warning: variable 'n' is read before it is assigned [read-before-assignment]",
+                "6:37: warning: variable 'k' is read before it is assigned [read-before-assignment]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_on_some_paths() {
+        let source = "program p:\n\
+                      var n, m : integer;\n\
+                      begin read(n); if n > 0 then m := 1; output(m) end p.";
+        assert_eq!(
+            messages(source, &only(Lint::ReadBeforeAssignment)),
+            ["3:45: warning: variable 'm' may be read before it is assigned [read-before-assignment]"]
+        );
+    }
+
+    #[test]
+    fn test_dead_stores() {
+        let source = "program p:\n\
+                      var n, d : integer;\n\
+                      function f(a : integer) : integer;\n\
+                      var x : integer;\n\
+                      begin x := a; a := 2; x := a; return (x) end f;\n\
+                      begin n := 1; read(n); d := f(n); n := n + 1 end p.";
+        // storing a call's result only to call it isn't reported
+        assert_eq!(
+            messages(source, &only(Lint::DeadStore)),
+            [
+                "5:7: warning: the value stored in 'x' is never read [dead-store]",
+                "6:7: warning: the value stored in 'n' is never read [dead-store]",
+                "6:35: warning: the value stored in 'n' is never read [dead-store]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let source = "program p:\n\
+                      type color = (red, green);\n\
+                      var n : integer;\n\
+                      function f(n : integer) : integer;\n\
+                      var red : integer; boolean : char;\n\
+                      begin red := n; boolean := 'a'; output(red); return (ord(boolean)) end f;\n\
+                      begin n := f(1) end p.";
+        assert_eq!(
+            messages(source, &only(Lint::Shadowing)),
+            [
+                "4:12: warning: parameter 'n' shadows global variable 'n' [shadowing]",
+                "5:5: warning: variable 'red' shadows global enumeration literal 'red' [shadowing]",
+                "5:20: warning: variable 'boolean' shadows predeclared type 'boolean' [shadowing]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let source = "program p:\nvar n, m : integer;\nbegin output(n) end p.";
+        let mut config = LintConfig::from_toml("[lints]\nunused-variable = \"deny\"\n").unwrap();
+        config.set(Lint::ReadBeforeAssignment, Level::Allow);
+        assert_eq!(messages(source, &config), ["2:8: error: variable 'm' is never used [unused-variable]"]);
+
+        assert_eq!(LintConfig::from_toml("[lints]\nunused = \"allow\"").unwrap_err(), "unknown lint 'unused'");
+        assert!(LintConfig::from_toml("[lints]\nshadowing = \"off\"").is_err());
+        assert_eq!(LintConfig::from_toml("").unwrap().level(Lint::Shadowing), Level::Warn);
+    }
+
+    #[test]
+    fn test_keyword_case() {
+        let source = "program p:\n\
+                      const a = Eof, b = 1;\n\
+                      begin case a of Pool, 2: output(Ord(b)) end end p.";
+        assert_eq!(
+            messages(source, &only(Lint::KeywordCase)),
+            [
+                "2:11: warning: 'Eof' is an identifier, not a keyword; did you mean 'eof'? [keyword-case]",
+                "3:17: warning: 'Pool' is an identifier, not a keyword; did you mean 'pool'? [keyword-case]",
+                "3:33: warning: 'Ord' is an identifier, not a keyword; did you mean 'ord'? [keyword-case]",
+            ]
+        );
+
+        // names the program declares, also after the first of a list
+        let source = "program p:\n\
+                      const Begin = 1, End = 2; type mark = (Loop, Pool); var x, Read, y : integer;\n\
+                      function Ord(x : integer) : integer; begin return (Ord(x)) end Ord;\n\
+                      begin x := End + Begin; Read := Ord(x); y := ord(Loop); output(x, y, Read) end p.";
+        assert!(messages(source, &only(Lint::KeywordCase)).is_empty());
+    }
+}
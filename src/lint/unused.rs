@@ -0,0 +1,38 @@
+use std::collections::HashSet;
+
+use crate::parser::Program;
+use crate::semantic::{SemanticModel, SymbolId, SymbolKind};
+
+use super::{kind_name, Lint, Lints};
+
+/// Reports declared variables, parameters, constants, types and functions that nothing
+/// uses. The name after a function's `end` isn't a use, nor is a call from the function's
+/// own body, and a type is used when one of its literals is.
+pub(super) fn check(program: &Program, model: &SemanticModel, lints: &mut Lints) {
+    let end_names: HashSet<usize> =
+        program.functions().iter().map(|f| f.end_name.span.start).chain([program.end_name.span.start]).collect();
+    let is_used = |symbol: SymbolId| {
+        model.references_to(symbol).any(|r| {
+            !r.is_declaration && !end_names.contains(&r.span.start) && model.function_of(r.scope) != Some(symbol)
+        })
+    };
+
+    for (id, symbol) in model.symbols().iter().enumerate() {
+        let Some(span) = symbol.span else {
+            continue;
+        };
+        let lint = match symbol.kind {
+            SymbolKind::Variable => Lint::UnusedVariable,
+            SymbolKind::Parameter => Lint::UnusedParameter,
+            SymbolKind::Constant => Lint::UnusedConstant,
+            SymbolKind::Type => Lint::UnusedType,
+            SymbolKind::Function => Lint::UnusedFunction,
+            SymbolKind::Program | SymbolKind::EnumLiteral => continue,
+        };
+        if is_used(id) || (symbol.kind == SymbolKind::Type && model.literals_of(id).into_iter().any(is_used)) {
+            continue;
+        }
+        let message = format!("{} '{}' is never used", kind_name(symbol.kind), symbol.name);
+        lints.report(lint, message, span);
+    }
+}
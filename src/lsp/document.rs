@@ -0,0 +1,170 @@
+use lsp_types::{DiagnosticSeverity, NumberOrString, Position, Range, TextDocumentContentChangeEvent};
+
+use crate::check::{check, CheckOptions};
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::lexer::{Lexer, LexerConfig};
+use crate::lint::{lint, LintConfig};
+use crate::parser::incremental::ParsedFile;
+use crate::parser::{Parser, Program};
+use crate::semantic::SemanticModel;
+
+/// Converts between byte offsets and LSP positions, whose characters are UTF-16 code units.
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { starts }
+    }
+
+    pub fn position(&self, text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let character = text[self.starts[line]..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, text: &str, position: Position) -> usize {
+        let Some(start) = self.starts.get(position.line as usize) else {
+            return text.len();
+        };
+        let mut units = 0;
+        for (i, c) in text[*start..].char_indices() {
+            if units >= position.character as usize || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        text.len()
+    }
+}
+
+pub struct Analysis {
+    pub file: ParsedFile,
+    pub model: SemanticModel,
+}
+
+/// An open file with everything the server knows about it.
+pub struct Document {
+    pub text: String,
+    pub lines: LineIndex,
+    /// The most recent version of the text that parsed, if any did.
+    pub analysis: Option<Analysis>,
+    /// Whether `analysis` is of the current text, so that its positions can be used.
+    pub is_current: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    /// How the text is lexed, for the features that lex it again.
+    pub config: LexerConfig,
+}
+
+impl Document {
+    pub fn new(text: String, config: LexerConfig) -> Self {
+        let mut document = Self {
+            lines: LineIndex::new(&text),
+            text,
+            analysis: None,
+            is_current: false,
+            diagnostics: Vec::new(),
+            config,
+        };
+        document.analyze();
+        document
+    }
+
+    /// Applies the changes an editor sends, in order: a change with a range replaces the text
+    /// in it, one without replaces the whole text. While the text parses, ranges are edited
+    /// into the parsed file, which lexes and parses again only around them. The old analysis
+    /// is kept if the new text doesn't parse.
+    pub fn update(&mut self, changes: Vec<TextDocumentContentChangeEvent>) {
+        for change in changes {
+            let Some(range) = change.range else {
+                self.text = change.text;
+                self.lines = LineIndex::new(&self.text);
+                self.is_current = false;
+                continue;
+            };
+            let range = self.offset(range.start)..self.offset(range.end);
+            self.text.replace_range(range.clone(), &change.text);
+            self.lines = LineIndex::new(&self.text);
+            if let Some(analysis) = self.analysis.as_mut().filter(|_| self.is_current) {
+                self.is_current = analysis.file.edit(range, &change.text).is_ok();
+            }
+        }
+        self.analyze();
+    }
+
+    // Checks the current text, parsing it whole unless an edit already did.
+    fn analyze(&mut self) {
+        let file = match self.analysis.take() {
+            Some(analysis) if self.is_current => Ok(analysis.file),
+            stale => {
+                self.analysis = stale;
+                ParsedFile::parse(self.text.clone(), self.config.clone())
+            }
+        };
+        let Ok(file) = file else {
+            self.is_current = false;
+            self.diagnostics = syntax_errors(&self.text, &self.config);
+            return;
+        };
+        let program = file.program();
+        let model = SemanticModel::build(program);
+        let mut diagnostics = file.diagnostics();
+        diagnostics.extend(model.diagnostics().iter().cloned());
+        diagnostics.extend(check(program, &model, &CheckOptions::default()));
+        diagnostics.extend(lint(program, &model, &LintConfig::default()));
+        diagnostics.sort_by_key(|d| d.span.start);
+        self.diagnostics = diagnostics;
+        self.analysis = Some(Analysis { file, model });
+        self.is_current = true;
+    }
+
+    /// The program, when the current text parses.
+    pub fn program(&self) -> Option<&Program> {
+        self.analysis.as_ref().filter(|_| self.is_current).map(|a| a.file.program())
+    }
+
+    /// The resolved names, when the current text parses.
+    pub fn model(&self) -> Option<&SemanticModel> {
+        self.analysis.as_ref().filter(|_| self.is_current).map(|a| &a.model)
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range::new(self.lines.position(&self.text, span.start), self.lines.position(&self.text, span.end))
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        self.lines.offset(&self.text, position)
+    }
+
+    pub fn lsp_diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        self.diagnostics
+            .iter()
+            .map(|d| lsp_types::Diagnostic {
+                range: self.range(d.span),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                code: d.code.map(|code| NumberOrString::String(code.to_string())),
+                source: Some("winzig".to_string()),
+                message: d.message.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+// The errors of a text that doesn't parse, which is lexed and parsed whole to find them all.
+fn syntax_errors(text: &str, config: &LexerConfig) -> Vec<Diagnostic> {
+    let mut lexer = Lexer::with_config(text.to_string(), config.clone());
+    let tokens = lexer.tokenize();
+    let mut diagnostics = lexer.diagnostics().to_vec();
+    let mut parser = Parser::with_spans(tokens, false).with_word_size(config.word_size);
+    let result = parser.parse();
+    diagnostics.extend(parser.diagnostics());
+    diagnostics.extend(result.err());
+    diagnostics
+}
@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, FoldingRangeRequest, GotoDefinition, HoverRequest, PrepareRenameRequest,
+    References, Rename, Request as _, SemanticTokensFullRequest, SignatureHelpRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse, DocumentSymbol,
+    DocumentSymbolParams, DocumentSymbolResponse, Documentation, FoldingRange, FoldingRangeKind, FoldingRangeParams,
+    FoldingRangeProviderCapability, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, LanguageString, Location, MarkedString, OneOf, ParameterInformation, ParameterLabel,
+    Position, PrepareRenameResponse, PublishDiagnosticsParams, ReferenceParams, RenameOptions, RenameParams,
+    SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend,
+    SemanticTokensOptions, SemanticTokensParams, SemanticTokensResult, SemanticTokensServerCapabilities,
+    ServerCapabilities, SignatureHelp, SignatureHelpOptions, SignatureHelpParams, SignatureInformation,
+    InitializeParams, TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+    WorkspaceEdit,
+};
+use serde::Deserialize;
+
+use crate::analysis::variables::VariableFlow;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::ide::completion::{self, CompletionKind};
+use crate::ide::folding::folding_ranges;
+use crate::ide::outline::{outline, OutlineItem};
+use crate::ide::rename::rename;
+use crate::ide::semantic_tokens::{semantic_tokens, SemanticToken};
+use crate::lexer::{LexerConfig, WordSize};
+use crate::semantic::{Reference, SymbolKind};
+
+pub mod document;
+mod test;
+
+use document::Document;
+
+pub type LspResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: Default::default(),
+        })),
+        completion_provider: Some(CompletionOptions::default()),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            ..Default::default()
+        }),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: SEMANTIC_TOKEN_TYPES.to_vec(),
+                    token_modifiers: SEMANTIC_TOKEN_MODIFIERS.to_vec(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        ..Default::default()
+    }
+}
+
+/// The lexer options a client may send as `initializationOptions`, as in
+/// `{"wordSize": 16, "nestedComments": true, "caseInsensitive": true}`.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct InitializationOptions {
+    word_size: Option<u32>,
+    nested_comments: bool,
+    case_insensitive: bool,
+}
+
+fn lexer_config(params: InitializeParams) -> LspResult<LexerConfig> {
+    let options: InitializationOptions = match params.initialization_options {
+        Some(options) => serde_json::from_value(options)?,
+        None => InitializationOptions::default(),
+    };
+    let word_size = match options.word_size {
+        Some(bits) => WordSize::from_bits(bits).ok_or_else(|| format!("unsupported word size {}", bits))?,
+        None => WordSize::default(),
+    };
+    Ok(LexerConfig { word_size, nested_comments: options.nested_comments, case_insensitive: options.case_insensitive })
+}
+
+/// Serves one client until it shuts down.
+pub fn run(connection: Connection) -> LspResult<()> {
+    let params = connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server { documents: HashMap::new(), config: lexer_config(serde_json::from_value(params)?)? };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection.sender.send(Message::Response(server.request(request)))?;
+            }
+            Message::Notification(notification) => {
+                for outgoing in server.notification(notification) {
+                    connection.sender.send(Message::Notification(outgoing))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    documents: HashMap<Url, Document>,
+    /// How every document is lexed, from the initialization options.
+    config: LexerConfig,
+}
+
+impl Server {
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            DocumentSymbolRequest::METHOD => self.dispatch::<DocumentSymbolRequest>(request, Self::document_symbols),
+            GotoDefinition::METHOD => self.dispatch::<GotoDefinition>(request, Self::definition),
+            References::METHOD => self.dispatch::<References>(request, Self::references),
+            HoverRequest::METHOD => self.dispatch::<HoverRequest>(request, Self::hover),
+            PrepareRenameRequest::METHOD => self.dispatch::<PrepareRenameRequest>(request, Self::prepare_rename),
+            Rename::METHOD => self.try_dispatch::<Rename>(request, Self::rename),
+            Completion::METHOD => self.dispatch::<Completion>(request, Self::completion),
+            SignatureHelpRequest::METHOD => self.dispatch::<SignatureHelpRequest>(request, Self::signature_help),
+            SemanticTokensFullRequest::METHOD => {
+                self.dispatch::<SemanticTokensFullRequest>(request, Self::semantic_tokens)
+            }
+            FoldingRangeRequest::METHOD => self.dispatch::<FoldingRangeRequest>(request, Self::folding_ranges),
+            method => {
+                let message = format!("unsupported request '{}'", method);
+                Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+            }
+        }
+    }
+
+    fn dispatch<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: fn(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        let id = request.id.clone();
+        match request.extract::<R::Params>(R::METHOD) {
+            Ok((id, params)) => Response::new_ok(id, handler(self, params)),
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, format!("{:?}", error)),
+        }
+    }
+
+    /// Like `dispatch`, for requests that can fail with a message for the user.
+    fn try_dispatch<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: fn(&Self, R::Params) -> Result<R::Result, Diagnostic>,
+    ) -> Response {
+        let id = request.id.clone();
+        match request.extract::<R::Params>(R::METHOD) {
+            Ok((id, params)) => match handler(self, params) {
+                Ok(result) => Response::new_ok(id, result),
+                Err(error) => Response::new_err(id, ErrorCode::RequestFailed as i32, error.message),
+            },
+            Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, format!("{:?}", error)),
+        }
+    }
+
+    /// Updates the open documents, returning the notifications to send back.
+    fn notification(&mut self, notification: Notification) -> Vec<Notification> {
+        let (uri, version) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = notification.extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                else {
+                    return Vec::new();
+                };
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), Document::new(document.text, self.config.clone()));
+                (document.uri, Some(document.version))
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(params) =
+                    notification.extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+                else {
+                    return Vec::new();
+                };
+                let document = params.text_document;
+                let config = &self.config;
+                let previous = self.documents.entry(document.uri.clone());
+                previous.or_insert_with(|| Document::new(String::new(), config.clone())).update(params.content_changes);
+                (document.uri, Some(document.version))
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) =
+                    notification.extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                else {
+                    return Vec::new();
+                };
+                self.documents.remove(&params.text_document.uri);
+                (params.text_document.uri, None)
+            }
+            _ => return Vec::new(),
+        };
+        let diagnostics = self.documents.get(&uri).map(|d| d.lsp_diagnostics()).unwrap_or_default();
+        let params = PublishDiagnosticsParams { uri, diagnostics, version };
+        vec![Notification::new(PublishDiagnostics::METHOD.to_string(), params)]
+    }
+
+    /// The document and the resolved name under the cursor.
+    fn reference_at(&self, position: &TextDocumentPositionParams) -> Option<(&Document, &Reference)> {
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.offset(position.position);
+        let reference = document.model()?.reference_at(offset)?;
+        Some((document, reference))
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let mut program = document_symbol(document, outline(document.program()?));
+        // the program covers the whole file, comments included
+        program.range = document.range(Span::new(0, document.text.len(), 1, 1));
+        Some(DocumentSymbolResponse::Nested(vec![program]))
+    }
+
+    fn semantic_tokens(&self, params: SemanticTokensParams) -> Option<SemanticTokensResult> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let mut data = Vec::new();
+        let mut previous = Position::new(0, 0);
+        for token in semantic_tokens(document.model()?) {
+            let range = document.range(token.span);
+            let delta_line = range.start.line - previous.line;
+            let delta_start = match delta_line {
+                0 => range.start.character - previous.character,
+                _ => range.start.character,
+            };
+            data.push(lsp_types::SemanticToken {
+                delta_line,
+                delta_start,
+                length: range.end.character - range.start.character,
+                token_type: token_type(token.kind),
+                token_modifiers_bitset: token_modifiers(&token),
+            });
+            previous = range.start;
+        }
+        Some(SemanticTokensResult::Tokens(SemanticTokens { result_id: None, data }))
+    }
+
+    // folds are found from the keywords, so they are there while the text doesn't parse
+    fn folding_ranges(&self, params: FoldingRangeParams) -> Option<Vec<FoldingRange>> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let ranges = folding_ranges(&document.text, &document.config)
+            .into_iter()
+            .map(|fold| document.range(fold.span))
+            // editors only fold whole lines
+            .filter(|range| range.start.line < range.end.line)
+            .map(|range| FoldingRange {
+                start_line: range.start.line,
+                end_line: range.end.line,
+                kind: Some(FoldingRangeKind::Region),
+                ..Default::default()
+            })
+            .collect();
+        Some(ranges)
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let (document, reference) = self.reference_at(&position)?;
+        let span = document.model()?.symbol(reference.symbol).span?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(position.text_document.uri, document.range(span))))
+    }
+
+    fn references(&self, params: ReferenceParams) -> Option<Vec<Location>> {
+        let position = params.text_document_position;
+        let (document, reference) = self.reference_at(&position)?;
+        let model = document.model()?;
+        let locations = model
+            .references_to(reference.symbol)
+            .filter(|r| params.context.include_declaration || !r.is_declaration)
+            .map(|r| Location::new(position.text_document.uri.clone(), document.range(r.span)))
+            .collect();
+        Some(locations)
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let (document, reference) = self.reference_at(&params.text_document_position_params)?;
+        let symbol = document.model()?.symbol(reference.symbol);
+        let declaration = MarkedString::LanguageString(LanguageString {
+            language: "winzig".to_string(),
+            value: symbol.detail.clone(),
+        });
+        let contents = match flow_note(document, reference) {
+            Some(note) => HoverContents::Array(vec![declaration, MarkedString::String(note)]),
+            None => HoverContents::Scalar(declaration),
+        };
+        Some(Hover { contents, range: Some(document.range(reference.span)) })
+    }
+
+    fn prepare_rename(&self, params: TextDocumentPositionParams) -> Option<PrepareRenameResponse> {
+        let (document, reference) = self.reference_at(&params)?;
+        let symbol = document.model()?.symbol(reference.symbol);
+        // predeclared names can't be renamed
+        symbol.span?;
+        Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range: document.range(reference.span),
+            placeholder: symbol.name.clone(),
+        })
+    }
+
+    fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>, Diagnostic> {
+        let position = params.text_document_position;
+        let Some(document) = self.documents.get(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(model) = document.model() else {
+            return Err(Diagnostic::error("the file has syntax errors".to_string(), Span::default()));
+        };
+        let edits = rename(model, document.offset(position.position), &params.new_name, &document.config)?
+            .into_iter()
+            .map(|edit| TextEdit::new(document.range(edit.span), edit.new_text))
+            .collect();
+        Ok(Some(WorkspaceEdit::new(HashMap::from([(position.text_document.uri, edits)]))))
+    }
+
+    // completion and signature help work from the last text that parsed, since the
+    // current text is usually incomplete
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.offset(position.position);
+        let model = document.analysis.as_ref().map(|a| &a.model);
+        let items = completion::completions(&document.text, offset, model, &document.config)
+            .into_iter()
+            .map(|c| CompletionItem {
+                label: c.label,
+                kind: Some(match c.kind {
+                    CompletionKind::Keyword => CompletionItemKind::KEYWORD,
+                    CompletionKind::Variable | CompletionKind::Parameter => CompletionItemKind::VARIABLE,
+                    CompletionKind::Constant => CompletionItemKind::CONSTANT,
+                    CompletionKind::EnumLiteral => CompletionItemKind::ENUM_MEMBER,
+                    CompletionKind::Type => CompletionItemKind::CLASS,
+                    CompletionKind::Function => CompletionItemKind::FUNCTION,
+                }),
+                detail: c.detail,
+                ..Default::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn signature_help(&self, params: SignatureHelpParams) -> Option<SignatureHelp> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = document.offset(position.position);
+        let program = document.analysis.as_ref()?.file.program();
+        let help = completion::signature_help(&document.text, offset, program, &document.config)?;
+        let utf16 = |offset: usize| help.label[..offset].encode_utf16().count() as u32;
+        let parameters = help
+            .parameters
+            .iter()
+            .map(|p| ParameterInformation {
+                label: ParameterLabel::LabelOffsets([utf16(p.range.start), utf16(p.range.end)]),
+                documentation: None,
+            })
+            .collect();
+        let signature = SignatureInformation {
+            documentation: help.documentation.clone().map(Documentation::String),
+            label: help.label.clone(),
+            parameters: Some(parameters),
+            active_parameter: None,
+        };
+        Some(SignatureHelp {
+            signatures: vec![signature],
+            active_signature: Some(0),
+            active_parameter: Some(help.active_parameter as u32),
+        })
+    }
+}
+
+const SEMANTIC_TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::FUNCTION,
+];
+
+const SEMANTIC_TOKEN_MODIFIERS: [SemanticTokenModifier; 2] =
+    [SemanticTokenModifier::DECLARATION, SemanticTokenModifier::READONLY];
+
+/// What the flow of its body says about a variable where it is used: which stores its value
+/// may come from, and whether a value stored there is read.
+fn flow_note(document: &Document, reference: &Reference) -> Option<String> {
+    let flow = VariableFlow::build(document.program()?, document.model()?, reference.scope);
+    let access = flow.access_at(reference.span)?;
+    let mut notes = Vec::new();
+    if access.reads() {
+        let definitions = flow.reaching_definitions(access.span);
+        let lines: Vec<String> = definitions.iter().map(|d| d.span.line.to_string()).collect();
+        notes.push(match (&lines[..], flow.is_definitely_assigned(access.span)) {
+            ([], _) => "Not assigned on any path to here.".to_string(),
+            ([line], true) => format!("Assigned on line {}.", line),
+            (lines, true) => format!("Assigned on line {}, depending on the path.", lines.join(" or ")),
+            (lines, false) => format!("Assigned on line {} on some paths only.", lines.join(" or ")),
+        });
+    }
+    if access.writes() {
+        notes.push(match flow.is_live_after(access.span) {
+            true => "The value stored here may be read.".to_string(),
+            false => "The value stored here is never read.".to_string(),
+        });
+    }
+    Some(notes.join(" "))
+}
+
+/// Indexes into `SEMANTIC_TOKEN_TYPES`.
+fn token_type(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Program => 0,
+        SymbolKind::Type => 1,
+        SymbolKind::EnumLiteral => 2,
+        SymbolKind::Constant | SymbolKind::Variable => 3,
+        SymbolKind::Parameter => 4,
+        SymbolKind::Function => 5,
+    }
+}
+
+/// Bits for `SEMANTIC_TOKEN_MODIFIERS`; constants are read-only variables.
+fn token_modifiers(token: &SemanticToken) -> u32 {
+    let declaration = token.is_declaration as u32;
+    let readonly = (token.kind == SymbolKind::Constant) as u32;
+    declaration | readonly << 1
+}
+
+#[allow(deprecated)]
+fn document_symbol(document: &Document, item: OutlineItem) -> DocumentSymbol {
+    let children = item.children.into_iter().map(|child| document_symbol(document, child)).collect::<Vec<_>>();
+    DocumentSymbol {
+        name: item.name,
+        detail: Some(item.detail),
+        kind: match item.kind {
+            SymbolKind::Program => lsp_types::SymbolKind::MODULE,
+            SymbolKind::Constant => lsp_types::SymbolKind::CONSTANT,
+            SymbolKind::Type => lsp_types::SymbolKind::ENUM,
+            SymbolKind::EnumLiteral => lsp_types::SymbolKind::ENUM_MEMBER,
+            SymbolKind::Variable | SymbolKind::Parameter => lsp_types::SymbolKind::VARIABLE,
+            SymbolKind::Function => lsp_types::SymbolKind::FUNCTION,
+        },
+        tags: None,
+        deprecated: None,
+        range: document.range(item.span),
+        selection_range: document.range(item.name_span),
+        children: (!children.is_empty()).then_some(children),
+    }
+}
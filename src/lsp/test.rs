@@ -0,0 +1,467 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::thread::JoinHandle;
+
+    use lsp_server::{Connection, Message, Notification, Request, RequestId, ResponseError};
+    use lsp_types::notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as LspNotification,
+        PublishDiagnostics,
+    };
+    use lsp_types::request::{
+        Completion, DocumentSymbolRequest, FoldingRangeRequest, GotoDefinition, HoverRequest, Initialize,
+        PrepareRenameRequest, References, Rename, Request as LspRequest, SemanticTokensFullRequest, Shutdown,
+        SignatureHelpRequest,
+    };
+    use lsp_types::*;
+
+    use crate::lsp::document::LineIndex;
+    use crate::lsp::{run, LspResult};
+
+    /// Drives a server running on another thread, the way an editor would.
+    struct Client {
+        connection: Connection,
+        server: JoinHandle<LspResult<()>>,
+        notifications: VecDeque<Notification>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            Self::start_with(InitializeParams::default())
+        }
+
+        fn start_with(params: InitializeParams) -> Self {
+            let (server, connection) = Connection::memory();
+            let server = std::thread::spawn(move || run(server));
+            let mut client = Client { connection, server, notifications: VecDeque::new(), next_id: 0 };
+            client.request::<Initialize>(params);
+            client.notify::<Initialized>(InitializedParams {});
+            client
+        }
+
+        fn request<R: LspRequest>(&mut self, params: R::Params) -> R::Result {
+            let result = self.try_request::<R>(params);
+            assert!(result.is_ok(), "{:?}", result.err());
+            result.unwrap()
+        }
+
+        fn try_request<R: LspRequest>(&mut self, params: R::Params) -> Result<R::Result, ResponseError> {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            let request = Request::new(id.clone(), R::METHOD.to_string(), params);
+            self.connection.sender.send(Message::Request(request)).unwrap();
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) if response.id == id => {
+                        if let Some(error) = response.error {
+                            return Err(error);
+                        }
+                        return Ok(serde_json::from_value(response.result.unwrap_or_default()).unwrap());
+                    }
+                    Message::Notification(notification) => self.notifications.push_back(notification),
+                    message => panic!("unexpected message {:?}", message),
+                }
+            }
+        }
+
+        fn notify<N: LspNotification>(&mut self, params: N::Params) {
+            let notification = Notification::new(N::METHOD.to_string(), params);
+            self.connection.sender.send(Message::Notification(notification)).unwrap();
+        }
+
+        fn diagnostics(&mut self) -> PublishDiagnosticsParams {
+            let notification = match self.notifications.pop_front() {
+                Some(notification) => notification,
+                None => match self.connection.receiver.recv().unwrap() {
+                    Message::Notification(notification) => notification,
+                    message => panic!("unexpected message {:?}", message),
+                },
+            };
+            assert_eq!(notification.method, PublishDiagnostics::METHOD);
+            serde_json::from_value(notification.params).unwrap()
+        }
+
+        fn open(&mut self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "winzig".to_string(), 1, text.to_string()),
+            });
+            self.diagnostics()
+        }
+
+        fn change(
+            &mut self,
+            version: i32,
+            changes: Vec<TextDocumentContentChangeEvent>,
+        ) -> PublishDiagnosticsParams {
+            self.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri(), version),
+                content_changes: changes,
+            });
+            self.diagnostics()
+        }
+
+        fn shutdown(mut self) {
+            self.request::<Shutdown>(());
+            self.notify::<Exit>(());
+            self.server.join().unwrap().unwrap();
+        }
+    }
+
+    fn uri() -> Url {
+        Url::parse("file:///merge.wz").unwrap()
+    }
+
+    fn position(text: &str, needle: &str, nth: usize) -> TextDocumentPositionParams {
+        let offset = text.match_indices(needle).nth(nth).unwrap().0;
+        let position = LineIndex::new(text).position(text, offset);
+        TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri()), position)
+    }
+
+    /// A change replacing the first `old` in `text`, and the text it leaves.
+    fn replace(text: &str, old: &str, new_text: &str) -> (TextDocumentContentChangeEvent, String) {
+        let start = text.find(old).unwrap();
+        let lines = LineIndex::new(text);
+        let range = Range::new(lines.position(text, start), lines.position(text, start + old.len()));
+        let change =
+            TextDocumentContentChangeEvent { range: Some(range), range_length: None, text: new_text.to_string() };
+        (change, text.replacen(old, new_text, 1))
+    }
+
+    const MERGE: &str = include_str!("./../../winzig_test_programs/winzig_12");
+
+    #[test]
+    fn test_diagnostics() {
+        let mut client = Client::start();
+        let published = client.open(&uri(), MERGE);
+        assert_eq!(published.version, Some(1));
+        // the sample checks cleanly: all that's reported are warnings
+        assert!(published.diagnostics.iter().all(|d| d.severity == Some(DiagnosticSeverity::WARNING)));
+        let lints: Vec<_> = published.diagnostics.iter().filter_map(|d| d.code.clone()).collect();
+        assert_eq!(
+            lints,
+            [
+                NumberOrString::String("unused-variable".to_string()),
+                NumberOrString::String("shadowing".to_string()),
+                NumberOrString::String("dead-store".to_string())
+            ]
+        );
+
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "program p: begin output(1 end p.".to_string(),
+            }],
+        });
+        let published = client.diagnostics();
+        assert_eq!(published.version, Some(2));
+        assert_eq!(published.diagnostics.len(), 1);
+        assert_eq!(published.diagnostics[0].message, "Expected ')', found 'end'");
+        assert_eq!(published.diagnostics[0].range.start, Position::new(0, 26));
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut client = Client::start();
+        client.open(&uri(), MERGE);
+
+        // the call to Merge in MergeSort
+        let call = position(MERGE, "Merge (", 1);
+        let definition = client.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: call.clone(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let Some(GotoDefinitionResponse::Scalar(location)) = definition else {
+            panic!("expected a single location, got {:?}", definition);
+        };
+        assert_eq!(location.range.start, position(MERGE, "Merge (", 0).position);
+
+        let references = client
+            .request::<References>(ReferenceParams {
+                text_document_position: call.clone(),
+                context: ReferenceContext { include_declaration: false },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .unwrap();
+        let lines: Vec<u32> = references.iter().map(|l| l.range.start.line).collect();
+        assert_eq!(lines, [121, 133]);
+
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position(MERGE, "nexthigh := middle", 0),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        let HoverContents::Array(contents) = hover.contents else {
+            panic!("unexpected hover {:?}", hover.contents);
+        };
+        let [MarkedString::LanguageString(declaration), MarkedString::String(note)] = &contents[..] else {
+            panic!("unexpected hover {:?}", contents);
+        };
+        assert_eq!(declaration.value, "nexthigh : integer");
+        assert_eq!(note, "The value stored here may be read.");
+
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position(MERGE, "nexthigh > high", 0),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        let HoverContents::Array(contents) = hover.contents else {
+            panic!("unexpected hover {:?}", hover.contents);
+        };
+        let note = "Assigned on line 99 or 104 or 112, depending on the path.";
+        assert_eq!(contents[1], MarkedString::String(note.to_string()));
+
+        // a function has no flow to describe
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position(MERGE, "Merge (", 0),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        assert!(matches!(hover.contents, HoverContents::Scalar(_)));
+
+        // whitespace
+        let hover = client.request::<HoverRequest>(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri()),
+                Position::new(1, 0),
+            ),
+            work_done_progress_params: Default::default(),
+        });
+        assert!(hover.is_none());
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let mut client = Client::start();
+        let opened = client.open(&uri(), MERGE);
+        let messages = |published: &PublishDiagnosticsParams| {
+            published.diagnostics.iter().map(|d| (d.range.start.line, d.message.clone())).collect::<Vec<_>>()
+        };
+
+        // changes apply in order, each to the text the one before left
+        let (line, text) = replace(MERGE, "function Merge ", "\nfunction Merge ");
+        let (statement, text) = replace(&text, "nexthigh := middle+1", "nexthigh := middle+1+0");
+        let published = client.change(2, vec![line, statement]);
+        let moved: Vec<_> =
+            messages(&opened).into_iter().map(|(line, m)| (if line > 90 { line + 1 } else { line }, m)).collect();
+        assert_eq!(messages(&published), moved);
+        let references = |client: &mut Client, text: &str| {
+            let references = client.request::<References>(ReferenceParams {
+                text_document_position: position(text, "Merge (", 1),
+                context: ReferenceContext { include_declaration: false },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            });
+            references.unwrap().iter().map(|l| l.range.start.line).collect::<Vec<_>>()
+        };
+        assert_eq!(references(&mut client, &text), [122, 134]);
+
+        // an edit that doesn't parse, then one that makes the text parse again
+        let (broken, text) = replace(&text, "middle+1+0", "middle+1+");
+        assert_eq!(client.change(3, vec![broken]).diagnostics[0].message, "Expected primary, found ';'");
+        let (fixed, text) = replace(&text, "middle+1+", "middle+1");
+        assert_eq!(messages(&client.change(4, vec![fixed])), moved);
+        assert_eq!(references(&mut client, &text), [122, 134]);
+
+        // a change without a range replaces the whole text
+        let whole = TextDocumentContentChangeEvent { range: None, range_length: None, text: MERGE.to_string() };
+        assert_eq!(messages(&client.change(5, vec![whole])), messages(&opened));
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let mut client = Client::start();
+        client.open(&uri(), MERGE);
+        let symbols = client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let Some(DocumentSymbolResponse::Nested(symbols)) = symbols else {
+            panic!("expected nested symbols, got {:?}", symbols);
+        };
+        assert_eq!(symbols.len(), 1);
+        let program = &symbols[0];
+        assert_eq!(program.name, "MergeSortTest");
+        assert_eq!(program.kind, SymbolKind::MODULE);
+
+        let children = program.children.as_ref().unwrap();
+        let names: Vec<&str> = children.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names.len(), 1 + 24 + 8);
+        assert_eq!(&names[..3], ["Array", "a1", "a2"]);
+        assert_eq!(children[0].children.as_ref().unwrap().len(), 2);
+
+        let merge = children.iter().find(|s| s.name == "Merge").unwrap();
+        assert_eq!(merge.kind, SymbolKind::FUNCTION);
+        assert_eq!(merge.detail.as_deref(), Some("function Merge(low, middle, high : integer) : integer"));
+        let locals: Vec<&str> = merge.children.as_ref().unwrap().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(locals, ["low", "middle", "high", "i", "last", "nextlow", "nexthigh"]);
+        assert!(merge.range.start.line < merge.range.end.line);
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_semantic_tokens_and_folding() {
+        let mut client = Client::start();
+        let text = "program p:\nconst c = 1;\nbegin\n    output(c)\nend p.";
+        client.open(&uri(), text);
+        let tokens = client.request::<SemanticTokensFullRequest>(SemanticTokensParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let Some(SemanticTokensResult::Tokens(tokens)) = tokens else {
+            panic!("expected tokens, got {:?}", tokens);
+        };
+        let data: Vec<[u32; 5]> = tokens
+            .data
+            .iter()
+            .map(|t| [t.delta_line, t.delta_start, t.length, t.token_type, t.token_modifiers_bitset])
+            .collect();
+        // 'p' is a namespace, 'c' a read-only variable; bit 0 marks declarations
+        assert_eq!(data, [[0, 8, 1, 0, 1], [1, 6, 1, 3, 3], [2, 11, 1, 3, 2], [1, 4, 1, 0, 0]]);
+
+        let folds = client.request::<FoldingRangeRequest>(FoldingRangeParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let lines: Vec<(u32, u32)> = folds.unwrap().iter().map(|f| (f.start_line, f.end_line)).collect();
+        assert_eq!(lines, [(2, 4)]);
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_initialization_options() {
+        let options = serde_json::json!({ "wordSize": 16, "caseInsensitive": true });
+        let mut client =
+            Client::start_with(InitializeParams { initialization_options: Some(options), ..Default::default() });
+        let text = "PROGRAM p:\nVAR x : Integer;\nBEGIN\n    x := 40000;\n    Output(x)\nEND p.";
+        let published = client.open(&uri(), text);
+        let messages: Vec<_> = published.diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, ["integer literal out of range for 16-bit word: 40000"]);
+
+        let folds = client.request::<FoldingRangeRequest>(FoldingRangeParams {
+            text_document: TextDocumentIdentifier::new(uri()),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let lines: Vec<(u32, u32)> = folds.unwrap().iter().map(|f| (f.start_line, f.end_line)).collect();
+        assert_eq!(lines, [(2, 5)]);
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut client = Client::start();
+        client.open(&uri(), MERGE);
+
+        let call = position(MERGE, "Merge (", 1);
+        let prepared = client.request::<PrepareRenameRequest>(call.clone());
+        let Some(PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }) = prepared else {
+            panic!("unexpected response {:?}", prepared);
+        };
+        assert_eq!(placeholder, "Merge");
+        assert_eq!(range.start, call.position);
+
+        let edit = client
+            .request::<Rename>(RenameParams {
+                text_document_position: call,
+                new_name: "Combine".to_string(),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        let edits = &edit.changes.unwrap()[&uri()];
+        let lines: Vec<u32> = edits.iter().map(|e| e.range.start.line).collect();
+        assert_eq!(lines, [91, 121, 133]);
+        assert!(edits.iter().all(|e| e.new_text == "Combine"));
+
+        let error = client
+            .try_request::<Rename>(RenameParams {
+                text_document_position: position(MERGE, "i := 1;", 0),
+                new_name: "low".to_string(),
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap_err();
+        assert_eq!(error.message, "'low' is already declared in this scope");
+
+        let predeclared = client.request::<PrepareRenameRequest>(position(MERGE, "integer", 0));
+        assert_eq!(predeclared, None);
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_completion_while_typing() {
+        let mut client = Client::start();
+        client.open(&uri(), MERGE);
+
+        // a call to Merge half written, which no longer parses
+        let needle = "d:=MergeSort ( start, middle );";
+        let typing = MERGE.replacen(needle, "d:=Merge ( start, mid", 1);
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: typing.clone() }],
+        });
+        assert!(!client.diagnostics().diagnostics.is_empty());
+
+        let cursor = position(&typing, "d:=Merge ( start, mid", 0);
+        let cursor = TextDocumentPositionParams {
+            position: Position::new(cursor.position.line, cursor.position.character + 21),
+            ..cursor
+        };
+        let completions = client.request::<Completion>(CompletionParams {
+            text_document_position: cursor.clone(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let Some(CompletionResponse::Array(items)) = completions else {
+            panic!("expected completion items, got {:?}", completions);
+        };
+        let middle = items.iter().find(|i| i.label == "middle").unwrap();
+        assert_eq!(middle.kind, Some(CompletionItemKind::VARIABLE));
+        assert_eq!(middle.detail.as_deref(), Some("middle : integer"));
+        assert!(items.iter().any(|i| i.label == "not" && i.kind == Some(CompletionItemKind::KEYWORD)));
+
+        let help = client
+            .request::<SignatureHelpRequest>(SignatureHelpParams {
+                context: None,
+                text_document_position_params: cursor,
+                work_done_progress_params: Default::default(),
+            })
+            .unwrap();
+        assert_eq!(help.signatures[0].label, "function Merge(low, middle, high : integer) : integer");
+        assert_eq!(help.active_parameter, Some(1));
+        let parameters = help.signatures[0].parameters.as_ref().unwrap();
+        assert_eq!(parameters[1].label, ParameterLabel::LabelOffsets([20, 26]));
+
+        // navigation only uses positions from the current text
+        let hover = client.request::<HoverRequest>(HoverParams {
+            text_document_position_params: position(&typing, "nexthigh := middle", 0),
+            work_done_progress_params: Default::default(),
+        });
+        assert!(hover.is_none());
+        client.shutdown();
+    }
+
+    #[test]
+    fn test_line_index() {
+        let text = "ab\n\u{e9}\u{1f600}x\n";
+        let index = LineIndex::new(text);
+        let offset = text.find('x').unwrap();
+        assert_eq!(index.position(text, offset), Position::new(1, 3));
+        assert_eq!(index.offset(text, Position::new(1, 3)), offset);
+        assert_eq!(index.offset(text, Position::new(0, 99)), 2);
+        assert_eq!(index.offset(text, Position::new(9, 0)), text.len());
+    }
+}
@@ -1,15 +1,171 @@
-use crate::lexer::Lexer;
-use crate::parser::tree::StringTree;
-use clap::{Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use win_zig::lexer::{Lexer, LexerConfig, SpannedToken, WordSize};
+use win_zig::lexer::printer::token_listing;
+use win_zig::parser::tree::StringTree;
+use win_zig::analysis::callgraph::CallGraph;
+use win_zig::analysis::cfg::ControlFlowGraph;
+use win_zig::analysis::metrics::{self, Thresholds};
+use win_zig::check::{self, CheckOptions};
+use win_zig::lint::{self, Level, Lint, LintConfig};
+use win_zig::semantic::SemanticModel;
+use win_zig::{highlight, ide, parser, repl};
 
 
-mod lexer;
-mod parser;
+#[derive(ValueEnum, Clone, Debug)]
+enum HighlightFormat {
+    Html,
+    Ansi,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum MetricsFormat {
+    Table,
+    Json,
+}
+
+/// How sources are lexed, taken by every command.
+#[derive(Args, Debug)]
+struct LexerArgs {
+    /// target word size in bits (16, 32 or 64), bounds integer literals
+    #[arg(long, value_parser = parse_word_size, default_value = "64")]
+    word_size: WordSize,
+
+    /// allow { ... } comments to nest
+    #[arg(long, default_value = "false")]
+    nested_comments: bool,
+
+    /// treat keywords and identifiers case-insensitively
+    #[arg(long, default_value = "false")]
+    case_insensitive: bool,
+}
+
+impl LexerArgs {
+    fn config(&self) -> LexerConfig {
+        LexerConfig {
+            word_size: self.word_size,
+            nested_comments: self.nested_comments,
+            case_insensitive: self.case_insensitive,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// evaluate declarations, statements and expressions interactively
+    Repl {
+        #[command(flatten)]
+        lexer: LexerArgs,
+    },
+    /// report errors and warnings without printing the ast
+    Check {
+        source: String,
+
+        #[command(flatten)]
+        lexer: LexerArgs,
+
+        /// warn when a case over an enumeration has no otherwise and misses literals
+        #[arg(long, default_value = "false")]
+        exhaustive_case: bool,
+
+        /// read lint levels from a TOML file with a [lints] table, e.g. unused-parameter = "allow"
+        #[arg(long, value_name = "FILE")]
+        lints: Option<String>,
+
+        /// don't report a lint, e.g. unused-parameter
+        #[arg(long, value_name = "LINT", value_parser = parse_lint)]
+        allow: Vec<Lint>,
+
+        /// report a lint as a warning
+        #[arg(long, value_name = "LINT", value_parser = parse_lint)]
+        warn: Vec<Lint>,
+
+        /// report a lint as an error
+        #[arg(long, value_name = "LINT", value_parser = parse_lint)]
+        deny: Vec<Lint>,
+    },
+    /// print which functions call which, marking recursive and unreachable functions
+    Callgraph {
+        source: String,
+
+        #[command(flatten)]
+        lexer: LexerArgs,
+
+        #[arg(long, value_enum, default_value = "dot")]
+        format: GraphFormat,
+    },
+    /// print the control-flow graph of the main program or a function in Graphviz DOT
+    Cfg {
+        source: String,
+
+        #[command(flatten)]
+        lexer: LexerArgs,
+
+        /// the function to print instead of the main program
+        #[arg(long)]
+        function: Option<String>,
+    },
+    /// print statement counts, nesting, cyclomatic complexity and calls for each function
+    Metrics {
+        source: String,
+
+        #[command(flatten)]
+        lexer: LexerArgs,
+
+        #[arg(long, value_enum, default_value = "table")]
+        format: MetricsFormat,
+
+        /// warn about bodies with more statements than this
+        #[arg(long, value_name = "N", default_value = "50")]
+        max_statements: usize,
+
+        /// warn about ifs, loops and cases nested deeper than this
+        #[arg(long, value_name = "N", default_value = "4")]
+        max_nesting: usize,
+
+        /// warn about bodies with a higher cyclomatic complexity than this
+        #[arg(long, value_name = "N", default_value = "10")]
+        max_complexity: usize,
+
+        /// warn about functions with more parameters than this
+        #[arg(long, value_name = "N", default_value = "5")]
+        max_parameters: usize,
+    },
+    /// rename a variable, constant, type or function and print the changed source
+    Rename {
+        source: String,
+
+        #[command(flatten)]
+        lexer: LexerArgs,
+
+        /// position of the declaration or any use of the name, as LINE:COLUMN
+        #[arg(long, value_parser = parse_position)]
+        at: (usize, usize),
+
+        /// the new name
+        #[arg(long)]
+        to: String,
+
+        /// rewrite the source file instead of printing it
+        #[arg(short, long, default_value = "false")]
+        write: bool,
+    },
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct WinZigArgs {
-    source: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(required = true)]
+    source: Option<String>,
 
     /// verbose mode
     #[arg(short, long, default_value = "false")]
@@ -19,35 +175,284 @@ struct WinZigArgs {
     #[arg(short, long, conflicts_with = "ast", default_value = "false")]
     tokens: bool,
 
+    /// include whitespace, newlines and comments in the token listing
+    #[arg(long, requires = "tokens", default_value = "false")]
+    trivia: bool,
+
+    /// print the source with syntax highlighting
+    #[arg(long, value_enum, conflicts_with = "tokens")]
+    highlight: Option<HighlightFormat>,
+
+    /// colour functions, enumeration literals, constants and types when highlighting
+    #[arg(long, requires = "highlight", default_value = "false")]
+    semantic: bool,
+
     /// print ast
     #[arg(short, long, conflicts_with = "tokens", default_value = "true")]
     ast: bool,
+
+    #[command(flatten)]
+    lexer: LexerArgs,
+}
+
+fn parse_word_size(s: &str) -> Result<WordSize, String> {
+    s.parse::<u32>()
+        .ok()
+        .and_then(WordSize::from_bits)
+        .ok_or_else(|| format!("unsupported word size '{}', expected 16, 32 or 64", s))
+}
+
+fn parse_position(s: &str) -> Result<(usize, usize), String> {
+    let position = s.split_once(':').and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)));
+    position.ok_or_else(|| format!("invalid position '{}', expected LINE:COLUMN", s))
+}
+
+fn parse_lint(s: &str) -> Result<Lint, String> {
+    Lint::from_id(s).ok_or_else(|| {
+        let ids: Vec<&str> = Lint::ALL.iter().map(|l| l.id()).collect();
+        format!("unknown lint '{}', expected one of {}", s, ids.join(", "))
+    })
+}
+
+fn parse_or_exit(source: &str, tokens: Vec<SpannedToken>, word_size: WordSize, verbose: bool) -> parser::Program {
+    let mut parser = parser::Parser::with_spans(tokens, verbose).with_word_size(word_size);
+    let result = parser.parse();
+    let mut errors = parser.diagnostics();
+    errors.extend(result.as_ref().err().cloned());
+    for error in &errors {
+        eprintln!("{}: {}", source, error);
+    }
+    match result {
+        Ok(program) if errors.is_empty() => program,
+        _ => std::process::exit(1),
+    }
 }
 
 fn main() {
     let args = WinZigArgs::parse();
+    match args.command {
+        Some(Command::Repl { lexer }) => {
+            repl::run(lexer.config());
+            return;
+        }
+        Some(Command::Check { source, lexer, exhaustive_case, lints, allow, warn, deny }) => {
+            // the file sets the defaults, flags override it
+            let mut config = match lints {
+                Some(path) => read_lint_config(&path),
+                None => LintConfig::default(),
+            };
+            for (lints, level) in [(allow, Level::Allow), (warn, Level::Warn), (deny, Level::Deny)] {
+                lints.into_iter().for_each(|lint| config.set(lint, level));
+            }
+            check(&source, &lexer, &CheckOptions { exhaustive_case }, &config);
+            return;
+        }
+        Some(Command::Callgraph { source, lexer, format }) => {
+            callgraph(&source, &lexer, format);
+            return;
+        }
+        Some(Command::Cfg { source, lexer, function }) => {
+            cfg(&source, &lexer, function.as_deref());
+            return;
+        }
+        Some(Command::Metrics {
+            source,
+            lexer,
+            format,
+            max_statements,
+            max_nesting,
+            max_complexity,
+            max_parameters,
+        }) => {
+            let thresholds = Thresholds {
+                statements: max_statements,
+                nesting: max_nesting,
+                complexity: max_complexity,
+                parameters: max_parameters,
+            };
+            measure(&source, &lexer, format, &thresholds);
+            return;
+        }
+        Some(Command::Rename { source, lexer, at, to, write }) => {
+            rename(&source, &lexer, at, &to, write);
+            return;
+        }
+        None => {}
+    }
+    let source = args.source.unwrap();
 
     // read source file
-    let source_text = std::fs::read_to_string(&args.source).unwrap();
+    let source_text = read_or_exit(&source);
 
     // lexical analysis
-    let mut lexer = Lexer::new(source_text);
-    let tokens = lexer.lex();
+    let mut lexer = Lexer::with_config(source_text, args.lexer.config());
+    let tokens = lexer.tokenize();
+    for diagnostic in lexer.diagnostics() {
+        eprintln!("{}: {}", source, diagnostic);
+    }
+    let has_errors = lexer.diagnostics().iter().any(|d| d.is_error());
+    if args.tokens {
+        for line in token_listing(&tokens, args.trivia) {
+            println!("{}", line);
+        }
+    }
+    if has_errors {
+        std::process::exit(1);
+    }
     if args.tokens {
-        for token in &tokens {
-            println!("{:?}", token);
+        return;
+    }
+
+    if let Some(format) = &args.highlight {
+        let model = if args.semantic {
+            let program = parse_or_exit(&source, tokens.clone(), args.lexer.word_size, args.verbose);
+            Some(SemanticModel::build(&program))
+        } else {
+            None
+        };
+        match format {
+            HighlightFormat::Html => print!("{}", highlight::to_html(&tokens, model.as_ref(), &source)),
+            HighlightFormat::Ansi => println!("{}", highlight::to_ansi(&tokens, model.as_ref())),
         }
         return;
     }
 
     // ast construction
-    let mut parser = parser::Parser::new(tokens, args.verbose);
-    let program = parser.parse();
+    let program = parse_or_exit(&source, tokens, args.lexer.word_size, args.verbose);
     if args.ast {
         let tree = program.get_string_tree(0);
         for line in tree {
             println!("{}", line);
         }
-        return;
+    }
+}
+
+fn read_or_exit(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        std::process::exit(1);
+    })
+}
+
+/// Reads, lexes and parses a source, reporting what is wrong with it and exiting if anything is.
+fn load(source: &str, lexer: &LexerArgs) -> (String, parser::Program) {
+    let source_text = read_or_exit(source);
+    let config = lexer.config();
+    let mut lexer = Lexer::with_config(source_text.clone(), config.clone());
+    let tokens = lexer.tokenize();
+    for diagnostic in lexer.diagnostics() {
+        eprintln!("{}: {}", source, diagnostic);
+    }
+    // the parser skips what didn't lex, so its errors are reported too
+    let program = parse_or_exit(source, tokens, config.word_size, false);
+    if lexer.diagnostics().iter().any(|d| d.is_error()) {
+        std::process::exit(1);
+    }
+    (source_text, program)
+}
+
+fn read_lint_config(path: &str) -> LintConfig {
+    let text = read_or_exit(path);
+    LintConfig::from_toml(&text).unwrap_or_else(|message| {
+        eprintln!("{}: {}", path, message);
+        std::process::exit(1);
+    })
+}
+
+// Reports every error, so it reads the source itself rather than stopping at those of the parser.
+fn check(source: &str, lexer: &LexerArgs, options: &CheckOptions, lints: &LintConfig) {
+    let config = lexer.config();
+    let mut lexer = Lexer::with_config(read_or_exit(source), config.clone());
+    let tokens = lexer.tokenize();
+    let mut diagnostics = lexer.diagnostics().to_vec();
+    let mut parser = parser::Parser::with_spans(tokens, false).with_word_size(config.word_size);
+    let result = parser.parse();
+    diagnostics.extend(parser.diagnostics());
+    match result {
+        Ok(program) => {
+            let model = SemanticModel::build(&program);
+            diagnostics.extend(model.diagnostics().iter().cloned());
+            diagnostics.extend(check::check(&program, &model, options));
+            diagnostics.extend(lint::lint(&program, &model, lints));
+        }
+        Err(diagnostic) => diagnostics.push(diagnostic),
+    }
+    diagnostics.sort_by_key(|d| d.span.start);
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", source, diagnostic);
+    }
+    if diagnostics.iter().any(|d| d.is_error()) {
+        std::process::exit(1);
+    }
+}
+
+fn callgraph(source: &str, lexer: &LexerArgs, format: GraphFormat) {
+    let (_, program) = load(source, lexer);
+    let model = SemanticModel::build(&program);
+    let graph = CallGraph::build(&program, &model);
+    match format {
+        GraphFormat::Dot => print!("{}", graph.to_dot(&program.name.name)),
+        GraphFormat::Json => println!("{}", graph.to_json()),
+    }
+}
+
+fn cfg(source: &str, lexer: &LexerArgs, function: Option<&str>) {
+    let (_, program) = load(source, lexer);
+    let config = lexer.config();
+    let (name, body) = match function {
+        None => (&program.name.name, &program.body),
+        Some(function) => match program.functions().iter().find(|f| f.name.key == config.key(function)) {
+            Some(func) => (&func.name.name, &func.body),
+            None => {
+                eprintln!("{}: no function '{}'", source, function);
+                std::process::exit(1);
+            }
+        },
+    };
+    print!("{}", ControlFlowGraph::build(body).to_dot(name));
+}
+
+fn measure(source: &str, lexer: &LexerArgs, format: MetricsFormat, thresholds: &Thresholds) {
+    let (_, program) = load(source, lexer);
+    let model = SemanticModel::build(&program);
+    let metrics = metrics::measure(&program, &model);
+    match format {
+        MetricsFormat::Table => print!("{}", metrics::to_table(&metrics)),
+        MetricsFormat::Json => println!("{}", metrics::to_json(&metrics)),
+    }
+    for diagnostic in thresholds.check(&program, &metrics) {
+        eprintln!("{}: {}", source, diagnostic);
+    }
+}
+
+fn rename(source: &str, lexer: &LexerArgs, (line, column): (usize, usize), new_name: &str, write: bool) {
+    let (source_text, program) = load(source, lexer);
+    let model = SemanticModel::build(&program);
+
+    let Some(offset) = ide::offset_at(&source_text, line, column) else {
+        eprintln!("{}: no position {}:{} in the file", source, line, column);
+        std::process::exit(1);
+    };
+    match ide::rename::rename(&model, offset, new_name, &lexer.config()) {
+        Ok(edits) => {
+            let renamed = ide::apply_edits(&source_text, &edits);
+            if write {
+                std::fs::write(source, renamed).unwrap_or_else(|e| {
+                    eprintln!("{}: {}", source, e);
+                    std::process::exit(1);
+                });
+            } else {
+                print!("{}", renamed);
+            }
+        }
+        // errors that aren't about a particular name are reported at the requested position
+        Err(diagnostic) if diagnostic.span.line == 0 => {
+            eprintln!("{}: {}:{}: {}: {}", source, line, column, diagnostic.severity, diagnostic.message);
+            std::process::exit(1);
+        }
+        Err(diagnostic) => {
+            eprintln!("{}: {}", source, diagnostic);
+            std::process::exit(1);
+        }
     }
 }
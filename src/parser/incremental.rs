@@ -0,0 +1,395 @@
+use std::ops::Range;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{Keyword, Lexer, LexerConfig, SpannedToken, Token};
+
+use super::visit::{walk_statement_mut, walk_statements_mut, NameUsage, VisitorMut};
+use super::*;
+
+// How many characters past its end the lexer may look before deciding where a token ends.
+const LOOKAHEAD: usize = 2;
+
+/// What had to be parsed again after an edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reparsed {
+    Program,
+    /// The function with this index in `Program::functions`.
+    Function(usize),
+    /// A statement directly in the body of a function, or of the program when `function` is `None`.
+    Statement { function: Option<usize>, index: usize },
+}
+
+/// A program with the text and tokens it was parsed from, so that it can be updated after
+/// an edit without lexing and parsing the whole text again.
+#[derive(Debug)]
+pub struct ParsedFile {
+    text: String,
+    config: LexerConfig,
+    tokens: Vec<SpannedToken>,
+    program: Program,
+    lexer_diagnostics: Vec<Diagnostic>,
+    parser_diagnostics: Vec<Diagnostic>,
+    units: Vec<Unit>,
+    relexed: usize,
+    reparsed: Reparsed,
+}
+
+// Token ranges of the parts of the program that can be parsed on their own. Statement
+// units start after the preceding 'begin' or ';', function units after the preceding
+// significant token so that a doc comment before 'function' belongs to the function.
+#[derive(Debug, Clone)]
+struct Unit {
+    tokens: Range<usize>,
+    part: Reparsed,
+}
+
+impl ParsedFile {
+    pub fn parse(text: String, config: LexerConfig) -> ParseResult<ParsedFile> {
+        let mut lexer = Lexer::with_config(text.clone(), config.clone());
+        let tokens = lexer.tokenize();
+        let lexer_diagnostics = lexer.diagnostics().to_vec();
+        let mut parser = Parser::with_spans(tokens.clone(), false).with_word_size(config.word_size);
+        let program = parser.parse()?;
+        let parser_diagnostics = parser.diagnostics();
+        let units = units(&tokens, &program);
+        let relexed = tokens.len();
+        Ok(ParsedFile {
+            text,
+            config,
+            tokens,
+            program,
+            lexer_diagnostics,
+            parser_diagnostics,
+            units,
+            relexed,
+            reparsed: Reparsed::Program,
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[SpannedToken] {
+        &self.tokens
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn into_program(self) -> Program {
+        self.program
+    }
+
+    /// The errors of the lexer and those the parser found without stopping, such as integer
+    /// literals out of range.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.lexer_diagnostics.iter().chain(&self.parser_diagnostics).cloned().collect()
+    }
+
+    /// Number of tokens lexed by the last `edit`.
+    pub fn relexed(&self) -> usize {
+        self.relexed
+    }
+
+    /// The smallest part of the program the last `edit` parsed again.
+    pub fn reparsed(&self) -> Reparsed {
+        self.reparsed
+    }
+
+    /// Replaces the text in the byte range `range` with `new_text`. Only the tokens around
+    /// the edit are lexed again, and only the statement or function containing it is parsed
+    /// again when the edit stays inside one; the rest of the tree is kept. When the edited
+    /// text doesn't parse, the error is returned and the file is left as it was.
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) -> ParseResult<()> {
+        let mut text = self.text.clone();
+        text.replace_range(range.clone(), new_text);
+        let shift = Shift::new(&self.text, &text, &range, new_text);
+
+        // lex from the first token whose end the lexer saw within a few characters of the edit,
+        // until a token ends where one ended before
+        let mut first = self.tokens.partition_point(|t| t.span.start < range.start).saturating_sub(1);
+        while first > 0 && self.text[self.tokens[first - 1].span.end..range.start].chars().count() < LOOKAHEAD {
+            first -= 1;
+        }
+        let offset = self.tokens.get(first).map_or(range.start, |t| t.span.start);
+        let line = self.tokens.get(first).map_or(1, |t| t.span.line);
+        let mut lexer = Lexer::resume(text.clone(), self.config.clone(), offset, line);
+        let mut relexed = Vec::new();
+        let mut old_end = first;
+        let edited_end = range.start + new_text.len();
+        let mut resynchronized = false;
+        while let Some(token) = lexer.next_token() {
+            let end = token.span.end;
+            relexed.push(token);
+            if end < edited_end {
+                continue;
+            }
+            let old_position = end - edited_end + range.end;
+            while old_end < self.tokens.len() && self.tokens[old_end].span.start < old_position {
+                old_end += 1;
+            }
+            if self.tokens.get(old_end).map_or(false, |t| t.span.start == old_position) {
+                resynchronized = true;
+                break;
+            }
+        }
+        if !resynchronized {
+            old_end = self.tokens.len();
+        }
+
+        let relexed_count = relexed.len();
+        let new_end = first + relexed.len();
+        let mut tokens = self.tokens[..first].to_vec();
+        tokens.extend(relexed);
+        tokens.extend(self.tokens[old_end..].iter().map(|t| {
+            let mut t = t.clone();
+            shift.apply(&mut t.span);
+            t
+        }));
+
+        // diagnostics are at the tokens they are about: those of the tokens lexed again are replaced
+        let kept_from = self.tokens.get(old_end).map_or(usize::MAX, |t| t.span.start);
+        let keep = |diagnostics: &[Diagnostic], replaced: Range<usize>| -> Vec<Diagnostic> {
+            let kept = diagnostics.iter().filter(|d| !replaced.contains(&d.span.start));
+            kept.map(|d| Diagnostic { span: shift.moved(d.span), ..d.clone() }).collect()
+        };
+        let mut lexer_diagnostics = keep(&self.lexer_diagnostics, offset..kept_from);
+        lexer_diagnostics.extend(lexer.diagnostics().iter().cloned());
+        lexer_diagnostics.sort_by_key(|d| d.span.start);
+
+        // the smallest unit the edited tokens lie in, parsed again on its own
+        let unit = self.units.iter().find(|u| u.tokens.start <= first && old_end <= u.tokens.end);
+        let reparsed = unit.and_then(|unit| {
+            let range = unit.tokens.start..unit.tokens.end - old_end + new_end;
+            let mut parser = Parser::with_spans(tokens[range].to_vec(), false).with_word_size(self.config.word_size);
+            let part = match unit.part {
+                Reparsed::Statement { function, index } => {
+                    Part::Statement { function, index, statement: parser.parse_statement().ok()? }
+                }
+                Reparsed::Function(index) => Part::Function { index, func: parser.parse_function().ok()? },
+                Reparsed::Program => return None,
+            };
+            let old = &self.tokens[unit.tokens.clone()];
+            let replaced = old.first().zip(old.last()).map_or(0..0, |(first, last)| first.span.start..last.span.end);
+            Some((unit.part, part, replaced, parser.diagnostics()))
+        });
+
+        // nothing changes until the edit is known to parse
+        match reparsed {
+            Some((reparsed, part, replaced, diagnostics)) => {
+                let mut parser_diagnostics = keep(&self.parser_diagnostics, replaced);
+                parser_diagnostics.extend(diagnostics);
+                parser_diagnostics.sort_by_key(|d| d.span.start);
+                shift_program(&mut self.program, &shift);
+                match part {
+                    Part::Statement { function, index, statement } => {
+                        let body = match function {
+                            Some(function) => &mut self.program.sub_progs.sub_progs[function].body,
+                            None => &mut self.program.body,
+                        };
+                        body.statements[index] = statement;
+                    }
+                    Part::Function { index, func } => self.program.sub_progs.sub_progs[index] = func,
+                }
+                self.parser_diagnostics = parser_diagnostics;
+                self.reparsed = reparsed;
+            }
+            None => {
+                let mut parser = Parser::with_spans(tokens.clone(), false).with_word_size(self.config.word_size);
+                self.program = parser.parse()?;
+                self.parser_diagnostics = parser.diagnostics();
+                self.reparsed = Reparsed::Program;
+            }
+        }
+        self.units = units(&tokens, &self.program);
+        self.text = text;
+        self.tokens = tokens;
+        self.lexer_diagnostics = lexer_diagnostics;
+        self.relexed = relexed_count;
+        Ok(())
+    }
+}
+
+// A unit parsed again, with where it goes in the program.
+enum Part {
+    Statement { function: Option<usize>, index: usize, statement: Statement },
+    Function { index: usize, func: Func },
+}
+
+/// How positions after an edit move.
+struct Shift {
+    // the end of the replaced text, before the edit
+    old_end: usize,
+    old_end_line: usize,
+    old_end_column: usize,
+    new_end: usize,
+    new_end_line: usize,
+    new_end_column: usize,
+}
+
+impl Shift {
+    fn new(old_text: &str, new_text: &str, range: &Range<usize>, inserted: &str) -> Self {
+        let (old_end_line, old_end_column) = line_and_column(old_text, range.end);
+        let new_end = range.start + inserted.len();
+        let (new_end_line, new_end_column) = line_and_column(new_text, new_end);
+        Shift { old_end: range.end, old_end_line, old_end_column, new_end, new_end_line, new_end_column }
+    }
+
+    fn moved(&self, mut span: Span) -> Span {
+        self.apply(&mut span);
+        span
+    }
+
+    fn apply(&self, span: &mut Span) {
+        if span.start < self.old_end {
+            return;
+        }
+        if span.line == self.old_end_line {
+            span.column = span.column - self.old_end_column + self.new_end_column;
+        }
+        span.line = span.line - self.old_end_line + self.new_end_line;
+        span.start = span.start - self.old_end + self.new_end;
+        span.end = span.end - self.old_end + self.new_end;
+    }
+}
+
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..offset].matches('\n').count() + 1;
+    (line, text[line_start..offset].chars().count() + 1)
+}
+
+// Finds the units by following the block structure of the tokens of a program that parsed.
+fn units(tokens: &[SpannedToken], program: &Program) -> Vec<Unit> {
+    let mut units = Vec::new();
+    // index after the last significant token
+    let mut after_previous = 0;
+    let mut blocks = 0;
+    let mut parens = 0;
+    let mut function: Option<(usize, usize)> = None;
+    let mut function_count = 0;
+    let mut awaiting_function_end = false;
+    // owner of the body being scanned, the start of the current statement and its index
+    let mut statement: Option<(Option<usize>, usize, usize)> = None;
+
+    // error tokens, which the parser skips, still belong to the unit they are in
+    for (i, t) in tokens.iter().enumerate() {
+        if t.token.is_trivia() {
+            continue;
+        }
+        match &t.token {
+            Token::Keyword(Keyword::Function) if blocks == 0 => {
+                function = Some((function_count, after_previous));
+                function_count += 1;
+            }
+            Token::Keyword(Keyword::Begin) if blocks == 0 => {
+                blocks = 1;
+                statement = Some((function.map(|(index, _)| index), i + 1, 0));
+            }
+            Token::Keyword(Keyword::Begin | Keyword::Case | Keyword::Repeat | Keyword::Loop) => blocks += 1,
+            Token::Keyword(Keyword::End) if blocks == 1 => {
+                blocks = 0;
+                if let Some((owner, start, index)) = statement.take() {
+                    let part = Reparsed::Statement { function: owner, index };
+                    units.push(Unit { tokens: start..after_previous.max(start), part });
+                }
+                awaiting_function_end = function.is_some();
+            }
+            Token::Keyword(Keyword::End | Keyword::Until | Keyword::Pool) => blocks -= 1,
+            Token::LeftParen => parens += 1,
+            Token::RightParen => parens -= 1,
+            Token::Semicolon if awaiting_function_end => {
+                if let Some((index, start)) = function.take() {
+                    units.push(Unit { tokens: start..i + 1, part: Reparsed::Function(index) });
+                }
+                awaiting_function_end = false;
+            }
+            Token::Semicolon if blocks == 1 && parens == 0 => {
+                if let Some((owner, start, index)) = statement {
+                    let part = Reparsed::Statement { function: owner, index };
+                    units.push(Unit { tokens: start..after_previous.max(start), part });
+                    statement = Some((owner, i + 1, index + 1));
+                }
+            }
+            _ => {}
+        }
+        after_previous = i + 1;
+    }
+
+    // statement units come before the function that contains them, so the smallest unit is found first
+    units.sort_by_key(|u| matches!(u.part, Reparsed::Function(_)));
+
+    // the scan must agree with the parser, or nothing is reused
+    let statements = |function: Option<usize>| units.iter().filter(move |u| match u.part {
+        Reparsed::Statement { function: f, .. } => f == function,
+        _ => false,
+    });
+    let agrees = units.iter().filter(|u| matches!(u.part, Reparsed::Function(_))).count() == program.functions().len()
+        && statements(None).count() == program.body.statements.len()
+        && program.functions().iter().enumerate().all(|(i, f)| statements(Some(i)).count() == f.body.statements.len());
+    if agrees {
+        units
+    } else {
+        Vec::new()
+    }
+}
+
+// Moves the spans of everything after an edit.
+fn shift_program(program: &mut Program, shift: &Shift) {
+    let mut shifter = Shifter(shift);
+    shift.apply(&mut program.name.span);
+    shifter.declarations(&mut program.consts, &mut program.types, &mut program.dclns);
+    for func in &mut program.sub_progs.sub_progs {
+        shift.apply(&mut func.name.span);
+        func.params.params.iter_mut().for_each(|var| shifter.variable(var));
+        shift.apply(&mut func.return_type.span);
+        shifter.declarations(&mut func.consts, &mut func.types, &mut func.dclns);
+        walk_statements_mut(&mut shifter, &mut func.body.statements);
+        shift.apply(&mut func.end_name.span);
+    }
+    walk_statements_mut(&mut shifter, &mut program.body.statements);
+    shift.apply(&mut program.end_name.span);
+}
+
+struct Shifter<'a>(&'a Shift);
+
+impl Shifter<'_> {
+    fn declarations(&mut self, consts: &mut Consts, types: &mut Types, dclns: &mut Dclns) {
+        for c in &mut consts.consts {
+            self.0.apply(&mut c.name.span);
+            if let ConstValue::Name(name) = &mut c.value {
+                self.0.apply(&mut name.span);
+            }
+        }
+        for t in &mut types.types {
+            self.0.apply(&mut t.name.span);
+            t.lit_list.names.iter_mut().for_each(|n| self.0.apply(&mut n.span));
+        }
+        dclns.vars.iter_mut().for_each(|var| self.variable(var));
+    }
+
+    fn variable(&mut self, var: &mut Var) {
+        var.names.iter_mut().for_each(|n| self.0.apply(&mut n.span));
+        self.0.apply(&mut var.typename.span);
+    }
+}
+
+impl VisitorMut for Shifter<'_> {
+    // the keywords and labels statements keep the spans of, besides their names
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Return { span, .. } | Statement::Exit { span } => self.0.apply(span),
+            Statement::Case { cases, .. } => {
+                cases.iter_mut().flat_map(|clause| &mut clause.labels).for_each(|label| self.0.apply(&mut label.span))
+            }
+            _ => {}
+        }
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_name_mut(&mut self, name: &mut Identifier, _usage: NameUsage) {
+        self.0.apply(&mut name.span)
+    }
+}
@@ -1,79 +1,116 @@
 #![allow(dead_code)]
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 
-use crate::lexer::{Token};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{Token, WordSize};
 
 
 mod test;
+#[allow(clippy::module_inception)]
 mod parser;
+pub mod incremental;
+pub mod print;
 pub mod tree;
+pub mod visit;
+
+/// Result of a parser entry point; the error points at the offending token.
+pub type ParseResult<T> = Result<T, Diagnostic>;
 
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
+    docs: HashMap<usize, String>,
+    // names spelled otherwise than their tokens, which a case-insensitive lexer folds
+    spellings: HashMap<usize, String>,
     current: Cell<usize>,
+    /// Tokens looked for at the end of the input.
+    expected: RefCell<Vec<Token>>,
+    word_size: WordSize,
+    /// Errors that don't stop the parse.
+    diagnostics: RefCell<Vec<Diagnostic>>,
     verbose: bool,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Program {
-    name: Identifier,
-    consts: Consts,
-    types: Types,
-    dclns: Dclns,
-    sub_progs: SubProgs,
-    body: Body,
-    end_name: Identifier,
+    pub doc: Option<String>,
+    pub name: Identifier,
+    pub consts: Consts,
+    pub types: Types,
+    pub dclns: Dclns,
+    pub sub_progs: SubProgs,
+    pub body: Body,
+    pub end_name: Identifier,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Identifier {
-    name: String,
+    /// The name as written.
+    pub name: String,
+    /// The name as compared with others: `name`, folded to lowercase in a case-insensitive
+    /// source.
+    pub key: String,
+    pub span: Span,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Consts {
-    consts: Vec<Const>,
+    pub consts: Vec<Const>,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Const {
-    name: Identifier,
-    value: ConstValue,
+    pub name: Identifier,
+    pub value: ConstValue,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Dclns {
-    vars: Vec<Var>,
+    pub vars: Vec<Var>,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Types {
-    types: Vec<Type>,
+    pub types: Vec<Type>,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ConstValue {
     Integer(i64),
     Char(char),
     Name(Identifier),
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Type {
-    name: Identifier,
-    lit_list: LitList,
+    pub name: Identifier,
+    pub lit_list: LitList,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct LitList {
-    names: Vec<Identifier>,
+    pub names: Vec<Identifier>,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Fcn {
     Fcn { name: String, params: Vec<Var>, consts: Vec<Const>, types: Vec<Type>, dclns: Vec<Var>, body: Body },
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Var {
-    names: Vec<Identifier>,
-    typename: Identifier,
+    pub names: Vec<Identifier>,
+    pub typename: Identifier,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Body {
-    statements: Vec<Statement>,
+    pub statements: Vec<Statement>,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Statement {
     Assign { assignment: Assignment },
     Output { expressions: Vec<OutExp> },
@@ -93,47 +130,64 @@ pub enum Statement {
         otherwise: Option<Box<OtherwiseClause>>,
     },
     Read { names: Vec<Identifier> },
-    Exit,
-    Return { exp: Expression },
+    /// `span` is the keyword's.
+    Exit { span: Span },
+    /// `span` is the keyword's.
+    Return { exp: Expression, span: Span },
     Body { body: Body },
     Null,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ForStat {
     Assignment(Assignment),
     Null,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum ForExp {
     Expression(Expression),
     True,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Assignment {
     Assignment { name: Identifier, exp: Expression },
     Swap { name1: Identifier, name2: Identifier },
 }
 
+#[derive(Debug, PartialEq)]
 pub enum OutExp {
     Integer { exp: Expression },
     String { value: String },
 }
 
+#[derive(Debug, PartialEq)]
 pub struct CaseClause {
-    expressions: Vec<CaseExpression>,
-    statement: Statement,
+    pub labels: Vec<CaseLabel>,
+    pub statement: Statement,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CaseLabel {
+    pub expression: CaseExpression,
+    /// From the first value to the last, e.g. all of `1..5`.
+    pub span: Span,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum CaseExpression {
     Value(ConstValue),
     Range(ConstValue, ConstValue),
 }
 
+#[derive(Debug, PartialEq)]
 pub struct OtherwiseClause {
-    stmt: Statement,
+    pub stmt: Statement,
 }
 
 
+#[derive(Debug, PartialEq)]
 pub enum Expression {
     Le { left: Box<Term>, right: Box<Term> },
     Lt { left: Box<Term>, right: Box<Term> },
@@ -144,6 +198,7 @@ pub enum Expression {
     Term(Box<Term>),
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Term {
     Add { left: Box<Term>, right: Box<Factor> },
     Subtract { left: Box<Term>, right: Box<Factor> },
@@ -151,6 +206,7 @@ pub enum Term {
     Factor(Factor),
 }
 
+#[derive(Debug, PartialEq)]
 pub enum Factor {
     Multiply { left: Box<Factor>, right: Box<Primary> },
     Divide { left: Box<Factor>, right: Box<Primary> },
@@ -160,6 +216,7 @@ pub enum Factor {
 }
 
 
+#[derive(Debug, PartialEq)]
 pub enum Primary {
     Negate { primary: Box<Primary> },
     Not { primary: Box<Primary> },
@@ -175,21 +232,55 @@ pub enum Primary {
     Ord { exp: Box<Expression> },
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Params {
-    params: Vec<Var>,
+    pub params: Vec<Var>,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct Func {
-    name: Identifier,
-    params: Params,
-    return_type: Identifier,
-    consts: Consts,
-    types: Types,
-    dclns: Dclns,
-    body: Body,
-    end_name: Identifier,
+    pub doc: Option<String>,
+    pub name: Identifier,
+    pub params: Params,
+    pub return_type: Identifier,
+    pub consts: Consts,
+    pub types: Types,
+    pub dclns: Dclns,
+    pub body: Body,
+    pub end_name: Identifier,
 }
 
+#[derive(Debug, PartialEq)]
 pub struct SubProgs {
-    sub_progs: Vec<Func>,
+    pub sub_progs: Vec<Func>,
+}
+
+/// The `const`, `type` and `var` sections parsed by `Parser::parse_declarations`.
+#[derive(Debug, PartialEq)]
+pub struct Declarations {
+    pub consts: Consts,
+    pub types: Types,
+    pub dclns: Dclns,
+}
+
+impl Program {
+    /// Doc comment written immediately before `program`.
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    pub fn functions(&self) -> &[Func] {
+        &self.sub_progs.sub_progs
+    }
+}
+
+impl Func {
+    /// Doc comment written immediately before `function`.
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name.name
+    }
 }
@@ -1,36 +1,229 @@
 #![allow(dead_code)]
 
-use std::cell::Cell;
-use crate::lexer::{Operator, Token};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::lexer::{CommentKind, Operator, SpannedToken, Token, WordSize};
 use crate::lexer::Keyword;
 use crate::parser::*;
 
+// Newlines never reach the parser, so one marks the end of the input.
+static END_OF_INPUT: Token = Token::Newline;
+
+// Stand-ins for names and literals in the sets of tokens the parser looks for.
+const NAME: Token = Token::Identifier(String::new());
+const INTEGER: Token = Token::Integer(0);
+const CHAR: Token = Token::Char('\0');
+const STRING: Token = Token::String(String::new());
+
+const RELATIONS: [Token; 6] = [
+    Token::Operator(Operator::LessEqual),
+    Token::Operator(Operator::LessThan),
+    Token::Operator(Operator::GreaterEqual),
+    Token::Operator(Operator::GreaterThan),
+    Token::Operator(Operator::Equal),
+    Token::Operator(Operator::NotEqual),
+];
+
+const ADDING: [Token; 3] = [Token::Operator(Operator::Plus), Token::Operator(Operator::Minus), Token::Keyword(Keyword::Or)];
+
+const MULTIPLYING: [Token; 4] = [
+    Token::Operator(Operator::Multiply),
+    Token::Operator(Operator::Divide),
+    Token::Keyword(Keyword::And),
+    Token::Keyword(Keyword::Mod),
+];
+
+const PRIMARY_STARTS: [Token; 12] = [
+    Token::Operator(Operator::Minus),
+    Token::Operator(Operator::Plus),
+    Token::Keyword(Keyword::Not),
+    Token::Keyword(Keyword::Eof),
+    NAME,
+    INTEGER,
+    CHAR,
+    Token::LeftParen,
+    Token::Keyword(Keyword::Succ),
+    Token::Keyword(Keyword::Pred),
+    Token::Keyword(Keyword::Chr),
+    Token::Keyword(Keyword::Ord),
+];
+
+const STATEMENT_STARTS: [Token; 12] = [
+    Token::Keyword(Keyword::Output),
+    Token::Keyword(Keyword::If),
+    Token::Keyword(Keyword::While),
+    Token::Keyword(Keyword::Repeat),
+    Token::Keyword(Keyword::For),
+    Token::Keyword(Keyword::Loop),
+    Token::Keyword(Keyword::Case),
+    Token::Keyword(Keyword::Read),
+    Token::Keyword(Keyword::Exit),
+    Token::Keyword(Keyword::Return),
+    Token::Keyword(Keyword::Begin),
+    NAME,
+];
+
 impl Parser {
     pub fn new(tokens: Vec<Token>, verbose: bool) -> Parser {
+        let tokens = tokens.into_iter().map(|t| (t, Span::default(), None)).collect();
+        Self::build(tokens, verbose)
+    }
+
+    /// Like `new`, but keeps token positions so errors point into the source, and names
+    /// as they are spelled.
+    pub fn with_spans(tokens: Vec<SpannedToken>, verbose: bool) -> Parser {
+        let tokens = tokens
+            .into_iter()
+            .map(|t| {
+                let spelling = matches!(&t.token, Token::Identifier(key) if *key != t.lexeme).then_some(t.lexeme);
+                (t.token, t.span, spelling)
+            })
+            .collect();
+        Self::build(tokens, verbose)
+    }
+
+    fn build(tokens: Vec<(Token, Span, Option<String>)>, verbose: bool) -> Parser {
 
-        // Filter unnecessary tokens
-        let tokens = tokens.into_iter().filter(|t| {
+        // Filter unnecessary tokens and lexical errors (already reported by the lexer),
+        // remembering doc comments that precede a 'program' or 'function' keyword
+        let mut docs = HashMap::new();
+        let mut spellings = HashMap::new();
+        let mut pending_doc: Vec<String> = Vec::new();
+        let mut kept = Vec::new();
+        let mut spans = Vec::new();
+        for (t, span, spelling) in tokens {
             match t {
-                Token::Comment => false,
-                Token::Whitespace => false,
-                Token::Newline => false,
-                _ => true,
+                Token::Comment(CommentKind::DocLine, text) => pending_doc.push(text),
+                Token::Comment(CommentKind::Block, text) => pending_doc.push(text),
+                Token::Comment(CommentKind::Line, _) => pending_doc.clear(),
+                Token::Whitespace | Token::Newline | Token::Error(_) => {}
+                t => {
+                    if !pending_doc.is_empty()
+                        && (t == Token::Keyword(Keyword::Program) || t == Token::Keyword(Keyword::Function))
+                    {
+                        docs.insert(kept.len(), doc_text(&pending_doc));
+                    }
+                    pending_doc.clear();
+                    if let Some(spelling) = spelling {
+                        spellings.insert(kept.len(), spelling);
+                    }
+                    kept.push(t);
+                    spans.push(span);
+                }
             }
-        }).collect::<Vec<Token>>();
+        }
 
         Parser {
-            tokens,
+            tokens: kept,
+            spans,
+            docs,
+            spellings,
             current: Cell::new(0),
+            expected: RefCell::new(Vec::new()),
+            word_size: WordSize::default(),
+            diagnostics: RefCell::new(Vec::new()),
             verbose,
         }
     }
 
-    pub fn parse(&mut self) -> Program {
-        self.winzig()
+    /// Reports integer literals beyond a `word_size` word, rather than a 64-bit one.
+    pub fn with_word_size(mut self, word_size: WordSize) -> Parser {
+        self.word_size = word_size;
+        self
+    }
+
+    pub fn parse(&mut self) -> ParseResult<Program> {
+        let program = self.winzig()?;
+        self.end_of_input()?;
+        Ok(program)
+    }
+
+    /// Parses a single expression, such as `a + f(b) * 2`.
+    pub fn parse_expression(&mut self) -> ParseResult<Expression> {
+        let expression = self.expression()?;
+        self.end_of_input()?;
+        Ok(expression)
+    }
+
+    /// Parses a single statement, such as `if a > b then output(a)`.
+    pub fn parse_statement(&mut self) -> ParseResult<Statement> {
+        let statement = self.statement()?;
+        self.end_of_input()?;
+        Ok(statement)
+    }
+
+    /// Parses a `;`-separated list of statements, as found between `begin` and `end`.
+    pub fn parse_statements(&mut self) -> ParseResult<Vec<Statement>> {
+        let statements = self._statement_list()?;
+        self.end_of_input()?;
+        Ok(statements)
+    }
+
+    /// Parses `const`, `type` and `var` sections, in that order, each of which may be absent.
+    pub fn parse_declarations(&mut self) -> ParseResult<Declarations> {
+        let consts = self.consts()?;
+        let types = self.types()?;
+        let dclns = self.dclns()?;
+        self.end_of_input()?;
+        Ok(Declarations {
+            consts,
+            types,
+            dclns,
+        })
+    }
+
+    /// Parses a single `function ... end Name;` definition.
+    pub fn parse_function(&mut self) -> ParseResult<Func> {
+        let func = match self.func()? {
+            Some(func) => func,
+            None => return Err(self.error("Expected 'function'")),
+        };
+        self.end_of_input()?;
+        Ok(func)
+    }
+
+    /// The tokens that could have come next when the parser reached the end of the input, so
+    /// those that could continue a truncated source. Names and literals are given with empty
+    /// values, e.g. `Token::Identifier("")`.
+    pub fn expected(&self) -> Vec<Token> {
+        self.expected.borrow().clone()
+    }
+
+    /// Errors found along the way, such as integer literals out of range, that don't stop
+    /// the parse.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.borrow().clone()
+    }
+
+    fn end_of_input(&self) -> ParseResult<()> {
+        if self.is_at_end() {
+            Ok(())
+        } else {
+            Err(self.error("Expected end of input"))
+        }
     }
 
     fn peek(&self) -> &Token {
-        &self.tokens[self.current.get()]
+        self.tokens.get(self.current.get()).unwrap_or(&END_OF_INPUT)
+    }
+
+    /// The current token, which is checked against `tokens`; at the end of the input, these
+    /// are remembered as expected.
+    fn peek_for(&self, tokens: &[Token]) -> &Token {
+        if self.is_at_end() {
+            let mut expected = self.expected.borrow_mut();
+            for token in tokens {
+                if !expected.contains(token) {
+                    expected.push(token.clone());
+                }
+            }
+        }
+        self.peek()
+    }
+
+    fn check(&self, token: Token) -> bool {
+        *self.peek_for(std::slice::from_ref(&token)) == token
     }
 
     fn previous(&self) -> &Token {
@@ -51,32 +244,80 @@ impl Parser {
         self.previous()
     }
 
+    /// Span of the current token, or an empty span just past the last one at the end of input.
+    fn current_span(&self) -> Span {
+        match self.spans.get(self.current.get()) {
+            Some(span) => *span,
+            None => match self.spans.last() {
+                Some(last) => Span::new(last.end, last.end, last.line, last.column + (last.end - last.start)),
+                None => Span::default(),
+            },
+        }
+    }
+
+    /// Span of the token just consumed.
+    fn previous_span(&self) -> Span {
+        self.spans.get(self.current.get().wrapping_sub(1)).copied().unwrap_or_default()
+    }
+
+    fn error(&self, message: &str) -> Diagnostic {
+        let found = match self.peek() {
+            _ if self.is_at_end() => "end of input".to_string(),
+            Token::Identifier(name) => format!("'{}'", name),
+            Token::Integer(i) => format!("'{}'", i),
+            Token::Char(c) => format!("'{}'", c),
+            Token::String(s) => format!("\"{}\"", s),
+            t => format!("'{}'", t.category()),
+        };
+        Diagnostic::error(format!("{}, found {}", message, found), self.current_span())
+    }
+
+    // Reports the current token, the integer literal `value`, if it is beyond the word and
+    // isn't negated.
+    fn check_range(&self, value: i64) {
+        if value > self.word_size.max_value() {
+            let message = format!("integer literal out of range for {}-bit word: {}", self.word_size.bits(), value);
+            self.diagnostics.borrow_mut().push(Diagnostic::error(message, self.current_span()));
+        }
+    }
+
+    fn expect_name(&self, message: &str) -> ParseResult<Identifier> {
+        match self.name() {
+            Some(name) => Ok(name),
+            None => Err(self.error(message)),
+        }
+    }
+
     // Winzig -> 'program' Name ':' Consts Types Dclns SubProgs Body Name '.' => "program";
-    fn winzig(&mut self) -> Program {
-        let t = self.peek();
-        if t == Token::Keyword(Keyword::Program) {
+    fn winzig(&mut self) -> ParseResult<Program> {
+        if self.check(Token::Keyword(Keyword::Program)) {
+            let doc = self.docs.get(&self.current.get()).cloned();
             self.advance();
-            let name = self.name().expect("Expected program name");
-            if self.peek() == &Token::Colon {
+            let name = self.expect_name("Expected program name")?;
+            if self.check(Token::Colon) {
                 self.advance();
             } else {
-                panic!("Expected ':'");
+                return Err(self.error("Expected ':'"));
             }
 
-            let consts = self.consts();
-            let types = self.types();
-            let dclns = self.dclns();
-            let sub_progs = self.sub_progs();
-            let body = self.body().expect("Expected program body");
+            let consts = self.consts()?;
+            let types = self.types()?;
+            let dclns = self.dclns()?;
+            let sub_progs = self.sub_progs()?;
+            let body = match self.body()? {
+                Some(body) => body,
+                None => return Err(self.error("Expected program body")),
+            };
 
-            let end_name = self.name().expect("Expected program end name");
-            if self.peek() == Token::Dot {
+            let end_name = self.expect_name("Expected program end name")?;
+            if self.check(Token::Dot) {
                 self.advance();
             } else {
-                panic!("Expected '.'");
+                return Err(self.error("Expected '.'"));
             }
 
-            Program {
+            Ok(Program {
+                doc,
                 name,
                 consts,
                 types,
@@ -84,18 +325,20 @@ impl Parser {
                 sub_progs,
                 body,
                 end_name,
-            }
+            })
         } else {
-            panic!("Expected program");
+            Err(self.error("Expected program"))
         }
     }
 
     // Name -> '<identifier>';
     fn name(&self) -> Option<Identifier> {
-        let t = self.peek();
-        if let Token::Identifier(name) = t {
+        let t = self.peek_for(&[NAME]);
+        if let Token::Identifier(key) = t {
+            let span = self.current_span();
+            let name = self.spellings.get(&self.current.get()).unwrap_or(key).clone();
             self.advance();
-            Some(Identifier { name: name.clone() })
+            Some(Identifier { name, key: key.clone(), span })
         } else {
             None
         }
@@ -103,205 +346,186 @@ impl Parser {
 
     // Consts -> 'const' Const list ',' ';' => "consts";
     // Consts -> => "consts";
-    fn consts(&mut self) -> Consts {
-        let t = self.peek();
-        if t == Token::Keyword(Keyword::Const) {
+    fn consts(&mut self) -> ParseResult<Consts> {
+        if self.check(Token::Keyword(Keyword::Const)) {
             self.advance();
             let mut consts = Vec::new();
             loop {
-                let c = self.const_expr();
-                let t = self.peek();
-                if t == Token::Comma {
+                let c = self.const_expr()?;
+                if self.check(Token::Comma) {
                     self.advance();
                     consts.push(c);
-                } else if t == Token::Comma {
+                } else if self.check(Token::Semicolon) {
                     self.advance();
                     consts.push(c);
                     break;
                 } else {
-                    panic!("Expected ',' or ';'");
+                    return Err(self.error("Expected ',' or ';'"));
                 }
             }
-            Consts {
+            Ok(Consts {
                 consts
-            }
+            })
         } else {
-            Consts {
+            Ok(Consts {
                 consts: Vec::new()
-            }
+            })
         }
     }
 
     // Const -> Name '=' ConstValue => "const";
-    fn const_expr(&mut self) -> Const {
-        let name = self.name().expect("Expected const name");
-        let t = self.peek();
-        if t == Token::Operator(Operator::Equal) {
+    fn const_expr(&mut self) -> ParseResult<Const> {
+        let name = self.expect_name("Expected const name")?;
+        if self.check(Token::Operator(Operator::Equal)) {
             self.advance();
-            let value = self.const_value();
-            Const {
+            let value = self.const_value()?;
+            Ok(Const {
                 name,
                 value,
-            }
+            })
         } else {
-            panic!("Expected '='");
+            Err(self.error("Expected '='"))
         }
     }
 
     // ConstValue -> '<integer>';
     // ConstValue -> '<char>';
     // ConstValue -> Name;
-    fn const_value(&mut self) -> ConstValue {
-        let t = self.peek();
+    fn const_value(&mut self) -> ParseResult<ConstValue> {
+        let t = self.peek_for(&[INTEGER, CHAR, NAME]);
         if let Token::Integer(i) = t {
+            self.check_range(*i);
             self.advance();
-            ConstValue::Integer(i.clone())
+            Ok(ConstValue::Integer(*i))
         } else if let Token::Char(c) = t {
             self.advance();
-            ConstValue::Char(c.clone())
+            Ok(ConstValue::Char(*c))
         } else if let Token::Identifier(_) = t {
-            let name = self.name().expect("Expected const name");
-            ConstValue::Name(name)
+            let name = self.expect_name("Expected const name")?;
+            Ok(ConstValue::Name(name))
         } else {
-            panic!("Expected const value");
+            Err(self.error("Expected const value"))
         }
     }
 
     // Types -> 'type' (Type ';')+ => "types";
     // Types -> => "types";
-    fn types(&mut self) -> Types {
-        let t = self.peek();
-        if t == Token::Keyword(Keyword::Type) {
+    fn types(&mut self) -> ParseResult<Types> {
+        if self.check(Token::Keyword(Keyword::Type)) {
             self.advance();
             let mut types = Vec::new();
-            loop {
-                match self.type_expr() {
-                    Some(t) => {
-                        types.push(t);
-                        let t = self.peek();
-                        if t == Token::Semicolon {
-                            self.advance();
-                        } else {
-                            panic!("Expected ';'");
-                        }
-                    }
-                    None => break,
-                };
+            while let Some(t) = self.type_expr()? {
+                types.push(t);
+                if self.check(Token::Semicolon) {
+                    self.advance();
+                } else {
+                    return Err(self.error("Expected ';'"));
+                }
             }
-            Types {
+            Ok(Types {
                 types
-            }
+            })
         } else {
-            Types {
+            Ok(Types {
                 types: Vec::new()
-            }
+            })
         }
     }
 
     // Type -> Name '=' LitList => "type";
-    fn type_expr(&mut self) -> Option<Type> {
+    fn type_expr(&mut self) -> ParseResult<Option<Type>> {
         let name = match self.name() {
             Some(name) => name,
-            None => return None,
+            None => return Ok(None),
         };
-        let t = self.peek();
-        if t == Token::Operator(Operator::Equal) {
+        if self.check(Token::Operator(Operator::Equal)) {
             self.advance();
-            let lit_list = self.lit_list();
-            Some(Type {
+            let lit_list = self.lit_list()?;
+            Ok(Some(Type {
                 name,
                 lit_list,
-            })
+            }))
         } else {
-            panic!("Expected '='");
+            Err(self.error("Expected '='"))
         }
     }
 
     // LitList -> '(' Name list ',' ')' => "lit";
-    fn lit_list(&mut self) -> LitList {
-        let t = self.peek();
-        if t == Token::LeftParen {
+    fn lit_list(&mut self) -> ParseResult<LitList> {
+        if self.check(Token::LeftParen) {
             self.advance();
             let mut names = Vec::new();
             loop {
-                let name = self.name().expect("Expected name");
+                let name = self.expect_name("Expected name")?;
                 names.push(name);
-                let t = self.peek();
-                if t == Token::Comma {
+                if self.check(Token::Comma) {
                     self.advance();
-                } else if t == Token::RightParen {
+                } else if self.check(Token::RightParen) {
                     self.advance();
                     break;
                 } else {
-                    panic!("Expected ',' or ')'");
+                    return Err(self.error("Expected ',' or ')'"));
                 }
             }
-            LitList { names }
+            Ok(LitList { names })
         } else {
-            panic!("Expected '('");
+            Err(self.error("Expected '('"))
         }
     }
 
     // Dclns -> 'var' (Dcln ';')+ => "dclns";
     // Dclns -> => "dclns";
-    fn dclns(&mut self) -> Dclns {
-        let t = self.peek();
-        if t == Token::Keyword(Keyword::Var) {
+    fn dclns(&mut self) -> ParseResult<Dclns> {
+        if self.check(Token::Keyword(Keyword::Var)) {
             self.advance();
             let mut dclns = Vec::new();
-            loop {
-                let d = match self.dcln() {
-                    Some(d) => d,
-                    None => break,
-                };
+            while let Some(d) = self.dcln()? {
                 dclns.push(d);
-                let t = self.peek();
-                if t == Token::Semicolon {
+                if self.check(Token::Semicolon) {
                     self.advance();
                 } else {
-                    panic!("Expected ';'");
+                    return Err(self.error("Expected ';'"));
                 }
             }
-            Dclns {
+            Ok(Dclns {
                 vars: dclns,
-            }
+            })
         } else {
-            Dclns {
+            Ok(Dclns {
                 vars: Vec::new()
-            }
+            })
         }
     }
 
     // Dcln -> Name list ',' ':' Name => "var";
-    fn dcln(&mut self) -> Option<Var> {
+    fn dcln(&mut self) -> ParseResult<Option<Var>> {
         // check if it's a identifier
-        match self.peek() {
+        match self.peek_for(&[NAME]) {
             Token::Identifier(_) => {},
-            _ => return None,
+            _ => return Ok(None),
         }
 
         // parse name list
         let mut names = vec![];
         loop {
-            let name = self.name().expect("Expected name");
+            let name = self.expect_name("Expected name")?;
             names.push(name);
-            let t = self.peek();
-            if t == Token::Comma {
+            if self.check(Token::Comma) {
                 self.advance();
-            } else if t == Token::Colon {
+            } else if self.check(Token::Colon) {
                 self.advance();
                 break;
             } else {
-                panic!("Expected ',' or ':'");
+                return Err(self.error("Expected ',' or ':'"));
             }
         }
 
         // parse type name
-        let name = self.name().expect("Expected name");
-        Some(Var {
+        let name = self.expect_name("Expected type name")?;
+        Ok(Some(Var {
             names,
             typename: name,
-        })
+        }))
     }
 
     // Expression -> Term;
@@ -311,12 +535,12 @@ impl Parser {
     // Expression -> Term '>' Term => ">";
     // Expression -> Term '=' Term => "=";
     // Expression -> Term '<>' Term => "<>";
-    fn expression(&self) -> Expression {
-        let left = self.term();
-        match self.peek() {
+    fn expression(&self) -> ParseResult<Expression> {
+        let left = self.term()?;
+        let expression = match self.peek_for(&RELATIONS) {
             Token::Operator(Operator::LessEqual) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Le {
                     left: Box::new(left),
                     right: Box::new(right),
@@ -324,7 +548,7 @@ impl Parser {
             }
             Token::Operator(Operator::LessThan) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Lt {
                     left: Box::new(left),
                     right: Box::new(right),
@@ -332,7 +556,7 @@ impl Parser {
             }
             Token::Operator(Operator::GreaterEqual) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Ge {
                     left: Box::new(left),
                     right: Box::new(right),
@@ -340,7 +564,7 @@ impl Parser {
             }
             Token::Operator(Operator::GreaterThan) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Gt {
                     left: Box::new(left),
                     right: Box::new(right),
@@ -348,7 +572,7 @@ impl Parser {
             }
             Token::Operator(Operator::Equal) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Eq {
                     left: Box::new(left),
                     right: Box::new(right),
@@ -356,20 +580,21 @@ impl Parser {
             }
             Token::Operator(Operator::NotEqual) => {
                 self.advance();
-                let right = self.term();
+                let right = self.term()?;
                 Expression::Ne {
                     left: Box::new(left),
                     right: Box::new(right),
                 }
             }
             _ => Expression::Term(Box::new(left)),
-        }
+        };
+        Ok(expression)
     }
 
 
     // Term -> Factor Term_
-    fn term(&self) -> Term {
-        let f = self.factor();
+    fn term(&self) -> ParseResult<Term> {
+        let f = self.factor()?;
         self.term_(Term::Factor(f))
     }
 
@@ -377,11 +602,11 @@ impl Parser {
     // Term_ -> '-' Factor Term_ => "-"
     // Term_ -> 'or' Factor Term_ => "or"
     // Term_ -> ε;
-    fn term_(&self, t: Term) -> Term {
-        match self.peek() {
+    fn term_(&self, t: Term) -> ParseResult<Term> {
+        match self.peek_for(&ADDING) {
             Token::Operator(Operator::Plus) => {
                 self.advance();
-                let f = self.factor();
+                let f = self.factor()?;
                 self.term_(Term::Add {
                     left: Box::new(t),
                     right: Box::new(f),
@@ -389,7 +614,7 @@ impl Parser {
             }
             Token::Operator(Operator::Minus) => {
                 self.advance();
-                let f = self.factor();
+                let f = self.factor()?;
                 self.term_(Term::Subtract {
                     left: Box::new(t),
                     right: Box::new(f),
@@ -397,19 +622,19 @@ impl Parser {
             }
             Token::Keyword(Keyword::Or) => {
                 self.advance();
-                let f = self.factor();
+                let f = self.factor()?;
                 self.term_(Term::Or {
                     left: Box::new(t),
                     right: Box::new(f),
                 })
             }
-            _ => t,
+            _ => Ok(t),
         }
     }
 
     // Factor -> Primary Factor_
-    fn factor(&self) -> Factor {
-        let p = self.primary();
+    fn factor(&self) -> ParseResult<Factor> {
+        let p = self.primary()?;
         self.factor_(Factor::Primary(p))
     }
 
@@ -418,11 +643,11 @@ impl Parser {
     // Factor_ -> 'and' Primary Factor_ => "and"
     // Factor_ -> 'mod' Primary Factor_ => "mod"
     // Factor_ -> ε;
-    fn factor_(&self, f: Factor) -> Factor {
-        match self.peek() {
+    fn factor_(&self, f: Factor) -> ParseResult<Factor> {
+        match self.peek_for(&MULTIPLYING) {
             Token::Operator(Operator::Multiply) => {
                 self.advance();
-                let p = self.primary();
+                let p = self.primary()?;
                 self.factor_(Factor::Multiply {
                     left: Box::new(f),
                     right: Box::new(p),
@@ -430,7 +655,7 @@ impl Parser {
             }
             Token::Operator(Operator::Divide) => {
                 self.advance();
-                let p = self.primary();
+                let p = self.primary()?;
                 self.factor_(Factor::Divide {
                     left: Box::new(f),
                     right: Box::new(p),
@@ -438,7 +663,7 @@ impl Parser {
             }
             Token::Keyword(Keyword::And) => {
                 self.advance();
-                let p = self.primary();
+                let p = self.primary()?;
                 self.factor_(Factor::And {
                     left: Box::new(f),
                     right: Box::new(p),
@@ -446,13 +671,13 @@ impl Parser {
             }
             Token::Keyword(Keyword::Mod) => {
                 self.advance();
-                let p = self.primary();
+                let p = self.primary()?;
                 self.factor_(Factor::Mod {
                     left: Box::new(f),
                     right: Box::new(p),
                 })
             }
-            _ => f,
+            _ => Ok(f),
         }
     }
 
@@ -469,23 +694,31 @@ impl Parser {
     // Primary -> 'pred' '(' Expression ')' => "pred";
     // Primary -> 'chr' '(' Expression ')' => "chr";
     // Primary -> 'ord' '(' Expression ')' => "ord";
-    fn primary(&self) -> Primary {
-        let t = self.peek();
-        match t {
+    fn primary(&self) -> ParseResult<Primary> {
+        let t = self.peek_for(&PRIMARY_STARTS);
+        let primary = match t {
             Token::Operator(Operator::Minus) => {
                 self.advance();
-                let p = self.primary();
+                let p = match self.peek() {
+                    // the lexer lets through the magnitude of the smallest value, which is
+                    // only in range here, as in -32768 for a 16-bit word
+                    Token::Integer(i) => {
+                        self.advance();
+                        Primary::Integer(*i)
+                    }
+                    _ => self.primary()?,
+                };
                 Primary::Negate {
                     primary: Box::new(p),
                 }
             }
             Token::Operator(Operator::Plus) => {
                 self.advance();
-                self.primary()
+                self.primary()?
             }
             Token::Keyword(Keyword::Not) => {
                 self.advance();
-                let p = self.primary();
+                let p = self.primary()?;
                 Primary::Not {
                     primary: Box::new(p),
                 }
@@ -495,20 +728,20 @@ impl Parser {
                 Primary::Eof
             }
             Token::Identifier(_) => {
-                let name = self.name().expect("Expected identifier");
-                if self.peek() == Token::LeftParen {
+                let name = self.expect_name("Expected identifier")?;
+                if self.check(Token::LeftParen) {
                     self.advance();
                     let mut exprs = Vec::new();
                     loop {
-                        let expr = self.expression();
+                        let expr = self.expression()?;
                         exprs.push(expr);
-                        if self.peek() == Token::Comma {
+                        if self.check(Token::Comma) {
                             self.advance();
-                        } else if self.peek() == Token::RightParen {
+                        } else if self.check(Token::RightParen) {
                             self.advance();
                             break;
                         } else {
-                            panic!("Expected ',' or ')'");
+                            return Err(self.error("Expected ',' or ')'"));
                         }
                     }
                     Primary::Call {
@@ -520,126 +753,94 @@ impl Parser {
                 }
             }
             Token::Integer(i) => {
+                self.check_range(*i);
                 self.advance();
-                Primary::Integer(i.clone())
+                Primary::Integer(*i)
             }
             Token::Char(c) => {
                 self.advance();
-                Primary::Char(c.clone())
+                Primary::Char(*c)
             }
             Token::LeftParen => {
                 self.advance();
-                let expr = self.expression();
-                if self.peek() == Token::RightParen {
+                let expr = self.expression()?;
+                if self.check(Token::RightParen) {
                     self.advance();
                     Primary::Expression(expr)
                 } else {
-                    panic!("Expected ')'");
+                    return Err(self.error("Expected ')'"));
                 }
             }
             Token::Keyword(Keyword::Succ) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
-                    self.advance();
-                    let expr = self.expression();
-                    if self.peek() == Token::RightParen {
-                        self.advance();
-                        Primary::Succ {
-                            exp: Box::new(expr),
-                        }
-                    } else {
-                        panic!("Expected ')'");
-                    }
-                } else {
-                    panic!("Expected '('");
+                Primary::Succ {
+                    exp: Box::new(self.parenthesized_expression()?),
                 }
             }
             Token::Keyword(Keyword::Pred) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
-                    self.advance();
-                    let expr = self.expression();
-                    if self.peek() == Token::RightParen {
-                        self.advance();
-                        Primary::Pred {
-                            exp: Box::new(expr),
-                        }
-                    } else {
-                        panic!("Expected ')'");
-                    }
-                } else {
-                    panic!("Expected '('");
+                Primary::Pred {
+                    exp: Box::new(self.parenthesized_expression()?),
                 }
             }
             Token::Keyword(Keyword::Chr) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
-                    self.advance();
-                    let expr = self.expression();
-                    if self.peek() == Token::RightParen {
-                        self.advance();
-                        Primary::Chr {
-                            exp: Box::new(expr),
-                        }
-                    } else {
-                        panic!("Expected ')'");
-                    }
-                } else {
-                    panic!("Expected '('");
+                Primary::Chr {
+                    exp: Box::new(self.parenthesized_expression()?),
                 }
             }
             Token::Keyword(Keyword::Ord) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
-                    self.advance();
-                    let expr = self.expression();
-                    if self.peek() == Token::RightParen {
-                        self.advance();
-                        Primary::Ord {
-                            exp: Box::new(expr),
-                        }
-                    } else {
-                        panic!("Expected ')'");
-                    }
-                } else {
-                    panic!("Expected '('");
+                Primary::Ord {
+                    exp: Box::new(self.parenthesized_expression()?),
                 }
             }
-            _ => panic!("Expected primary"),
+            _ => return Err(self.error("Expected primary")),
+        };
+        Ok(primary)
+    }
+
+    // '(' Expression ')', the argument of 'succ', 'pred', 'chr' and 'ord'
+    fn parenthesized_expression(&self) -> ParseResult<Expression> {
+        if !self.check(Token::LeftParen) {
+            return Err(self.error("Expected '('"));
         }
+        self.advance();
+        let expr = self.expression()?;
+        if !self.check(Token::RightParen) {
+            return Err(self.error("Expected ')'"));
+        }
+        self.advance();
+        Ok(expr)
     }
 
     // Params -> Dcln list ';' => "params";
-    fn params(&mut self) -> Params {
+    fn params(&mut self) -> ParseResult<Params> {
         let mut params = Vec::new();
-        loop {
-            let d = match self.dcln() {
-                Some(d) => d,
-                None => break,
-            };
+        while let Some(d) = self.dcln()? {
             params.push(d);
-            if self.peek() == Token::Semicolon {
+            if self.check(Token::Semicolon) {
                 self.advance();
             } else {
                 break;
             }
         }
-        Params { params }
+        Ok(Params { params })
     }
 
     // Body -> 'begin' Statement list ';' 'end' => "block";
-    fn body(&mut self) -> Option<Body> {
-        if self.peek() == Token::Keyword(Keyword::Begin) {
+    fn body(&mut self) -> ParseResult<Option<Body>> {
+        if self.check(Token::Keyword(Keyword::Begin)) {
             self.advance();
-            let statements = self._statement_list();
-            if self.peek() == Token::Keyword(Keyword::End) {
+            let statements = self._statement_list()?;
+            if self.check(Token::Keyword(Keyword::End)) {
                 self.advance();
-                Some(Body { statements })
+                Ok(Some(Body { statements }))
             } else {
-                panic!("Expected 'end'");
+                Err(self.error("Expected 'end'"))
             }
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -656,34 +857,34 @@ impl Parser {
     // Statement -> Body;
     // Statement -> Assignment;
     // Statement -> => "<null>";
-    fn statement(&mut self) -> Statement {
-        match self.peek() {
+    fn statement(&mut self) -> ParseResult<Statement> {
+        let statement = match self.peek_for(&STATEMENT_STARTS) {
             Token::Keyword(Keyword::Output) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
+                if self.check(Token::LeftParen) {
                     self.advance();
-                    let outexps = self._statement_outexp_list();
-                    if self.peek() == Token::RightParen {
+                    let outexps = self._statement_outexp_list()?;
+                    if self.check(Token::RightParen) {
                         self.advance();
                         Statement::Output {
                             expressions: outexps,
                         }
                     } else {
-                        panic!("Expected ')'");
+                        return Err(self.error("Expected ')'"));
                     }
                 } else {
-                    panic!("Expected '('");
+                    return Err(self.error("Expected '('"));
                 }
             }
             Token::Keyword(Keyword::If) => {
                 self.advance();
-                let expr = self.expression();
-                if self.peek() == Token::Keyword(Keyword::Then) {
+                let expr = self.expression()?;
+                if self.check(Token::Keyword(Keyword::Then)) {
                     self.advance();
-                    let stmt = self.statement();
-                    let else_stmt = if self.peek() == Token::Keyword(Keyword::Else) {
+                    let stmt = self.statement()?;
+                    let else_stmt = if self.check(Token::Keyword(Keyword::Else)) {
                         self.advance();
-                        Some(self.statement())
+                        Some(self.statement()?)
                     } else {
                         None
                     };
@@ -693,75 +894,75 @@ impl Parser {
                         else_stmt: else_stmt.map(Box::new),
                     }
                 } else {
-                    panic!("Expected 'then'");
+                    return Err(self.error("Expected 'then'"));
                 }
             }
             Token::Keyword(Keyword::While) => {
                 self.advance();
-                let expr = self.expression();
-                if self.peek() == Token::Keyword(Keyword::Do) {
+                let expr = self.expression()?;
+                if self.check(Token::Keyword(Keyword::Do)) {
                     self.advance();
-                    let stmt = self.statement();
+                    let stmt = self.statement()?;
                     Statement::While {
                         cond: expr,
                         stmt: Box::new(stmt),
                     }
                 } else {
-                    panic!("Expected 'do'");
+                    return Err(self.error("Expected 'do'"));
                 }
             }
             Token::Keyword(Keyword::Repeat) => {
                 self.advance();
-                let stmts = self._statement_list();
-                if self.peek() == Token::Keyword(Keyword::Until) {
+                let stmts = self._statement_list()?;
+                if self.check(Token::Keyword(Keyword::Until)) {
                     self.advance();
-                    let expr = self.expression();
+                    let expr = self.expression()?;
                     Statement::Repeat {
                         stmts,
                         cond: expr,
                     }
                 } else {
-                    panic!("Expected 'until'");
+                    return Err(self.error("Expected 'until'"));
                 }
             }
             Token::Keyword(Keyword::For) => {
                 self.advance();
-                if self.peek() != Token::LeftParen {
-                    panic!("Expected '('");
+                if !self.check(Token::LeftParen) {
+                    return Err(self.error("Expected '('"));
                 }
                 self.advance();
 
-                let init = match self.assignment() {
+                let init = match self.assignment()? {
                     Some(a) => ForStat::Assignment(a),
                     None => ForStat::Null,
                 };
-                if self.peek() != Token::Semicolon {
-                    panic!("Expected ';'");
+                if !self.check(Token::Semicolon) {
+                    return Err(self.error("Expected ';'"));
                 }
                 self.advance();
 
-                let cond  = if self.peek() == Token::Semicolon {
+                let cond  = if self.check(Token::Semicolon) {
                     self.advance();
                     ForExp::True
                 } else {
-                    let cond = self.expression();
-                    if self.peek() != Token::Semicolon {
-                        panic!("Expected ';'");
+                    let cond = self.expression()?;
+                    if !self.check(Token::Semicolon) {
+                        return Err(self.error("Expected ';'"));
                     }
                     self.advance();
                     ForExp::Expression(cond)
                 };
 
-                let update = match self.assignment() {
+                let update = match self.assignment()? {
                     Some(a) => ForStat::Assignment(a),
                     None => ForStat::Null,
                 };
-                if self.peek() != Token::RightParen {
-                    panic!("Expected ')'");
+                if !self.check(Token::RightParen) {
+                    return Err(self.error("Expected ')'"));
                 }
 
                 self.advance();
-                let stmt = self.statement();
+                let stmt = self.statement()?;
                 Statement::For {
                     init,
                     cond,
@@ -771,47 +972,47 @@ impl Parser {
             }
             Token::Keyword(Keyword::Loop) => {
                 self.advance();
-                let stmts = self._statement_list();
-                if self.peek() == Token::Keyword(Keyword::Pool) {
+                let stmts = self._statement_list()?;
+                if self.check(Token::Keyword(Keyword::Pool)) {
                     self.advance();
                     Statement::Loop {
                         stmts,
                     }
                 } else {
-                    panic!("Expected 'pool'");
+                    return Err(self.error("Expected 'pool'"));
                 }
             }
             Token::Keyword(Keyword::Case) => {
                 // Statement -> 'case' Expression 'of' Caseclauses OtherwiseClause 'end' => "case";
                 self.advance();
-                let expr = self.expression();
-                if self.peek() != Token::Keyword(Keyword::Of) {
-                    panic!("Expected 'of'");
+                let expr = self.expression()?;
+                if !self.check(Token::Keyword(Keyword::Of)) {
+                    return Err(self.error("Expected 'of'"));
                 }
                 self.advance();
 
                 // case clauses
                 let mut case_clauses = Vec::new();
                 loop {
-                    let clause = self.case_clause();
+                    let clause = self.case_clause()?;
                     case_clauses.push(clause);
-                    if self.peek() == Token::Semicolon {
+                    if self.check(Token::Semicolon) {
                         self.advance();
                     }
-                    if self.peek() == Token::Keyword(Keyword::End) {
+                    if self.check(Token::Keyword(Keyword::End)) {
                         break;
                     }
-                    if self.peek() == Token::Keyword(Keyword::Otherwise) {
+                    if self.check(Token::Keyword(Keyword::Otherwise)) {
                         break;
                     }
                 }
 
                 // otherwise clause
-                let otherwise_clause = self.otherwise_clause();
+                let otherwise_clause = self.otherwise_clause()?;
 
                 // end case
-                if self.peek() != Token::Keyword(Keyword::End) {
-                    panic!("Expected 'end'");
+                if !self.check(Token::Keyword(Keyword::End)) {
+                    return Err(self.error("Expected 'end'"));
                 }
                 self.advance();
 
@@ -824,236 +1025,241 @@ impl Parser {
             }
             Token::Keyword(Keyword::Read) => {
                 self.advance();
-                if self.peek() == Token::LeftParen {
+                if self.check(Token::LeftParen) {
                     self.advance();
-                    let names = self._name_list();
-                    if self.peek() == Token::RightParen {
+                    let names = self._name_list()?;
+                    if self.check(Token::RightParen) {
                         self.advance();
                         Statement::Read {
                             names,
                         }
                     } else {
-                        panic!("Expected ')'");
+                        return Err(self.error("Expected ')'"));
                     }
                 } else {
-                    panic!("Expected '('");
+                    return Err(self.error("Expected '('"));
                 }
             }
             Token::Keyword(Keyword::Exit) => {
                 self.advance();
-                Statement::Exit
+                Statement::Exit { span: self.previous_span() }
             }
             Token::Keyword(Keyword::Return) => {
                 self.advance();
-                let expr = self.expression();
+                let span = self.previous_span();
+                let expr = self.expression()?;
                 Statement::Return {
                     exp: expr,
+                    span,
                 }
             }
             Token::Keyword(Keyword::Begin) => {
-                Statement::Body{
-                    body: self.body().expect("Expected body"),
+                match self.body()? {
+                    Some(body) => Statement::Body { body },
+                    None => return Err(self.error("Expected body")),
                 }
             }
             Token::Identifier(_) => {
-                Statement::Assign {
-                    assignment: self.assignment().expect("Expected assignment"),
+                match self.assignment()? {
+                    Some(assignment) => Statement::Assign { assignment },
+                    None => return Err(self.error("Expected assignment")),
                 }
             }
             _ => Statement::Null,
-        }
+        };
+        Ok(statement)
     }
 
-    fn _statement_outexp_list(&mut self) -> Vec<OutExp> {
+    fn _statement_outexp_list(&mut self) -> ParseResult<Vec<OutExp>> {
         let mut outexps = Vec::new();
         loop {
-            let outexp = self.outexp();
+            let outexp = self.outexp()?;
             outexps.push(outexp);
-            if self.peek() == Token::Comma {
+            if self.check(Token::Comma) {
                 self.advance();
             } else {
                 break;
             }
         }
-        outexps
+        Ok(outexps)
     }
 
-    fn _statement_list(&mut self) -> Vec<Statement> {
+    fn _statement_list(&mut self) -> ParseResult<Vec<Statement>> {
         let mut stmts = Vec::new();
         loop {
-            let stmt = self.statement();
+            let stmt = self.statement()?;
             stmts.push(stmt);
-            if self.peek() == Token::Semicolon {
+            if self.check(Token::Semicolon) {
                 self.advance();
             } else {
                 break;
             }
         }
-        stmts
+        Ok(stmts)
     }
 
-    fn _name_list(&mut self) -> Vec<Identifier> {
+    fn _name_list(&mut self) -> ParseResult<Vec<Identifier>> {
         let mut names = Vec::new();
         loop {
-            let name = self.name().expect("Expected name");
+            let name = self.expect_name("Expected name")?;
             names.push(name);
-            if self.peek() == Token::Comma {
+            if self.check(Token::Comma) {
                 self.advance();
             } else {
                 break;
             }
         }
-        names
+        Ok(names)
     }
 
     // OutExp -> Expression => "integer";
     // OutExp -> StringNode => "string";
-    fn outexp(&mut self) -> OutExp {
-        let expr = self.expression();
-        match self.peek() {
+    fn outexp(&mut self) -> ParseResult<OutExp> {
+        let outexp = match self.peek_for(&[STRING]) {
             Token::String(s) => {
+                let value = s.to_string();
                 self.advance();
                 OutExp::String{
-                    value: s.to_string(),
+                    value,
                 }
             }
             _ => OutExp::Integer{
-                exp: expr,
+                exp: self.expression()?,
             }
-        }
+        };
+        Ok(outexp)
     }
 
     // Assignment -> Name ':=' Expression => "assign";
     // Assignment -> Name ':=:' Name => "swap";
-    fn assignment(&mut self) -> Option<Assignment> {
-        let name1 = match self.peek() {
-            Token::Identifier(name) => Identifier { name: name.to_string()},
-            _ => return None,
+    fn assignment(&mut self) -> ParseResult<Option<Assignment>> {
+        let name1 = match self.name() {
+            Some(name) => name,
+            None => return Ok(None),
         };
-        self.advance();
-        match self.peek() {
+        match self.peek_for(&[Token::Operator(Operator::Assignment), Token::Operator(Operator::Swap)]) {
             Token::Operator(Operator::Assignment) => {
                 self.advance();
-                let expr = self.expression();
-                Some(Assignment::Assignment {
+                let expr = self.expression()?;
+                Ok(Some(Assignment::Assignment {
                     name: name1,
                     exp: expr,
-                })
+                }))
             }
             Token::Operator(Operator::Swap) => {
                 self.advance();
-                let name2 = match self.peek() {
-                    Token::Identifier(name) => Identifier { name: name.to_string()},
-                    _ => panic!("Expected identifier"),
-                };
-                self.advance();
-                Some(Assignment::Swap {
+                let name2 = self.expect_name("Expected identifier")?;
+                Ok(Some(Assignment::Swap {
                     name1,
                     name2,
-                })
+                }))
             }
-            _ => panic!("Expected ':=' or ':=:'"),
+            _ => Err(self.error("Expected ':=' or ':=:'")),
         }
     }
 
     // Caseclause -> CaseExpression list ',' ':' Statement => "case_clause";
-    fn case_clause(&mut self) -> CaseClause {
+    fn case_clause(&mut self) -> ParseResult<CaseClause> {
         let mut exprs = Vec::new();
         loop {
-            let expr = self.case_expression();
+            let expr = self.case_expression()?;
             exprs.push(expr);
-            if self.peek() == Token::Comma {
+            if self.check(Token::Comma) {
                 self.advance();
-            } else if self.peek() == Token::Colon {
+            } else if self.check(Token::Colon) {
                 self.advance();
                 break;
             } else {
-                panic!("Expected ',' or ':'");
+                return Err(self.error("Expected ',' or ':'"));
             }
         }
-        let stmt = self.statement();
-        CaseClause {
-            expressions: exprs,
+        let stmt = self.statement()?;
+        Ok(CaseClause {
+            labels: exprs,
             statement: stmt,
-        }
+        })
     }
 
     // CaseExpression -> ConstValue;
     // CaseExpression -> ConstValue '..' ConstValue => "..";
-    fn case_expression(&mut self) -> CaseExpression {
-        let expr1 = self.const_value();
-        if self.peek() == Token::Dots {
+    fn case_expression(&mut self) -> ParseResult<CaseLabel> {
+        let start = self.current_span();
+        let expr1 = self.const_value()?;
+        let expression = if self.check(Token::Dots) {
             self.advance();
-            let expr2 = self.const_value();
+            let expr2 = self.const_value()?;
             CaseExpression::Range(expr1, expr2)
         } else {
             CaseExpression::Value(expr1)
-        }
+        };
+        Ok(CaseLabel { expression, span: start.until(self.previous_span()) })
     }
 
     // OtherwiseClause -> 'otherwise' Statement => "otherwise";
     // OtherwiseClause -> ;
-    fn otherwise_clause(&mut self) -> Option<OtherwiseClause> {
-        if self.peek() == Token::Keyword(Keyword::Otherwise) {
+    fn otherwise_clause(&mut self) -> ParseResult<Option<OtherwiseClause>> {
+        if self.check(Token::Keyword(Keyword::Otherwise)) {
             self.advance();
-            let stmt = self.statement();
-            Some(OtherwiseClause {
+            let stmt = self.statement()?;
+            Ok(Some(OtherwiseClause {
                 stmt,
-            })
+            }))
         } else {
-            None
+            Ok(None)
         }
     }
 
     // SubProgs -> Fcn* => "subprogs";
-    fn sub_progs(&mut self) -> SubProgs {
+    fn sub_progs(&mut self) -> ParseResult<SubProgs> {
         let mut sub_progs = Vec::new();
-        loop {
-            match self.func() {
-                Some(func) => sub_progs.push(func),
-                None => break,
-            }
+        while let Some(func) = self.func()? {
+            sub_progs.push(func);
         }
-        SubProgs {
+        Ok(SubProgs {
             sub_progs,
-        }
+        })
     }
 
     // Fcn -> 'function' Name '(' Params ')' ':' Name ';' Consts Types Dclns Body Name ';' => "fcn";
-    fn func(&mut self) -> Option<Func> {
-        if self.peek() != Token::Keyword(Keyword::Function) {
-            return None;
+    fn func(&mut self) -> ParseResult<Option<Func>> {
+        if !self.check(Token::Keyword(Keyword::Function)) {
+            return Ok(None);
         }
+        let doc = self.docs.get(&self.current.get()).cloned();
         self.advance();
-        let name = self.name().expect("Expected name");
-        if self.peek() != Token::LeftParen {
-            panic!("Expected '('");
+        let name = self.expect_name("Expected function name")?;
+        if !self.check(Token::LeftParen) {
+            return Err(self.error("Expected '('"));
         }
         self.advance();
-        let params = self.params();
-        if self.peek() != Token::RightParen {
-            panic!("Expected ')'");
+        let params = self.params()?;
+        if !self.check(Token::RightParen) {
+            return Err(self.error("Expected ')'"));
         }
         self.advance();
-        if self.peek() != Token::Colon {
-            panic!("Expected ':'");
+        if !self.check(Token::Colon) {
+            return Err(self.error("Expected ':'"));
         }
         self.advance();
-        let return_type = self.name().expect("Expected name");
-        if self.peek() != Token::Semicolon {
-            panic!("Expected ';'");
+        let return_type = self.expect_name("Expected return type name")?;
+        if !self.check(Token::Semicolon) {
+            return Err(self.error("Expected ';'"));
         }
         self.advance();
-        let consts = self.consts();
-        let types = self.types();
-        let dclns = self.dclns();
-        let body = self.body().expect("Expected body");
-        let end_name = self.name().expect("Expected name");
-        if self.peek() != Token::Semicolon {
-            panic!("Expected ';'");
+        let consts = self.consts()?;
+        let types = self.types()?;
+        let dclns = self.dclns()?;
+        let body = match self.body()? {
+            Some(body) => body,
+            None => return Err(self.error("Expected function body")),
+        };
+        let end_name = self.expect_name("Expected function end name")?;
+        if !self.check(Token::Semicolon) {
+            return Err(self.error("Expected ';'"));
         }
         self.advance();
-        Some(Func {
+        Ok(Some(Func {
+            doc,
             name,
             params,
             return_type,
@@ -1062,7 +1268,20 @@ impl Parser {
             dclns,
             body,
             end_name,
-        })
+        }))
     }
 }
 
+/// Joins the comments forming a doc comment and strips the indentation they share.
+fn doc_text(comments: &[String]) -> String {
+    let lines: Vec<&str> = comments.iter().flat_map(|c| c.lines()).collect();
+    let indent = lines.iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let lines: Vec<&str> = lines.iter()
+        .map(|l| if l.trim().is_empty() { "" } else { l[indent..].trim_end() })
+        .collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
@@ -0,0 +1,148 @@
+//! Source text for parts of the tree, on one line, for labels and messages that show code.
+//! Parentheses are printed where the source had them, which is enough for the text to
+//! parse back to the same tree.
+
+use crate::parser::tree::{char_literal, string_literal};
+use crate::parser::*;
+
+pub fn statement(statement: &Statement) -> String {
+    match statement {
+        Statement::Assign { assignment: a } => assignment(a),
+        Statement::Output { expressions } => {
+            let expressions: Vec<String> = expressions
+                .iter()
+                .map(|e| match e {
+                    OutExp::Integer { exp } => expression(exp),
+                    OutExp::String { value } => string_literal(value),
+                })
+                .collect();
+            format!("output({})", expressions.join(", "))
+        }
+        Statement::If { cond, then, else_stmt } => match else_stmt {
+            Some(else_stmt) => {
+                format!("if {} then {} else {}", expression(cond), self::statement(then), self::statement(else_stmt))
+            }
+            None => format!("if {} then {}", expression(cond), self::statement(then)),
+        },
+        Statement::While { cond, stmt } => format!("while {} do {}", expression(cond), self::statement(stmt)),
+        Statement::Repeat { stmts, cond } => format!("repeat {} until {}", statements(stmts), expression(cond)),
+        Statement::For { init, cond, update, stmt } => {
+            let cond = match cond {
+                ForExp::Expression(cond) => expression(cond),
+                ForExp::True => String::new(),
+            };
+            format!("for ({}; {}; {}) {}", for_stat(init), cond, for_stat(update), self::statement(stmt))
+        }
+        Statement::Loop { stmts } => format!("loop {} pool", statements(stmts)),
+        Statement::Case { expr, cases, otherwise } => {
+            let clauses: Vec<String> = cases
+                .iter()
+                .map(|c| {
+                    let labels: Vec<String> = c.labels.iter().map(|l| case_expression(&l.expression)).collect();
+                    format!("{}: {}", labels.join(", "), self::statement(&c.statement))
+                })
+                .collect();
+            let otherwise = match otherwise {
+                Some(otherwise) => format!(" otherwise {}", self::statement(&otherwise.stmt)),
+                None => String::new(),
+            };
+            format!("case {} of {}{} end", expression(expr), clauses.join("; "), otherwise)
+        }
+        Statement::Read { names } => {
+            let names: Vec<&str> = names.iter().map(|n| n.name.as_str()).collect();
+            format!("read({})", names.join(", "))
+        }
+        Statement::Exit { .. } => "exit".to_string(),
+        Statement::Return { exp, .. } => format!("return {}", expression(exp)),
+        Statement::Body { body } => format!("begin {} end", statements(&body.statements)),
+        Statement::Null => String::new(),
+    }
+}
+
+fn statements(statements: &[Statement]) -> String {
+    statements.iter().map(statement).collect::<Vec<_>>().join("; ")
+}
+
+pub fn assignment(assignment: &Assignment) -> String {
+    match assignment {
+        Assignment::Assignment { name, exp } => format!("{} := {}", name.name, expression(exp)),
+        Assignment::Swap { name1, name2 } => format!("{} :=: {}", name1.name, name2.name),
+    }
+}
+
+fn for_stat(stat: &ForStat) -> String {
+    match stat {
+        ForStat::Assignment(a) => assignment(a),
+        ForStat::Null => String::new(),
+    }
+}
+
+fn case_expression(expression: &CaseExpression) -> String {
+    match expression {
+        CaseExpression::Value(value) => const_value(value),
+        CaseExpression::Range(low, high) => format!("{}..{}", const_value(low), const_value(high)),
+    }
+}
+
+fn const_value(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Integer(i) => i.to_string(),
+        ConstValue::Char(c) => char_literal(*c),
+        ConstValue::Name(name) => name.name.clone(),
+    }
+}
+
+pub fn expression(expression: &Expression) -> String {
+    let (left, operator, right) = match expression {
+        Expression::Le { left, right } => (left, "<=", right),
+        Expression::Lt { left, right } => (left, "<", right),
+        Expression::Ge { left, right } => (left, ">=", right),
+        Expression::Gt { left, right } => (left, ">", right),
+        Expression::Eq { left, right } => (left, "=", right),
+        Expression::Ne { left, right } => (left, "<>", right),
+        Expression::Term(term) => return self::term(term),
+    };
+    format!("{} {} {}", term(left), operator, term(right))
+}
+
+fn term(term: &Term) -> String {
+    let (left, operator, right) = match term {
+        Term::Add { left, right } => (left, "+", right),
+        Term::Subtract { left, right } => (left, "-", right),
+        Term::Or { left, right } => (left, "or", right),
+        Term::Factor(f) => return factor(f),
+    };
+    format!("{} {} {}", self::term(left), operator, factor(right))
+}
+
+fn factor(factor: &Factor) -> String {
+    let (left, operator, right) = match factor {
+        Factor::Multiply { left, right } => (left, "*", right),
+        Factor::Divide { left, right } => (left, "/", right),
+        Factor::And { left, right } => (left, "and", right),
+        Factor::Mod { left, right } => (left, "mod", right),
+        Factor::Primary(p) => return primary(p),
+    };
+    format!("{} {} {}", self::factor(left), operator, primary(right))
+}
+
+fn primary(primary: &Primary) -> String {
+    match primary {
+        Primary::Negate { primary } => format!("-{}", self::primary(primary)),
+        Primary::Not { primary } => format!("not {}", self::primary(primary)),
+        Primary::Eof => "eof".to_string(),
+        Primary::Name(name) => name.name.clone(),
+        // literals are magnitudes, but the one of i64::MIN has wrapped
+        Primary::Integer(i) => i.to_string(),
+        Primary::Char(c) => char_literal(*c),
+        Primary::Call { name, exps } => {
+            let arguments: Vec<String> = exps.iter().map(expression).collect();
+            format!("{}({})", name.name, arguments.join(", "))
+        }
+        Primary::Expression(exp) => format!("({})", expression(exp)),
+        Primary::Succ { exp } => format!("succ({})", expression(exp)),
+        Primary::Pred { exp } => format!("pred({})", expression(exp)),
+        Primary::Chr { exp } => format!("chr({})", expression(exp)),
+        Primary::Ord { exp } => format!("ord({})", expression(exp)),
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::lexer::{Lexer};
+    use crate::lexer::{Keyword, Lexer, LexerConfig, Operator, Token, WordSize};
     use crate::parser::{Parser};
     use crate::parser::tree::StringTree;
 
@@ -9,7 +9,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_01").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_01.tree").trim().to_string();
@@ -21,7 +21,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_02").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_02.tree").trim().to_string();
@@ -33,7 +33,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_03").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_03.tree").trim().to_string();
@@ -45,7 +45,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_04").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_04.tree").trim().to_string();
@@ -57,7 +57,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_05").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_05.tree").trim().to_string();
@@ -69,7 +69,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_06").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_06.tree").trim().to_string();
@@ -81,7 +81,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_07").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_07.tree").trim().to_string();
@@ -93,7 +93,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_08").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_08.tree").trim().to_string();
@@ -105,7 +105,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_09").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_09.tree").trim().to_string();
@@ -117,7 +117,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_10").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_10.tree").trim().to_string();
@@ -129,7 +129,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_11").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_11.tree").trim().to_string();
@@ -141,7 +141,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_12").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_12.tree").trim().to_string();
@@ -153,7 +153,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_13").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_13.tree").trim().to_string();
@@ -165,7 +165,7 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_14").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_14.tree").trim().to_string();
@@ -177,10 +177,348 @@ mod tests {
         let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_15").to_string());
         let tokens = lexer.lex();
         let mut parser = Parser::new(tokens, false);
-        let program = parser.parse();
+        let program = parser.parse().unwrap();
 
         let tree_str = program.get_string_tree(0).join("\n").trim().to_string();
         let expected = include_str!("./../../winzig_test_programs/winzig_15.tree").trim().to_string();
         assert_eq!(tree_str, expected);
     }
+
+    #[test]
+    fn test_output_string() {
+        let mut lexer = Lexer::new("program p: begin output(\"a \"\"b\"\"\", 1) end p.".to_string());
+        let tokens = lexer.lex();
+        let mut parser = Parser::new(tokens, false);
+        let program = parser.parse().unwrap();
+
+        let tree = program.get_string_tree(0);
+        assert_eq!(tree[9], ". . . <string>(1)");
+        assert_eq!(tree[10], ". . . . \"a \"\"b\"\"\"(0)");
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let source = "## Adds one.\n##   Really.\nfunction f(n : integer) : integer;\nbegin return (n + 1) end f;\n";
+        let mut lexer = Lexer::new(format!("{{ not a doc }} # plain\nprogram p:\n{}# plain\nfunction g(n : integer) : integer;\nbegin return (n) end g;\nbegin end p.", source));
+        let tokens = lexer.lex();
+        let mut parser = Parser::new(tokens, false);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.doc(), None);
+        assert_eq!(program.functions()[0].doc(), Some("Adds one.\n  Really."));
+        assert_eq!(program.functions()[1].doc(), None);
+    }
+
+    #[test]
+    fn test_program_doc_comment() {
+        let mut lexer = Lexer::new(include_str!("./../../winzig_test_programs/winzig_01").to_string());
+        let tokens = lexer.lex();
+        let mut parser = Parser::new(tokens, false);
+        let program = parser.parse().unwrap();
+
+        let doc = program.doc().unwrap();
+        assert!(doc.starts_with("This is a program to compute the factors of entered numbers.\nIt tests:\n\tprocedures"));
+        assert_eq!(program.functions()[0].doc(), None);
+    }
+
+    #[test]
+    fn test_skip_error_tokens() {
+        let mut lexer = Lexer::new("program p: begin output(1) @ end p.".to_string());
+        let tokens = lexer.lex();
+        assert_eq!(lexer.diagnostics().len(), 1);
+        let mut parser = Parser::new(tokens, false);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.get_string_tree(0).len(), 14);
+    }
+
+    fn fragment(source: &str) -> Parser {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false)
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let expression = fragment("a + f(b, 2) * 3 <= 10").parse_expression().unwrap();
+        let tree = expression.get_string_tree(0);
+        assert_eq!(tree[0], "<=(2)");
+        assert_eq!(tree[1], ". +(2)");
+
+        let error = fragment("a + ").parse_expression().unwrap_err();
+        assert_eq!(error.message, "Expected primary, found end of input");
+        assert_eq!((error.span.line, error.span.column), (1, 4));
+
+        let error = fragment("a b").parse_expression().unwrap_err();
+        assert_eq!(error.message, "Expected end of input, found 'b'");
+        assert_eq!(error.span.column, 3);
+    }
+
+    #[test]
+    fn test_parse_statement() {
+        let statement = fragment("if a > b then output(a) else output(b)").parse_statement().unwrap();
+        assert_eq!(statement.get_string_tree(0)[0], "if(3)");
+
+        let error = fragment("output(1); output(2)").parse_statement().unwrap_err();
+        assert_eq!(error.message, "Expected end of input, found ';'");
+
+        let error = fragment("while a do").parse_statement();
+        assert!(error.is_ok(), "a null statement is a valid loop body");
+    }
+
+    #[test]
+    fn test_parse_declarations() {
+        let declarations = fragment("const n = 10; type color = (red, green); var a, b : integer; c : color;")
+            .parse_declarations()
+            .unwrap();
+        assert_eq!(declarations.consts.consts.len(), 1);
+        assert_eq!(declarations.types.types[0].lit_list.names.len(), 2);
+        assert_eq!(declarations.dclns.vars.len(), 2);
+
+        let empty = fragment("").parse_declarations().unwrap();
+        assert!(empty.consts.consts.is_empty() && empty.dclns.vars.is_empty());
+
+        let error = fragment("var a : integer").parse_declarations().unwrap_err();
+        assert_eq!(error.message, "Expected ';', found end of input");
+    }
+
+    #[test]
+    fn test_parse_function() {
+        let func = fragment("## Doubles n.\nfunction f(n : integer) : integer;\nbegin return (2 * n) end f;")
+            .parse_function()
+            .unwrap();
+        assert_eq!(func.name(), "f");
+        assert_eq!(func.doc(), Some("Doubles n."));
+        assert_eq!(func.get_string_tree(0)[0], "fcn(8)");
+
+        let error = fragment("begin end").parse_function().unwrap_err();
+        assert_eq!(error.message, "Expected 'function', found 'begin'");
+        assert_eq!((error.span.line, error.span.column), (1, 1));
+    }
+
+    fn expected(source: &str) -> Vec<Token> {
+        let mut parser = fragment(source);
+        assert!(parser.parse().is_err());
+        parser.expected()
+    }
+
+    #[test]
+    fn test_expected_tokens() {
+        use Keyword::*;
+        let keywords = |keywords: &[Keyword]| keywords.iter().map(|k| Token::Keyword(k.clone())).collect::<Vec<_>>();
+
+        assert_eq!(expected("program p: "), keywords(&[Const, Type, Var, Function, Begin]));
+        assert_eq!(expected("program p: var x : "), [Token::Identifier(String::new())]);
+        assert_eq!(expected("program p: begin output(1)"), [Token::Semicolon, Token::Keyword(End)]);
+
+        // after an operand, any operator may follow as well as what ends the statement
+        let after_operand = expected("program p: begin if a then x := 1 ");
+        assert_eq!(after_operand.len(), 16);
+        for token in [Token::Operator(Operator::Multiply), Token::Keyword(Or), Token::Operator(Operator::NotEqual)] {
+            assert!(after_operand.contains(&token), "{:?}", token);
+        }
+        assert!(after_operand.ends_with(&[Token::Keyword(Else), Token::Semicolon, Token::Keyword(End)]));
+        // a name may be called
+        assert!(expected("program p: begin x := a ").contains(&Token::LeftParen));
+
+        // every start of a primary, with names and literals given empty
+        let mut parser = fragment("a + ");
+        assert!(parser.parse_expression().is_err());
+        assert_eq!(
+            parser.expected(),
+            [
+                Token::Operator(Operator::Minus),
+                Token::Operator(Operator::Plus),
+                Token::Keyword(Not),
+                Token::Keyword(Eof),
+                Token::Identifier(String::new()),
+                Token::Integer(0),
+                Token::Char('\0'),
+                Token::LeftParen,
+                Token::Keyword(Succ),
+                Token::Keyword(Pred),
+                Token::Keyword(Chr),
+                Token::Keyword(Ord),
+            ]
+        );
+
+        // nothing is expected at the end when the error comes before it
+        assert!(expected("program p: begin ) ").is_empty());
+    }
+
+    #[test]
+    fn test_integer_range() {
+        let errors = |source: &str| {
+            let config = LexerConfig { word_size: WordSize::W16, ..LexerConfig::default() };
+            let mut lexer = Lexer::with_config(source.to_string(), config);
+            let mut parser = Parser::with_spans(lexer.tokenize(), false).with_word_size(WordSize::W16);
+            parser.parse_statements().unwrap();
+            parser.diagnostics().iter().map(|d| d.to_string()).collect::<Vec<_>>()
+        };
+        assert!(errors("x := -32768; y := (- 32768); z := -32767").is_empty());
+        assert_eq!(errors("x := 32768"), ["1:6: error: integer literal out of range for 16-bit word: 32768"]);
+        // subtracting it isn't negating it
+        assert_eq!(errors("x := 1 - 32768").len(), 1);
+        assert_eq!(errors("x := -(32768)").len(), 1);
+        assert_eq!(errors("case x of 32768: x := 1 end").len(), 1);
+
+        let mut parser = fragment("const a = 32768;").with_word_size(WordSize::W16);
+        parser.parse_declarations().unwrap();
+        assert_eq!(parser.diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn test_case_insensitive_names() {
+        let config = LexerConfig { case_insensitive: true, ..LexerConfig::default() };
+        let source = "PROGRAM Demo: VAR MyVar : Integer; BEGIN myvar := 1 END DEMO.";
+        let mut lexer = Lexer::with_config(source.to_string(), config);
+        let program = Parser::with_spans(lexer.tokenize(), false).parse().unwrap();
+        // names are kept as written, and compared folded
+        let names: Vec<(&str, &str)> =
+            program.dclns.vars[0].names.iter().map(|n| (n.name.as_str(), n.key.as_str())).collect();
+        assert_eq!(names, [("MyVar", "myvar")]);
+        assert_eq!((program.name.name.as_str(), program.end_name.name.as_str()), ("Demo", "DEMO"));
+        assert_eq!(program.name.key, program.end_name.key);
+        let tree = program.get_string_tree(0).join("\n");
+        assert!(tree.contains("MyVar") && tree.contains("myvar") && tree.contains("Integer"));
+    }
+
+    #[test]
+    fn test_const_list() {
+        let mut lexer = Lexer::new("program p: const a = 1, b = 2; begin output(a) end p.".to_string());
+        let tokens = lexer.lex();
+        let mut parser = Parser::new(tokens, false);
+        let program = parser.parse().unwrap();
+
+        let names: Vec<&str> = program.consts.consts.iter().map(|c| c.name.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    mod incremental {
+        use std::ops::Range;
+
+        use crate::lexer::{LexerConfig, WordSize};
+        use crate::parser::incremental::{ParsedFile, Reparsed};
+
+        const MERGE: &str = include_str!("./../../winzig_test_programs/winzig_12");
+
+        /// Edits `text` incrementally and checks the result against parsing the edited text.
+        fn edit(text: &str, range: Range<usize>, new_text: &str) -> ParsedFile {
+            let mut edited = ParsedFile::parse(text.to_string(), LexerConfig::default()).unwrap();
+            edited.edit(range.clone(), new_text).unwrap();
+            let mut expected_text = text.to_string();
+            expected_text.replace_range(range, new_text);
+            let expected = ParsedFile::parse(expected_text, LexerConfig::default()).unwrap();
+            assert_eq!(edited.text(), expected.text());
+            assert_eq!(edited.tokens(), expected.tokens());
+            assert_eq!(edited.program(), expected.program());
+            assert_eq!(edited.diagnostics(), expected.diagnostics());
+            edited
+        }
+
+        fn replace(text: &str, old: &str, new_text: &str) -> ParsedFile {
+            let start = text.find(old).unwrap();
+            edit(text, start..start + old.len(), new_text)
+        }
+
+        #[test]
+        fn test_reparse_statement() {
+            let edited = replace(MERGE, "nexthigh := middle+1", "nexthigh := middle + 2");
+            assert_eq!(edited.reparsed(), Reparsed::Statement { function: Some(6), index: 3 });
+            assert!(edited.relexed() < 20);
+
+            let edited = replace(MERGE, "d:=MergeSort ( 1, 6 )", "d:=MergeSort ( 1, n )");
+            assert_eq!(edited.reparsed(), Reparsed::Statement { function: None, index: 1 });
+
+            // new lines move everything after the edit down
+            let start = MERGE.find("i := 1;").unwrap();
+            let edited = edit(MERGE, start..start + "i := 1".len(), "i :=\n\n\t\t1");
+            assert_eq!(edited.reparsed(), Reparsed::Statement { function: Some(6), index: 0 });
+        }
+
+        #[test]
+        fn test_reparse_function() {
+            let edited = replace(MERGE, "function ElementA ( index : integer )", "function ElementA ( index, i : integer )");
+            assert_eq!(edited.reparsed(), Reparsed::Function(3));
+
+            // a second statement where there was one
+            let edited = replace(MERGE, "i := 1;\n\tlast", "i := 1; i := 2;\n\tlast");
+            assert_eq!(edited.reparsed(), Reparsed::Function(6));
+
+            let start = MERGE.find("function StoreT").unwrap();
+            let edited = edit(MERGE, start..start, "## Stores into the temporary array.\n");
+            assert_eq!(edited.reparsed(), Reparsed::Function(1));
+            assert_eq!(edited.program().functions()[1].doc(), Some("Stores into the temporary array."));
+        }
+
+        #[test]
+        fn test_reparse_program() {
+            let edited = replace(MERGE, "i, n, x, d : integer;", "i, n, x, d, y : integer;");
+            assert_eq!(edited.reparsed(), Reparsed::Program);
+
+            // an edit that doesn't parse leaves the file as it was
+            let mut file = ParsedFile::parse(MERGE.to_string(), LexerConfig::default()).unwrap();
+            let start = MERGE.find("i := 1;").unwrap();
+            let error = file.edit(start..start, "begin ").unwrap_err();
+            assert_eq!(error.message, "Expected 'end', found 'Merge'");
+            assert_eq!(file.text(), MERGE);
+            assert_eq!(file.program(), ParsedFile::parse(MERGE.to_string(), LexerConfig::default()).unwrap().program());
+        }
+
+        #[test]
+        fn test_diagnostics() {
+            let config = LexerConfig { word_size: WordSize::W16, ..LexerConfig::default() };
+            let text = "program p: var x : integer; begin x := 1$; output(x); x := 99999 end p.";
+            let file = || ParsedFile::parse(text.to_string(), config.clone()).unwrap();
+            let replace = |old: &str, new_text| {
+                let start = text.find(old).unwrap();
+                let mut file = file();
+                file.edit(start..start + old.len(), new_text).unwrap();
+                file
+            };
+            let messages = |file: &ParsedFile| file.diagnostics().iter().map(|d| d.to_string()).collect::<Vec<_>>();
+            assert_eq!(
+                messages(&file()),
+                ["1:41: error: unexpected character '$'", "1:60: error: integer literal out of range for 16-bit word: 99999"]
+            );
+
+            // the error in the edited statement goes, the one after it moves
+            let edited = replace("1$", "12");
+            assert_eq!(edited.reparsed(), Reparsed::Statement { function: None, index: 0 });
+            assert_eq!(messages(&edited), ["1:60: error: integer literal out of range for 16-bit word: 99999"]);
+            let edited = replace("1$", "1$$ ");
+            assert_eq!(messages(&edited)[1], "1:62: error: integer literal out of range for 16-bit word: 99999");
+
+            let edited = replace("99999", "9");
+            assert_eq!(edited.reparsed(), Reparsed::Statement { function: None, index: 2 });
+            assert_eq!(messages(&edited), ["1:41: error: unexpected character '$'"]);
+        }
+
+        #[test]
+        fn test_matches_full_reparse() {
+            // insert and delete characters all over the samples; wherever the result parses, it
+            // must be what a full parse gives
+            for text in [MERGE, include_str!("./../../winzig_test_programs/winzig_11")] {
+                let file = || ParsedFile::parse(text.to_string(), LexerConfig::default()).unwrap();
+                let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).step_by(23).collect();
+                for &offset in &boundaries {
+                    let next = offset + text[offset..].chars().next().unwrap().len_utf8();
+                    for (range, new_text) in [(offset..offset, " "), (offset..next, ""), (offset..offset, "\n")] {
+                        let mut expected_text = text.to_string();
+                        expected_text.replace_range(range.clone(), new_text);
+                        let expected = ParsedFile::parse(expected_text, LexerConfig::default());
+                        let mut edited = file();
+                        match (edited.edit(range.clone(), new_text), expected) {
+                            (Ok(()), Ok(expected)) => {
+                                assert_eq!(edited.tokens(), expected.tokens(), "{:?} {:?}", range, new_text);
+                                assert_eq!(edited.program(), expected.program(), "{:?} {:?}", range, new_text);
+                                assert_eq!(edited.diagnostics(), expected.diagnostics(), "{:?} {:?}", range, new_text);
+                            }
+                            (Err(error), Err(expected)) => assert_eq!(error, expected),
+                            (result, _) => panic!("{:?} {:?}: {:?}", range, new_text, result.err()),
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
@@ -4,6 +4,19 @@ pub trait StringTree {
     fn get_string_tree(&self, level: usize) -> Vec<String>;
 }
 
+// Literals are printed the way they are written in source, with quotes doubled.
+pub fn char_literal(c: char) -> String {
+    if c == '\'' {
+        "''''".to_string()
+    } else {
+        format!("'{}'", c)
+    }
+}
+
+pub fn string_literal(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
 impl StringTree for Program {
     fn get_string_tree(&self, level: usize) -> Vec<String> {
         let mut tree = Vec::new();
@@ -15,16 +28,16 @@ impl StringTree for Program {
         tree.append(&mut self.sub_progs.get_string_tree(level + 1));
         tree.append(&mut self.body.get_string_tree(level + 1));
         tree.append(&mut self.end_name.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
 impl StringTree for Identifier {
     fn get_string_tree(&self, level: usize) -> Vec<String> {
-        return vec![
+        vec![
             format!("{}<identifier>(1)", ". ".repeat(level)),
             format!("{}{}(0)", ". ".repeat(level + 1), self.name),
-        ];
+        ]
     }
 }
 
@@ -38,7 +51,7 @@ impl StringTree for Consts {
         for const_ in &self.consts {
             tree.append(&mut const_.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -51,7 +64,7 @@ impl StringTree for Const {
         tree.push(format!("{}const({})", ". ".repeat(level), len));
         tree.append(&mut self.name.get_string_tree(level + 1));
         tree.append(&mut self.value.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -66,7 +79,7 @@ impl StringTree for Types {
         for type_ in &self.types {
             tree.append(&mut type_.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -79,7 +92,7 @@ impl StringTree for Type {
         tree.push(format!("{}type({})", ". ".repeat(level), len));
         tree.append(&mut self.name.get_string_tree(level + 1));
         tree.append(&mut self.lit_list.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -93,7 +106,7 @@ impl StringTree for LitList {
         for name in &self.names {
             tree.append(&mut name.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -107,7 +120,7 @@ impl StringTree for Dclns {
         for var in &self.vars {
             tree.append(&mut var.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -122,7 +135,7 @@ impl StringTree for Var {
             tree.append(&mut name.get_string_tree(level + 1));
         }
         tree.append(&mut self.typename.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -136,7 +149,7 @@ impl StringTree for SubProgs {
         for sub_prog in &self.sub_progs {
             tree.append(&mut sub_prog.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -155,7 +168,7 @@ impl StringTree for Func {
         tree.append(&mut self.dclns.get_string_tree(level + 1));
         tree.append(&mut self.body.get_string_tree(level + 1));
         tree.append(&mut self.end_name.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -169,7 +182,7 @@ impl StringTree for Params {
         for param in &self.params {
             tree.append(&mut param.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -183,7 +196,7 @@ impl StringTree for Body {
         for statement in &self.statements {
             tree.append(&mut statement.get_string_tree(level + 1));
         }
-        return tree;
+        tree
     }
 }
 
@@ -258,10 +271,10 @@ impl StringTree for Statement {
                     tree.append(&mut name.get_string_tree(level + 1));
                 }
             }
-            Statement::Exit => {
+            Statement::Exit { .. } => {
                 tree.push(format!("{}exit(0)", ". ".repeat(level)));
             }
-            Statement::Return {exp} => {
+            Statement::Return {exp, ..} => {
                 let len = 1;
                 tree.push(format!("{}return({})", ". ".repeat(level), len));
                 tree.append(&mut exp.get_string_tree(level + 1));
@@ -273,7 +286,7 @@ impl StringTree for Statement {
                 tree.push(format!("{}<null>(0)", ". ".repeat(level)));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -281,14 +294,14 @@ impl StringTree for CaseClause {
     fn get_string_tree(&self, level: usize) -> Vec<String> {
         let mut tree = Vec::new();
 
-        let len = self.expressions.len() + 1;
+        let len = self.labels.len() + 1;
 
         tree.push(format!("{}case_clause({})", ". ".repeat(level), len));
-        for expression in &self.expressions {
-            tree.append(&mut expression.get_string_tree(level + 1));
+        for label in &self.labels {
+            tree.append(&mut label.expression.get_string_tree(level + 1));
         }
         tree.append(&mut self.statement.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -307,7 +320,7 @@ impl StringTree for CaseExpression {
                 tree.append(&mut c2.get_string_tree(level + 1));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -324,13 +337,13 @@ impl StringTree for ConstValue {
             ConstValue::Char(c) => {
                 let len = 1;
                 tree.push(format!("{}<char>({})", ". ".repeat(level), len));
-                tree.push(format!("{}'{}'(0)", ". ".repeat(level + 1), c));
+                tree.push(format!("{}{}(0)", ". ".repeat(level + 1), char_literal(*c)));
             }
             ConstValue::Name(name) => {
                 return name.get_string_tree(level);
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -340,7 +353,7 @@ impl StringTree for OtherwiseClause {
         let len = 1;
         tree.push(format!("{}otherwise({})", ". ".repeat(level), len));
         tree.append(&mut self.stmt.get_string_tree(level + 1));
-        return tree;
+        tree
     }
 }
 
@@ -356,7 +369,7 @@ impl StringTree for ForStat {
                 tree.push(format!("{}<null>(0)", ". ".repeat(level)));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -371,7 +384,7 @@ impl StringTree for ForExp {
                 tree.push(format!("{}true(0)", ". ".repeat(level)));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -391,7 +404,7 @@ impl StringTree for Assignment {
                 tree.append(&mut name2.get_string_tree(level + 1));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -440,7 +453,7 @@ impl StringTree for Expression {
                 return term.get_string_tree(level);
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -471,7 +484,7 @@ impl StringTree for Term {
                 return factor.get_string_tree(level);
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -508,7 +521,7 @@ impl StringTree for Factor {
                 return primary.get_string_tree(level);
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -542,7 +555,7 @@ impl StringTree for Primary {
             Primary::Char(ch) => {
                 let len = 1;
                 tree.push(format!("{}<char>({})", ". ".repeat(level), len));
-                tree.push(format!("{}'{}'(0)", ". ".repeat(level + 1), ch));
+                tree.push(format!("{}{}(0)", ". ".repeat(level + 1), char_literal(*ch)));
             }
             Primary::Call { name, exps } => {
                 let len = exps.len() + 1;
@@ -576,7 +589,7 @@ impl StringTree for Primary {
                 tree.append(&mut exp.get_string_tree(level + 1));
             }
         }
-        return tree;
+        tree
     }
 }
 
@@ -593,9 +606,9 @@ impl StringTree for OutExp {
             OutExp::String { value } => {
                 let len = 1;
                 tree.push(format!("{}<string>({})", ". ".repeat(level), len));
-                tree.push(format!("{}\"{}\"(0)", ". ".repeat(level + 1), value));
+                tree.push(format!("{}{}(0)", ". ".repeat(level + 1), string_literal(value)));
             }
         }
-        return tree;
+        tree
     }
 }
\ No newline at end of file
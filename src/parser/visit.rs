@@ -0,0 +1,357 @@
+use crate::parser::*;
+
+/// How a name is used in a statement or expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameUsage {
+    /// Read as a value in an expression.
+    Value,
+    /// Target of `:=`.
+    Assign,
+    /// Either side of `:=:`.
+    Swap,
+    /// Target of `read`.
+    Read,
+    /// Callee of a function call.
+    Call,
+    /// Constant in a `case` label.
+    Label,
+}
+
+/// Walks statements and expressions in evaluation order. Override a method to inspect a node,
+/// calling the matching `walk_*` function to continue into its children.
+pub trait Visitor: Sized {
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        walk_term(self, term)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) {
+        walk_factor(self, factor)
+    }
+
+    fn visit_primary(&mut self, primary: &Primary) {
+        walk_primary(self, primary)
+    }
+
+    fn visit_name(&mut self, _name: &Identifier, _usage: NameUsage) {}
+}
+
+pub fn walk_statements<V: Visitor>(visitor: &mut V, statements: &[Statement]) {
+    for statement in statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Assign { assignment } => walk_assignment(visitor, assignment),
+        Statement::Output { expressions } => {
+            for expression in expressions {
+                if let OutExp::Integer { exp } = expression {
+                    visitor.visit_expression(exp);
+                }
+            }
+        }
+        Statement::If { cond, then, else_stmt } => {
+            visitor.visit_expression(cond);
+            visitor.visit_statement(then);
+            if let Some(else_stmt) = else_stmt {
+                visitor.visit_statement(else_stmt);
+            }
+        }
+        Statement::While { cond, stmt } => {
+            visitor.visit_expression(cond);
+            visitor.visit_statement(stmt);
+        }
+        Statement::Repeat { stmts, cond } => {
+            walk_statements(visitor, stmts);
+            visitor.visit_expression(cond);
+        }
+        Statement::For { init, cond, update, stmt } => {
+            if let ForStat::Assignment(assignment) = init {
+                walk_assignment(visitor, assignment);
+            }
+            if let ForExp::Expression(cond) = cond {
+                visitor.visit_expression(cond);
+            }
+            visitor.visit_statement(stmt);
+            if let ForStat::Assignment(assignment) = update {
+                walk_assignment(visitor, assignment);
+            }
+        }
+        Statement::Loop { stmts } => walk_statements(visitor, stmts),
+        Statement::Case { expr, cases, otherwise } => {
+            visitor.visit_expression(expr);
+            for clause in cases {
+                for label in &clause.labels {
+                    let values = match &label.expression {
+                        CaseExpression::Value(value) => vec![value],
+                        CaseExpression::Range(low, high) => vec![low, high],
+                    };
+                    for value in values {
+                        if let ConstValue::Name(name) = value {
+                            visitor.visit_name(name, NameUsage::Label);
+                        }
+                    }
+                }
+                visitor.visit_statement(&clause.statement);
+            }
+            if let Some(otherwise) = otherwise {
+                visitor.visit_statement(&otherwise.stmt);
+            }
+        }
+        Statement::Read { names } => {
+            for name in names {
+                visitor.visit_name(name, NameUsage::Read);
+            }
+        }
+        Statement::Return { exp, .. } => visitor.visit_expression(exp),
+        Statement::Body { body } => walk_statements(visitor, &body.statements),
+        Statement::Exit { .. } | Statement::Null => {}
+    }
+}
+
+pub fn walk_assignment<V: Visitor>(visitor: &mut V, assignment: &Assignment) {
+    match assignment {
+        Assignment::Assignment { name, exp } => {
+            visitor.visit_expression(exp);
+            visitor.visit_name(name, NameUsage::Assign);
+        }
+        Assignment::Swap { name1, name2 } => {
+            visitor.visit_name(name1, NameUsage::Swap);
+            visitor.visit_name(name2, NameUsage::Swap);
+        }
+    }
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Le { left, right }
+        | Expression::Lt { left, right }
+        | Expression::Ge { left, right }
+        | Expression::Gt { left, right }
+        | Expression::Eq { left, right }
+        | Expression::Ne { left, right } => {
+            visitor.visit_term(left);
+            visitor.visit_term(right);
+        }
+        Expression::Term(term) => visitor.visit_term(term),
+    }
+}
+
+pub fn walk_term<V: Visitor>(visitor: &mut V, term: &Term) {
+    match term {
+        Term::Add { left, right } | Term::Subtract { left, right } | Term::Or { left, right } => {
+            visitor.visit_term(left);
+            visitor.visit_factor(right);
+        }
+        Term::Factor(factor) => visitor.visit_factor(factor),
+    }
+}
+
+pub fn walk_factor<V: Visitor>(visitor: &mut V, factor: &Factor) {
+    match factor {
+        Factor::Multiply { left, right }
+        | Factor::Divide { left, right }
+        | Factor::And { left, right }
+        | Factor::Mod { left, right } => {
+            visitor.visit_factor(left);
+            visitor.visit_primary(right);
+        }
+        Factor::Primary(primary) => visitor.visit_primary(primary),
+    }
+}
+
+pub fn walk_primary<V: Visitor>(visitor: &mut V, primary: &Primary) {
+    match primary {
+        Primary::Negate { primary } | Primary::Not { primary } => visitor.visit_primary(primary),
+        Primary::Name(name) => visitor.visit_name(name, NameUsage::Value),
+        Primary::Call { name, exps } => {
+            visitor.visit_name(name, NameUsage::Call);
+            for exp in exps {
+                visitor.visit_expression(exp);
+            }
+        }
+        Primary::Expression(exp) => visitor.visit_expression(exp),
+        Primary::Succ { exp } | Primary::Pred { exp } | Primary::Chr { exp } | Primary::Ord { exp } => {
+            visitor.visit_expression(exp)
+        }
+        Primary::Eof | Primary::Integer(_) | Primary::Char(_) => {}
+    }
+}
+
+/// Walks statements and expressions like `Visitor`, with mutable access to them.
+pub trait VisitorMut: Sized {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_term_mut(&mut self, term: &mut Term) {
+        walk_term_mut(self, term)
+    }
+
+    fn visit_factor_mut(&mut self, factor: &mut Factor) {
+        walk_factor_mut(self, factor)
+    }
+
+    fn visit_primary_mut(&mut self, primary: &mut Primary) {
+        walk_primary_mut(self, primary)
+    }
+
+    fn visit_name_mut(&mut self, _name: &mut Identifier, _usage: NameUsage) {}
+}
+
+pub fn walk_statements_mut<V: VisitorMut>(visitor: &mut V, statements: &mut [Statement]) {
+    for statement in statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Assign { assignment } => walk_assignment_mut(visitor, assignment),
+        Statement::Output { expressions } => {
+            for expression in expressions {
+                if let OutExp::Integer { exp } = expression {
+                    visitor.visit_expression_mut(exp);
+                }
+            }
+        }
+        Statement::If { cond, then, else_stmt } => {
+            visitor.visit_expression_mut(cond);
+            visitor.visit_statement_mut(then);
+            if let Some(else_stmt) = else_stmt {
+                visitor.visit_statement_mut(else_stmt);
+            }
+        }
+        Statement::While { cond, stmt } => {
+            visitor.visit_expression_mut(cond);
+            visitor.visit_statement_mut(stmt);
+        }
+        Statement::Repeat { stmts, cond } => {
+            walk_statements_mut(visitor, stmts);
+            visitor.visit_expression_mut(cond);
+        }
+        Statement::For { init, cond, update, stmt } => {
+            if let ForStat::Assignment(assignment) = init {
+                walk_assignment_mut(visitor, assignment);
+            }
+            if let ForExp::Expression(cond) = cond {
+                visitor.visit_expression_mut(cond);
+            }
+            visitor.visit_statement_mut(stmt);
+            if let ForStat::Assignment(assignment) = update {
+                walk_assignment_mut(visitor, assignment);
+            }
+        }
+        Statement::Loop { stmts } => walk_statements_mut(visitor, stmts),
+        Statement::Case { expr, cases, otherwise } => {
+            visitor.visit_expression_mut(expr);
+            for clause in cases {
+                for label in &mut clause.labels {
+                    let values = match &mut label.expression {
+                        CaseExpression::Value(value) => vec![value],
+                        CaseExpression::Range(low, high) => vec![low, high],
+                    };
+                    for value in values {
+                        if let ConstValue::Name(name) = value {
+                            visitor.visit_name_mut(name, NameUsage::Label);
+                        }
+                    }
+                }
+                visitor.visit_statement_mut(&mut clause.statement);
+            }
+            if let Some(otherwise) = otherwise {
+                visitor.visit_statement_mut(&mut otherwise.stmt);
+            }
+        }
+        Statement::Read { names } => {
+            for name in names {
+                visitor.visit_name_mut(name, NameUsage::Read);
+            }
+        }
+        Statement::Return { exp, .. } => visitor.visit_expression_mut(exp),
+        Statement::Body { body } => walk_statements_mut(visitor, &mut body.statements),
+        Statement::Exit { .. } | Statement::Null => {}
+    }
+}
+
+pub fn walk_assignment_mut<V: VisitorMut>(visitor: &mut V, assignment: &mut Assignment) {
+    match assignment {
+        Assignment::Assignment { name, exp } => {
+            visitor.visit_expression_mut(exp);
+            visitor.visit_name_mut(name, NameUsage::Assign);
+        }
+        Assignment::Swap { name1, name2 } => {
+            visitor.visit_name_mut(name1, NameUsage::Swap);
+            visitor.visit_name_mut(name2, NameUsage::Swap);
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Le { left, right }
+        | Expression::Lt { left, right }
+        | Expression::Ge { left, right }
+        | Expression::Gt { left, right }
+        | Expression::Eq { left, right }
+        | Expression::Ne { left, right } => {
+            visitor.visit_term_mut(left);
+            visitor.visit_term_mut(right);
+        }
+        Expression::Term(term) => visitor.visit_term_mut(term),
+    }
+}
+
+pub fn walk_term_mut<V: VisitorMut>(visitor: &mut V, term: &mut Term) {
+    match term {
+        Term::Add { left, right } | Term::Subtract { left, right } | Term::Or { left, right } => {
+            visitor.visit_term_mut(left);
+            visitor.visit_factor_mut(right);
+        }
+        Term::Factor(factor) => visitor.visit_factor_mut(factor),
+    }
+}
+
+pub fn walk_factor_mut<V: VisitorMut>(visitor: &mut V, factor: &mut Factor) {
+    match factor {
+        Factor::Multiply { left, right }
+        | Factor::Divide { left, right }
+        | Factor::And { left, right }
+        | Factor::Mod { left, right } => {
+            visitor.visit_factor_mut(left);
+            visitor.visit_primary_mut(right);
+        }
+        Factor::Primary(primary) => visitor.visit_primary_mut(primary),
+    }
+}
+
+pub fn walk_primary_mut<V: VisitorMut>(visitor: &mut V, primary: &mut Primary) {
+    match primary {
+        Primary::Negate { primary } | Primary::Not { primary } => visitor.visit_primary_mut(primary),
+        Primary::Name(name) => visitor.visit_name_mut(name, NameUsage::Value),
+        Primary::Call { name, exps } => {
+            visitor.visit_name_mut(name, NameUsage::Call);
+            for exp in exps {
+                visitor.visit_expression_mut(exp);
+            }
+        }
+        Primary::Expression(exp) => visitor.visit_expression_mut(exp),
+        Primary::Succ { exp } | Primary::Pred { exp } | Primary::Chr { exp } | Primary::Ord { exp } => {
+            visitor.visit_expression_mut(exp)
+        }
+        Primary::Eof | Primary::Integer(_) | Primary::Char(_) => {}
+    }
+}
@@ -0,0 +1,328 @@
+use std::io::Write;
+
+use crate::check::types::expression_type;
+use crate::check::{check, CheckOptions};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::interpreter::{Interpreter, Io, StdIo, Value};
+use crate::lexer::printer::token_listing;
+use crate::lexer::{Keyword, Lexer, LexerConfig, SpannedToken, Token};
+use crate::parser::tree::{char_literal, StringTree};
+use crate::parser::{
+    Body, Consts, Declarations, Dclns, Expression, Func, Identifier, OutExp, ParseResult, Parser, Program, Statement,
+    SubProgs, Types,
+};
+use crate::semantic::SemanticModel;
+
+mod test;
+
+const HELP: &str = "\
+Enter declarations (var, const, type, function), statements or expressions.
+Input continues on the next line while it is incomplete; an empty line ends it.
+  :ast <expr>      print the syntax tree of an expression
+  :tokens <text>   print the tokens of some text
+  :type <expr>     print the type of an expression without evaluating it
+  :help            print this help
+  :quit            leave the REPL";
+
+/// What a piece of input was read as.
+enum Input {
+    Declarations(Declarations),
+    Function(Box<Func>),
+    Statements(Vec<Statement>),
+    Expression(Expression),
+}
+
+/// Read-eval-print loop state: the environment built up so far and any unfinished input.
+///
+/// Input is checked before it runs, together with the declarations and functions entered
+/// before it, as the body of a program that declares them all.
+pub struct Repl<I: Io = StdIo> {
+    interpreter: Interpreter<I>,
+    config: LexerConfig,
+    // the tokens of the declarations and functions entered so far
+    entries: Vec<Vec<SpannedToken>>,
+    // where the next input starts; the input is numbered on from the earlier input, as the
+    // semantic model tells names apart by their positions
+    offset: usize,
+    pending: String,
+    finished: bool,
+}
+
+impl<I: Io> Repl<I> {
+    pub fn new(io: I, config: LexerConfig) -> Self {
+        Self {
+            interpreter: Interpreter::with_word_size(io, config.word_size),
+            config,
+            entries: Vec::new(),
+            // 0 is the position of the program's empty name, see `session`
+            offset: 1,
+            pending: String::new(),
+            finished: false,
+        }
+    }
+
+    pub fn interpreter(&self) -> &Interpreter<I> {
+        &self.interpreter
+    }
+
+    /// True while the previous lines were an incomplete declaration or statement.
+    pub fn is_continuing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// True once `:quit` was entered.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Handles one line of input, returning the lines to print in response.
+    pub fn eval_line(&mut self, line: &str) -> Vec<String> {
+        if !self.is_continuing() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                return self.command(command);
+            }
+            if line.trim().is_empty() {
+                return Vec::new();
+            }
+        }
+
+        let force = self.is_continuing() && line.trim().is_empty();
+        self.pending.push_str(line);
+        self.pending.push('\n');
+        let source = self.pending.clone();
+
+        let (tokens, mut output) = match self.tokenize(&source) {
+            Ok(lexed) => lexed,
+            Err(errors) => {
+                self.pending.clear();
+                return errors;
+            }
+        };
+        match self.submit(tokens) {
+            // wait for the rest of the input
+            Err(diagnostic) if !force && diagnostic.span.start >= self.offset + source.trim_end().len() => {
+                return Vec::new()
+            }
+            Ok(lines) => output.extend(lines),
+            Err(diagnostic) => output.push(render(&diagnostic)),
+        }
+        self.offset += source.len();
+        self.pending.clear();
+        output
+    }
+
+    /// Lexes `source` as the next input, returning its tokens and any warnings, or the errors
+    /// if there were any.
+    fn tokenize(&self, source: &str) -> Result<(Vec<SpannedToken>, Vec<String>), Vec<String>> {
+        let mut lexer = Lexer::with_config(source.to_string(), self.config.clone());
+        let mut tokens = lexer.tokenize();
+        let messages = lexer.diagnostics().iter().map(render).collect();
+        if lexer.diagnostics().iter().any(|d| d.is_error()) {
+            return Err(messages);
+        }
+        for token in &mut tokens {
+            token.span.start += self.offset;
+            token.span.end += self.offset;
+        }
+        Ok((tokens, messages))
+    }
+
+    /// Checks the input, then runs it, returning the warnings and the value of an expression.
+    fn submit(&mut self, tokens: Vec<SpannedToken>) -> Result<Vec<String>, Diagnostic> {
+        let input = self.read(tokens.clone())?;
+        let (_, warnings) = self.check(self.read(tokens.clone())?)?;
+        let mut output: Vec<String> = warnings.iter().map(render).collect();
+        match input {
+            Input::Declarations(declarations) => self.interpreter.declare(&declarations),
+            Input::Function(func) => self.interpreter.define_function(*func),
+            Input::Statements(statements) => {
+                self.interpreter.execute(&statements)?;
+                return Ok(output);
+            }
+            Input::Expression(expression) => {
+                let value = self.interpreter.evaluate(&expression)?;
+                output.push(format!("{} : {}", show(&value), value.type_of()));
+                return Ok(output);
+            }
+        }
+        self.entries.push(tokens);
+        Ok(output)
+    }
+
+    /// Parses the input as declarations or a function by its first keyword, or else as an
+    /// expression or else as statements.
+    fn read(&self, tokens: Vec<SpannedToken>) -> ParseResult<Input> {
+        let first = tokens.iter().map(|t| &t.token).find(|t| !t.is_trivia());
+        match first {
+            Some(Token::Keyword(Keyword::Const | Keyword::Type | Keyword::Var)) => {
+                self.parse(tokens, Parser::parse_declarations).map(Input::Declarations)
+            }
+            Some(Token::Keyword(Keyword::Function)) => {
+                self.parse(tokens, Parser::parse_function).map(|func| Input::Function(Box::new(func)))
+            }
+            _ => {
+                let expression_error = match self.parse(tokens.clone(), Parser::parse_expression) {
+                    Ok(expression) => return Ok(Input::Expression(expression)),
+                    Err(error) => error,
+                };
+                match self.parse(tokens, Parser::parse_statements) {
+                    Ok(statements) => Ok(Input::Statements(statements)),
+                    // report whichever reading of the input got further
+                    Err(error) if error.span.start >= expression_error.span.start => Err(error),
+                    Err(_) => Err(expression_error),
+                }
+            }
+        }
+    }
+
+    /// Checks `input` as the rest of the session, returning the semantic model and the
+    /// warnings about `input`, or the first error.
+    fn check(&self, input: Input) -> Result<(SemanticModel, Vec<Diagnostic>), Diagnostic> {
+        let program = self.session(input);
+        let model = SemanticModel::build(&program);
+        let mut diagnostics = model.diagnostics().to_vec();
+        diagnostics.extend(check(&program, &model, &CheckOptions::default()));
+        diagnostics.sort_by_key(|d| d.span.start);
+        if let Some(error) = diagnostics.iter().find(|d| d.is_error()) {
+            return Err(error.clone());
+        }
+        diagnostics.retain(|d| d.span.start >= self.offset);
+        Ok((model, diagnostics))
+    }
+
+    /// The declarations and functions entered so far, then `input`, as a program with an
+    /// empty name. Declaring a name again replaces the earlier declaration, as it does when
+    /// running.
+    fn session(&self, input: Input) -> Program {
+        let unnamed = || Identifier { name: String::new(), key: String::new(), span: Span::default() };
+        let mut program = Program {
+            doc: None,
+            name: unnamed(),
+            consts: Consts { consts: Vec::new() },
+            types: Types { types: Vec::new() },
+            dclns: Dclns { vars: Vec::new() },
+            sub_progs: SubProgs { sub_progs: Vec::new() },
+            body: Body { statements: Vec::new() },
+            end_name: unnamed(),
+        };
+        // the entries parsed when they were entered
+        let entries = self.entries.iter().filter_map(|tokens| self.read(tokens.clone()).ok());
+        for input in entries.chain(std::iter::once(input)) {
+            match input {
+                Input::Declarations(declarations) => {
+                    let Declarations { consts, types, dclns } = declarations;
+                    let mut names: Vec<&Identifier> = consts.consts.iter().map(|c| &c.name).collect();
+                    for t in &types.types {
+                        names.push(&t.name);
+                        names.extend(&t.lit_list.names);
+                    }
+                    names.extend(dclns.vars.iter().flat_map(|v| &v.names));
+                    for name in names {
+                        forget(&mut program, &name.key);
+                    }
+                    program.consts.consts.extend(consts.consts);
+                    program.types.types.extend(types.types);
+                    program.dclns.vars.extend(dclns.vars);
+                }
+                Input::Function(func) => {
+                    forget(&mut program, &func.name.key);
+                    program.sub_progs.sub_progs.push(*func);
+                }
+                Input::Statements(statements) => program.body.statements = statements,
+                Input::Expression(exp) => {
+                    program.body.statements = vec![Statement::Output { expressions: vec![OutExp::Integer { exp }] }]
+                }
+            }
+        }
+        program
+    }
+
+    fn command(&mut self, command: &str) -> Vec<String> {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let result = match name {
+            "ast" => self.parse_expression(argument).map(|e| e.get_string_tree(0)),
+            "tokens" => self
+                .tokenize(argument)
+                .map(|(tokens, warnings)| warnings.into_iter().chain(token_listing(&tokens, false)).collect()),
+            "type" => {
+                let (tokens, _) = match self.tokenize(argument) {
+                    Ok(lexed) => lexed,
+                    Err(errors) => return errors,
+                };
+                self.type_of(tokens).map_err(|d| vec![render(&d)])
+            }
+            "help" => Ok(HELP.lines().map(|l| l.to_string()).collect()),
+            "quit" | "q" => {
+                self.finished = true;
+                Ok(Vec::new())
+            }
+            _ => Err(vec![format!("error: unknown command ':{}', try :help", name)]),
+        };
+        result.unwrap_or_else(|errors| errors)
+    }
+
+    fn parse_expression(&self, text: &str) -> Result<Expression, Vec<String>> {
+        let (tokens, _) = self.tokenize(text)?;
+        self.parse(tokens, Parser::parse_expression).map_err(|d| vec![render(&d)])
+    }
+
+    /// The type of an expression, worked out by checking it rather than evaluating it.
+    fn type_of(&self, tokens: Vec<SpannedToken>) -> Result<Vec<String>, Diagnostic> {
+        let expression = self.parse(tokens.clone(), Parser::parse_expression)?;
+        let (model, _) = self.check(Input::Expression(self.parse(tokens, Parser::parse_expression)?))?;
+        Ok(expression_type(&model, &expression).map(|ty| model.symbol(ty).name.clone()).into_iter().collect())
+    }
+
+    /// Parses `tokens` with `parse`, failing on the first error, including those the parse
+    /// goes on after such as integer literals out of range.
+    fn parse<T>(&self, tokens: Vec<SpannedToken>, parse: impl FnOnce(&mut Parser) -> ParseResult<T>) -> ParseResult<T> {
+        let mut parser = Parser::with_spans(tokens, false).with_word_size(self.config.word_size);
+        let result = parse(&mut parser)?;
+        match parser.diagnostics().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(result),
+        }
+    }
+}
+
+/// Removes the declaration of the name with the key `key` from `program`, if it has one.
+fn forget(program: &mut Program, key: &str) {
+    program.consts.consts.retain(|c| c.name.key != key);
+    program.types.types.retain(|t| t.name.key != key && t.lit_list.names.iter().all(|l| l.key != key));
+    for var in &mut program.dclns.vars {
+        var.names.retain(|n| n.key != key);
+    }
+    program.dclns.vars.retain(|v| !v.names.is_empty());
+    program.sub_progs.sub_progs.retain(|f| f.name.key != key);
+}
+
+/// Formats a value for display as a result, with characters quoted as in source.
+fn show(value: &Value) -> String {
+    match value {
+        Value::Char(c) => char_literal(*c),
+        value => value.to_string(),
+    }
+}
+
+// REPL input is short, so positions add little.
+fn render(diagnostic: &Diagnostic) -> String {
+    format!("{}: {}", diagnostic.severity, diagnostic.message)
+}
+
+/// Runs an interactive session on standard input and output.
+pub fn run(config: LexerConfig) {
+    let mut repl = Repl::new(StdIo, config);
+    let stdin = std::io::stdin();
+    while !repl.is_finished() {
+        print!("{}", if repl.is_continuing() { "   ...> " } else { "winzig> " });
+        std::io::stdout().flush().ok();
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        for output in repl.eval_line(line.trim_end_matches(['\r', '\n'])) {
+            println!("{}", output);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::BufferIo;
+    use crate::lexer::{LexerConfig, WordSize};
+    use crate::repl::Repl;
+
+    fn repl() -> Repl<BufferIo> {
+        Repl::new(BufferIo::default(), LexerConfig::default())
+    }
+
+    #[test]
+    fn test_environment() {
+        let mut repl = repl();
+        assert!(repl.eval_line("var a, b : integer;").is_empty());
+        assert!(repl.eval_line("type dir = (north, south);").is_empty());
+        assert!(repl.eval_line("a := 6; b := a * 7").is_empty());
+        assert_eq!(repl.eval_line("b"), ["42 : integer"]);
+        assert_eq!(repl.eval_line("succ(north)"), ["south : dir"]);
+        assert_eq!(repl.eval_line("chr(b + 23)"), ["'A' : char"]);
+        assert!(repl.eval_line("output(\"hi\", b)").is_empty());
+        assert_eq!(repl.interpreter().io().output, ["hi", "42"]);
+    }
+
+    #[test]
+    fn test_multi_line_function() {
+        let mut repl = repl();
+        assert!(repl.eval_line("function double(n : integer) : integer;").is_empty());
+        assert!(repl.is_continuing());
+        assert!(repl.eval_line("begin return (2 * n)").is_empty());
+        assert!(repl.eval_line("end double;").is_empty());
+        assert!(!repl.is_continuing());
+        assert_eq!(repl.eval_line("double(21)"), ["42 : integer"]);
+
+        // an empty line gives up on incomplete input
+        assert!(repl.eval_line("begin output(1);").is_empty());
+        assert_eq!(repl.eval_line(""), ["error: Expected 'end', found end of input"]);
+        assert!(!repl.is_continuing());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut repl = repl();
+        assert_eq!(repl.eval_line("x := 1"), ["error: undeclared identifier 'x'"]);
+        assert_eq!(repl.eval_line("1 + )"), ["error: Expected primary, found ')'"]);
+        assert_eq!(repl.eval_line("1 @ 2"), ["error: unexpected character '@'"]);
+        assert_eq!(repl.eval_line(":frobnicate"), ["error: unknown command ':frobnicate', try :help"]);
+    }
+
+    #[test]
+    fn test_checked_before_running() {
+        let mut repl = repl();
+        repl.eval_line("var n : integer; c : char;");
+        // nothing runs when any of the input is in error
+        assert_eq!(repl.eval_line("output(1); n := c"), ["error: cannot assign char to 'n' of type integer"]);
+        assert_eq!(repl.eval_line("n :=: c"), ["error: cannot swap 'n' of type integer with 'c' of type char"]);
+        assert_eq!(repl.eval_line("exit"), ["error: 'exit' outside a loop"]);
+        assert!(repl.interpreter().io().output.is_empty());
+
+        // functions are checked with what was declared before them, and calls with the functions
+        assert!(repl.eval_line("function f(n : integer) : char; begin return (chr(n)) end f;").is_empty());
+        assert_eq!(repl.eval_line("f('a')"), ["error: argument 'n' of 'f' expects integer, found char"]);
+        assert_eq!(repl.eval_line("f(1, 2)"), ["error: function 'f' takes 1 argument but 2 were supplied"]);
+        assert_eq!(repl.eval_line(":type f(65)"), ["char"]);
+        assert_eq!(repl.eval_line(":type (ord(f(1)) < 3) or eof"), ["boolean"]);
+        assert_eq!(repl.eval_line(":type g(1)"), ["error: undeclared function 'g'"]);
+
+        // declaring a name again replaces it, and what uses it is checked again
+        assert!(repl.eval_line("var c : integer;").is_empty());
+        assert!(repl.eval_line("function g(k : integer) : integer; begin return (k + c) end g;").is_empty());
+        assert_eq!(repl.eval_line("g(2)"), ["2 : integer"]);
+        assert_eq!(repl.eval_line("var c : char;"), ["error: arithmetic expects integer, found char"]);
+        assert!(repl.eval_line("function f(n : integer) : integer; begin return (n) end f;").is_empty());
+        assert_eq!(repl.eval_line("f(2)"), ["2 : integer"]);
+    }
+
+    #[test]
+    fn test_word_size() {
+        let mut repl = Repl::new(BufferIo::default(), LexerConfig { word_size: WordSize::W16, ..Default::default() });
+        assert_eq!(repl.eval_line("-32768"), ["-32768 : integer"]);
+        assert_eq!(repl.eval_line("16384 * 2"), ["error: integer overflow for 16-bit word"]);
+        assert_eq!(repl.eval_line("40000"), ["error: integer literal out of range for 16-bit word: 40000"]);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut repl = repl();
+        assert_eq!(repl.eval_line(":ast 1 + 2"), ["+(2)", ". <integer>(1)", ". . 1(0)", ". <integer>(1)", ". . 2(0)"]);
+        assert_eq!(repl.eval_line(":tokens a:=1"), [
+            "1:1     <identifier>    a",
+            "1:2     :=              :=",
+            "1:4     <integer>       1",
+        ]);
+        repl.eval_line("var c : char;");
+        assert_eq!(repl.eval_line(":type ord(c) > 2"), ["boolean"]);
+        assert!(!repl.is_finished());
+        repl.eval_line(":quit");
+        assert!(repl.is_finished());
+    }
+}
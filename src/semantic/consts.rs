@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::tree::char_literal;
+use crate::parser::Identifier;
+
+use super::{SemanticModel, SymbolId, SymbolKind, CHAR, INTEGER};
+
+/// The value of a constant or enumeration literal, known before the program runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstantValue {
+    Integer(i64),
+    Char(char),
+    /// A literal by its position in its type: in `(red, green)`, `green` is 1. `false`
+    /// and `true` are the literals of `boolean`.
+    Literal { ty: SymbolId, ordinal: usize },
+}
+
+impl ConstantValue {
+    /// The type of the value, as the symbol of a type.
+    pub fn type_id(&self) -> SymbolId {
+        match self {
+            ConstantValue::Integer(_) => INTEGER,
+            ConstantValue::Char(_) => CHAR,
+            ConstantValue::Literal { ty, .. } => *ty,
+        }
+    }
+
+    /// Position of the value in its type, which orders values of the same type.
+    pub fn ordinal(&self) -> i64 {
+        match self {
+            ConstantValue::Integer(i) => *i,
+            ConstantValue::Char(c) => *c as i64,
+            ConstantValue::Literal { ordinal, .. } => *ordinal as i64,
+        }
+    }
+}
+
+/// What a constant is declared as: `const a = 1` or `const b = a`.
+pub(super) enum Definition {
+    Value(ConstantValue),
+    Name { symbol: SymbolId, name: String, span: Span },
+}
+
+impl SemanticModel {
+    /// The value of a constant or enumeration literal. `None` for other symbols, and for
+    /// constants whose definition is in error.
+    pub fn const_value(&self, symbol: SymbolId) -> Option<ConstantValue> {
+        self.values.get(&symbol).copied()
+    }
+
+    /// The value of the constant or literal that `name` declares or refers to.
+    pub fn const_value_of(&self, name: &Identifier) -> Option<ConstantValue> {
+        self.symbol_at(name.span).and_then(|s| self.const_value(s))
+    }
+
+    /// A value as it would be written, e.g. `3`, `'a'` or `green`.
+    pub fn value_text(&self, value: ConstantValue) -> String {
+        match value {
+            ConstantValue::Integer(i) => i.to_string(),
+            ConstantValue::Char(c) => char_literal(c),
+            ConstantValue::Literal { ty, ordinal } => match self.literals_of(ty).get(ordinal) {
+                Some(literal) => self.symbols[*literal].name.clone(),
+                None => format!("{}({})", self.symbols[ty].name, ordinal),
+            },
+        }
+    }
+
+    /// Follows constants defined as other constants to their values.
+    pub(super) fn evaluate_constants(&mut self, definitions: HashMap<SymbolId, Definition>) {
+        let mut constants: Vec<SymbolId> = definitions.keys().copied().collect();
+        constants.sort();
+        let mut evaluator = Evaluator { model: self, definitions: &definitions };
+        for constant in constants {
+            evaluator.evaluate(constant);
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    model: &'a mut SemanticModel,
+    definitions: &'a HashMap<SymbolId, Definition>,
+}
+
+impl Evaluator<'_> {
+    // Names are declared before they are used, so a constant is only defined as one declared
+    // before it, and no constant is ever defined in terms of itself.
+    fn evaluate(&mut self, constant: SymbolId) -> Option<ConstantValue> {
+        if let Some(value) = self.model.const_value(constant) {
+            return Some(value);
+        }
+        let value = match self.definitions.get(&constant)? {
+            Definition::Value(value) => Some(*value),
+            Definition::Name { symbol, name, span } => match self.model.symbols[*symbol].kind {
+                SymbolKind::Constant => self.evaluate(*symbol),
+                SymbolKind::EnumLiteral => self.model.const_value(*symbol),
+                _ => {
+                    let message = format!("'{}' is not a constant", name);
+                    self.model.diagnostics.push(Diagnostic::error(message, *span));
+                    None
+                }
+            },
+        };
+        if let Some(value) = value {
+            self.model.values.insert(constant, value);
+        }
+        value
+    }
+}
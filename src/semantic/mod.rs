@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use crate::diagnostic::{Diagnostic, Span};
+use crate::parser::tree::char_literal;
+use crate::parser::visit::{walk_statements, NameUsage, Visitor};
+use crate::parser::*;
+
+mod consts;
+mod test;
+
+pub use consts::ConstantValue;
+use consts::Definition;
+
+pub type SymbolId = usize;
+
+/// The predeclared types, the first symbols of every model.
+pub const INTEGER: SymbolId = 0;
+pub const CHAR: SymbolId = 1;
+pub const BOOLEAN: SymbolId = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Program,
+    Function,
+    Parameter,
+    Variable,
+    Constant,
+    EnumLiteral,
+    Type,
+}
+
+/// Where a symbol is declared: globally, or inside the function with that index in `Program::functions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeId {
+    Global,
+    Function(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    /// The name as compared with others, see `Identifier::key`.
+    pub key: String,
+    pub kind: SymbolKind,
+    pub scope: ScopeId,
+    /// Span of the declaring identifier, `None` for predeclared names such as `integer`.
+    pub span: Option<Span>,
+    /// Declared type of variables, parameters and literals, and the return type of functions.
+    pub type_name: Option<String>,
+    /// The declaration as it would be written, e.g. `function f(n : integer) : integer`.
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub span: Span,
+    pub symbol: SymbolId,
+    /// The scope the name was looked up in.
+    pub scope: ScopeId,
+    pub is_declaration: bool,
+}
+
+/// A use of a name that isn't declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unresolved {
+    pub name: String,
+    pub key: String,
+    pub span: Span,
+    pub scope: ScopeId,
+}
+
+/// Names resolved to their declarations, for a program that parsed successfully.
+pub struct SemanticModel {
+    symbols: Vec<Symbol>,
+    // sorted by position
+    references: Vec<Reference>,
+    scopes: HashMap<ScopeId, HashMap<String, SymbolId>>,
+    // function symbols in definition order, so `ScopeId::Function(i)` belongs to `functions[i]`
+    functions: Vec<SymbolId>,
+    // the type of each symbol that has one, see `type_of`
+    types: HashMap<SymbolId, SymbolId>,
+    // constants and literals, see `const_value`
+    values: HashMap<SymbolId, ConstantValue>,
+    unresolved: Vec<Unresolved>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl SemanticModel {
+    pub fn build(program: &Program) -> Self {
+        let mut model = SemanticModel {
+            symbols: Vec::new(),
+            references: Vec::new(),
+            scopes: HashMap::new(),
+            functions: Vec::new(),
+            types: HashMap::new(),
+            values: HashMap::new(),
+            unresolved: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        model.predeclare();
+        let mut resolver = Resolver { model: &mut model, scope: ScopeId::Global, definitions: HashMap::new() };
+        resolver.program(program);
+        let definitions = resolver.definitions;
+        model.evaluate_constants(definitions);
+        model.references.sort_by_key(|r| r.span.start);
+        model.diagnostics.sort_by_key(|d| d.span.start);
+        model
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id]
+    }
+
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// Undeclared and duplicate names, and `end` names that don't match.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+
+    /// The type of a variable, parameter, constant or literal, or the return type of a
+    /// function. `None` for other symbols, and when the type name is undeclared or not a type.
+    pub fn type_of(&self, symbol: SymbolId) -> Option<SymbolId> {
+        self.types.get(&symbol).copied()
+    }
+
+    /// The symbol that the identifier at `span` declares or refers to.
+    pub fn symbol_at(&self, span: Span) -> Option<SymbolId> {
+        let index = self.references.partition_point(|r| r.span.start < span.start);
+        self.references.get(index).filter(|r| r.span == span).map(|r| r.symbol)
+    }
+
+    /// The literals of an enumeration type, in declaration order.
+    pub fn literals_of(&self, ty: SymbolId) -> Vec<SymbolId> {
+        let is_literal = |s: &SymbolId| self.symbols[*s].kind == SymbolKind::EnumLiteral;
+        let literals = self.types.iter().filter(|(s, t)| **t == ty && is_literal(s));
+        let mut literals: Vec<SymbolId> = literals.map(|(s, _)| *s).collect();
+        literals.sort();
+        literals
+    }
+
+    /// The reference whose identifier contains `offset`, or ends exactly at it.
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        let index = self.references.partition_point(|r| r.span.end < offset);
+        self.references.get(index).filter(|r| r.span.start <= offset)
+    }
+
+    pub fn references_to(&self, symbol: SymbolId) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| r.symbol == symbol)
+    }
+
+    /// Resolves the name with the key `key` as seen from `scope`: locals first, then globals.
+    pub fn lookup(&self, scope: ScopeId, key: &str) -> Option<SymbolId> {
+        let local = self.scopes.get(&scope).and_then(|s| s.get(key));
+        local.or_else(|| self.scopes.get(&ScopeId::Global).and_then(|s| s.get(key))).copied()
+    }
+
+    /// The symbol with the key `key` declared directly in `scope`, ignoring enclosing scopes.
+    pub fn declared_in(&self, scope: ScopeId, key: &str) -> Option<SymbolId> {
+        self.scopes.get(&scope).and_then(|s| s.get(key)).copied()
+    }
+
+    /// The function whose body is `scope`.
+    pub fn function_of(&self, scope: ScopeId) -> Option<SymbolId> {
+        match scope {
+            ScopeId::Global => None,
+            ScopeId::Function(index) => self.functions.get(index).copied(),
+        }
+    }
+
+    /// Scope of the body of the function with the key `key`.
+    pub fn function_scope(&self, key: &str) -> Option<ScopeId> {
+        let index = self.functions.iter().position(|id| self.symbols[*id].key == key)?;
+        Some(ScopeId::Function(index))
+    }
+
+    /// Symbols visible from `scope`, locals hiding globals of the same name.
+    pub fn visible_symbols(&self, scope: ScopeId) -> Vec<SymbolId> {
+        let mut visible: Vec<SymbolId> = Vec::new();
+        for id in self.scopes.get(&scope).into_iter().chain(self.scopes.get(&ScopeId::Global)).flat_map(|s| s.values()) {
+            if !visible.iter().any(|v| self.symbols[*v].key == self.symbols[*id].key) {
+                visible.push(*id);
+            }
+        }
+        visible.sort();
+        visible
+    }
+
+    /// Symbols declared directly in `scope`.
+    pub fn symbols_in(&self, scope: ScopeId) -> impl Iterator<Item = (SymbolId, &Symbol)> {
+        self.symbols.iter().enumerate().filter(move |(_, s)| s.scope == scope)
+    }
+
+    fn predeclare(&mut self) {
+        let predeclared = |name: &str, kind, type_name: Option<&str>, detail| Symbol {
+            name: name.to_string(),
+            key: name.to_string(),
+            kind,
+            scope: ScopeId::Global,
+            span: None,
+            type_name: type_name.map(|t| t.to_string()),
+            detail,
+        };
+        for name in ["integer", "char", "boolean"] {
+            self.declare(predeclared(name, SymbolKind::Type, None, format!("type {}", name)));
+        }
+        for (ordinal, name) in ["false", "true"].into_iter().enumerate() {
+            let detail = format!("{} : boolean", name);
+            let id = self.declare(predeclared(name, SymbolKind::EnumLiteral, Some("boolean"), detail));
+            self.types.insert(id, BOOLEAN);
+            self.values.insert(id, ConstantValue::Literal { ty: BOOLEAN, ordinal });
+        }
+    }
+
+    fn declare(&mut self, symbol: Symbol) -> SymbolId {
+        let id = self.symbols.len();
+        if let Some(span) = symbol.span {
+            self.references.push(Reference { span, symbol: id, scope: symbol.scope, is_declaration: true });
+        }
+        let names = self.scopes.entry(symbol.scope).or_default();
+        if names.contains_key(&symbol.key) {
+            let message = format!("'{}' is already declared in this scope", symbol.name);
+            self.diagnostics.push(Diagnostic::error(message, symbol.span.unwrap_or_default()));
+        } else {
+            names.insert(symbol.key.clone(), id);
+        }
+        self.symbols.push(symbol);
+        id
+    }
+}
+
+/// `function f(a, b : integer; c : char) : integer`
+pub fn function_signature(func: &Func) -> String {
+    let params: Vec<String> = func
+        .params
+        .params
+        .iter()
+        .map(|var| format!("{} : {}", names(&var.names), var.typename.name))
+        .collect();
+    format!("function {}({}) : {}", func.name.name, params.join("; "), func.return_type.name)
+}
+
+fn names(names: &[Identifier]) -> String {
+    names.iter().map(|n| n.name.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+fn const_value_text(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Integer(i) => i.to_string(),
+        ConstValue::Char(c) => char_literal(*c),
+        ConstValue::Name(name) => name.name.clone(),
+    }
+}
+
+struct Resolver<'a> {
+    model: &'a mut SemanticModel,
+    scope: ScopeId,
+    definitions: HashMap<SymbolId, Definition>,
+}
+
+impl Resolver<'_> {
+    fn program(&mut self, program: &Program) {
+        let name = &program.name;
+        let id = self.model.symbols.len();
+        self.model.symbols.push(Symbol {
+            name: name.name.clone(),
+            key: name.key.clone(),
+            kind: SymbolKind::Program,
+            scope: ScopeId::Global,
+            span: Some(name.span),
+            type_name: None,
+            detail: format!("program {}", name.name),
+        });
+        let scope = ScopeId::Global;
+        self.model.references.push(Reference { span: name.span, symbol: id, scope, is_declaration: true });
+        self.end_name(id, "program", &program.end_name);
+
+        self.declarations(&program.consts, &program.types, &program.dclns);
+
+        // functions may be called before they are defined
+        let mut function_ids = Vec::new();
+        for func in program.functions() {
+            let detail = function_signature(func);
+            let id = self.declare(&func.name, SymbolKind::Function, Some(&func.return_type.name), detail);
+            self.set_type(id, self.model.lookup(ScopeId::Global, &func.return_type.key));
+            function_ids.push(id);
+        }
+        self.model.functions = function_ids.clone();
+        for (index, (func, id)) in program.functions().iter().zip(function_ids).enumerate() {
+            self.scope = ScopeId::Function(index);
+            self.function(func, id);
+        }
+
+        self.scope = ScopeId::Global;
+        walk_statements(self, &program.body.statements);
+    }
+
+    fn function(&mut self, func: &Func, id: SymbolId) {
+        for var in &func.params.params {
+            let ty = self.use_name(&var.typename);
+            for name in &var.names {
+                let detail = format!("(parameter) {} : {}", name.name, var.typename.name);
+                let id = self.declare(name, SymbolKind::Parameter, Some(&var.typename.name), detail);
+                self.set_type(id, ty);
+            }
+        }
+        self.use_name_in(ScopeId::Global, &func.return_type);
+        self.declarations(&func.consts, &func.types, &func.dclns);
+        walk_statements(self, &func.body.statements);
+        self.end_name(id, "function", &func.end_name);
+    }
+
+    fn declarations(&mut self, consts: &Consts, types: &Types, dclns: &Dclns) {
+        for c in &consts.consts {
+            let (ty, definition) = match &c.value {
+                ConstValue::Integer(i) => (Some(INTEGER), Some(Definition::Value(ConstantValue::Integer(*i)))),
+                ConstValue::Char(ch) => (Some(CHAR), Some(Definition::Value(ConstantValue::Char(*ch)))),
+                ConstValue::Name(name) => match self.use_name(name) {
+                    Some(symbol) => {
+                        let definition = Definition::Name { symbol, name: name.name.clone(), span: name.span };
+                        (self.model.type_of(symbol), Some(definition))
+                    }
+                    None => (None, None),
+                },
+            };
+            let detail = format!("const {} = {}", c.name.name, const_value_text(&c.value));
+            let id = self.declare(&c.name, SymbolKind::Constant, None, detail);
+            self.set_type(id, ty);
+            if let Some(definition) = definition {
+                self.definitions.insert(id, definition);
+            }
+        }
+        for t in &types.types {
+            let detail = format!("type {} = ({})", t.name.name, names(&t.lit_list.names));
+            let ty = self.declare(&t.name, SymbolKind::Type, None, detail);
+            for (ordinal, literal) in t.lit_list.names.iter().enumerate() {
+                let detail = format!("{} : {}", literal.name, t.name.name);
+                let id = self.declare(literal, SymbolKind::EnumLiteral, Some(&t.name.name), detail);
+                self.set_type(id, Some(ty));
+                self.model.values.insert(id, ConstantValue::Literal { ty, ordinal });
+            }
+        }
+        for var in &dclns.vars {
+            let ty = self.use_name(&var.typename);
+            for name in &var.names {
+                let detail = format!("{} : {}", name.name, var.typename.name);
+                let id = self.declare(name, SymbolKind::Variable, Some(&var.typename.name), detail);
+                self.set_type(id, ty);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Identifier, kind: SymbolKind, type_name: Option<&str>, detail: String) -> SymbolId {
+        self.model.declare(Symbol {
+            name: name.name.clone(),
+            key: name.key.clone(),
+            kind,
+            scope: self.scope,
+            span: Some(name.span),
+            type_name: type_name.map(|t| t.to_string()),
+            detail,
+        })
+    }
+
+    // only types are types: `var x : y` with a variable `y` gives `x` no type
+    fn set_type(&mut self, symbol: SymbolId, ty: Option<SymbolId>) {
+        if let Some(ty) = ty.filter(|t| self.model.symbols[*t].kind == SymbolKind::Type) {
+            self.model.types.insert(symbol, ty);
+        }
+    }
+
+    fn use_name(&mut self, name: &Identifier) -> Option<SymbolId> {
+        self.use_name_in(self.scope, name)
+    }
+
+    fn use_name_in(&mut self, scope: ScopeId, name: &Identifier) -> Option<SymbolId> {
+        self.resolve(scope, name, "identifier")
+    }
+
+    // `what` names the kind of symbol expected when the name is undeclared
+    fn resolve(&mut self, scope: ScopeId, name: &Identifier, what: &str) -> Option<SymbolId> {
+        match self.model.lookup(scope, &name.key) {
+            Some(symbol) => {
+                self.model.references.push(Reference { span: name.span, symbol, scope, is_declaration: false });
+                Some(symbol)
+            }
+            None => {
+                let message = format!("undeclared {} '{}'", what, name.name);
+                self.model.diagnostics.push(Diagnostic::error(message, name.span));
+                let (name, key, span) = (name.name.clone(), name.key.clone(), name.span);
+                self.model.unresolved.push(Unresolved { name, key, span, scope });
+                None
+            }
+        }
+    }
+
+    fn end_name(&mut self, id: SymbolId, what: &str, end_name: &Identifier) {
+        let Symbol { name, key, scope, .. } = &self.model.symbols[id];
+        if *key == end_name.key {
+            let reference = Reference { span: end_name.span, symbol: id, scope: *scope, is_declaration: false };
+            self.model.references.push(reference);
+        } else {
+            let message = format!("{} '{}' ends with '{}'", what, name, end_name.name);
+            self.model.diagnostics.push(Diagnostic::error(message, end_name.span));
+        }
+    }
+}
+
+impl Visitor for Resolver<'_> {
+    fn visit_name(&mut self, name: &Identifier, usage: NameUsage) {
+        let what = if usage == NameUsage::Call { "function" } else { "identifier" };
+        self.resolve(self.scope, name, what);
+    }
+}
@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{Lexer, LexerConfig};
+    use crate::parser::{Parser, Program};
+    use crate::semantic::{ConstantValue, ScopeId, SemanticModel, SymbolKind, BOOLEAN};
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::new(source.to_string());
+        Parser::with_spans(lexer.tokenize(), false).parse().unwrap()
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let source = include_str!("./../../winzig_test_programs/winzig_12");
+        let model = SemanticModel::build(&parse(source));
+
+        let merge = model.lookup(ScopeId::Global, "Merge").unwrap();
+        assert_eq!(model.symbol(merge).kind, SymbolKind::Function);
+        assert_eq!(model.symbol(merge).detail, "function Merge(low, middle, high : integer) : integer");
+        // declaration, the call in MergeSort and 'end Merge'
+        let spans: Vec<&str> = model.references_to(merge).map(|r| &source[r.span.start..r.span.end]).collect();
+        assert_eq!(spans, ["Merge", "Merge", "Merge"]);
+        assert_eq!(model.references_to(merge).filter(|r| r.is_declaration).count(), 1);
+
+        // 'i' inside Merge is the local, not the global
+        let offset = source.find("i := 1;").unwrap();
+        let local = model.reference_at(offset).unwrap().symbol;
+        assert_eq!(model.symbol(local).scope, ScopeId::Function(6));
+        assert_ne!(Some(local), model.lookup(ScopeId::Global, "i"));
+
+        // results are discarded into a global the sample declares
+        assert!(model.diagnostics().is_empty());
+        let offset = source.find("d:=MergeSort").unwrap();
+        assert_eq!(model.reference_at(offset).unwrap().symbol, model.lookup(ScopeId::Global, "d").unwrap());
+    }
+
+    #[test]
+    fn test_undeclared_discard() {
+        // 'd' is a name like any other
+        let source = "program p: function f(n : integer) : integer; begin return (n) end f; begin d := f(1) end p.";
+        let model = SemanticModel::build(&parse(source));
+        let messages: Vec<String> = model.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, ["1:77: error: undeclared identifier 'd'"]);
+    }
+
+    #[test]
+    fn test_local_types_shadow_globals() {
+        let source = include_str!("./../../winzig_test_programs/winzig_11");
+        let model = SemanticModel::build(&parse(source));
+
+        let offset = source.find("time : Result").unwrap() + "time : ".len();
+        let result = model.reference_at(offset).unwrap().symbol;
+        assert_eq!(model.symbol(result).kind, SymbolKind::Type);
+        assert_eq!(model.symbol(result).detail, "type Result = (Red, White, Blue)");
+        assert_eq!(model.symbol(result).scope, ScopeId::Function(1));
+
+        let red = model.lookup(ScopeId::Function(1), "Red").unwrap();
+        assert_eq!(model.symbol(red).type_name.as_deref(), Some("Result"));
+        assert_eq!(model.type_of(red), Some(result));
+        let literals: Vec<&str> = model.literals_of(result).iter().map(|l| model.symbol(*l).name.as_str()).collect();
+        assert_eq!(literals, ["Red", "White", "Blue"]);
+        assert_eq!(model.lookup(ScopeId::Global, "Red"), None);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let source = "program p:\n\
+                      var a, a : integer;\n\
+                      function f(n : integer) : integer;\nbegin return (n) end g;\n\
+                      begin output(b) end q.";
+        let model = SemanticModel::build(&parse(source));
+        let messages: Vec<String> = model.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, [
+            "2:8: error: 'a' is already declared in this scope",
+            "4:22: error: function 'f' ends with 'g'",
+            "5:14: error: undeclared identifier 'b'",
+            "5:21: error: program 'p' ends with 'q'",
+        ]);
+
+        let boolean = model.lookup(ScopeId::Global, "true").unwrap();
+        assert_eq!(model.symbol(boolean).span, None);
+        assert_eq!(model.symbol(boolean).detail, "true : boolean");
+    }
+
+    #[test]
+    fn test_case_insensitive_resolution() {
+        let config = LexerConfig { case_insensitive: true, ..LexerConfig::default() };
+        let source = "PROGRAM p: VAR Total : INTEGER; BEGIN total := 1; output(TOTAL) END P.";
+        let mut lexer = Lexer::with_config(source.to_string(), config);
+        let model = SemanticModel::build(&Parser::with_spans(lexer.tokenize(), false).parse().unwrap());
+        assert!(model.diagnostics().is_empty());
+        let total = model.lookup(ScopeId::Global, "total").unwrap();
+        assert_eq!(model.symbol(total).name, "Total");
+        assert_eq!(model.references_to(total).count(), 3);
+    }
+
+    #[test]
+    fn test_constant_values() {
+        let source = "program p:\n\
+                      const low = 1, high = low, letter = 'a';\n\
+                      type color = (red, green, blue);\n\
+                      var n : integer;\n\
+                      function f(m : integer) : integer;\n\
+                      const first = green, last = first, wrong = n;\n\
+                      begin return (high) end f;\n\
+                      begin end p.";
+        let program = parse(source);
+        let model = SemanticModel::build(&program);
+        let value = |scope, name| model.const_value(model.lookup(scope, name).unwrap());
+
+        assert_eq!(value(ScopeId::Global, "high"), Some(ConstantValue::Integer(1)));
+        assert_eq!(value(ScopeId::Global, "letter"), Some(ConstantValue::Char('a')));
+        let color = model.lookup(ScopeId::Global, "color").unwrap();
+        assert_eq!(value(ScopeId::Global, "blue"), Some(ConstantValue::Literal { ty: color, ordinal: 2 }));
+        assert_eq!(value(ScopeId::Global, "true"), Some(ConstantValue::Literal { ty: BOOLEAN, ordinal: 1 }));
+        assert_eq!(value(ScopeId::Function(0), "last"), Some(ConstantValue::Literal { ty: color, ordinal: 1 }));
+        assert_eq!(value(ScopeId::Function(0), "wrong"), None);
+        assert_eq!(value(ScopeId::Global, "n"), None);
+        assert_eq!(model.value_text(value(ScopeId::Function(0), "last").unwrap()), "green");
+
+        let high = &program.consts.consts[1].name;
+        assert_eq!(model.const_value_of(high), Some(ConstantValue::Integer(1)));
+        let messages: Vec<String> = model.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, ["6:44: error: 'n' is not a constant"]);
+
+        // a constant can't be defined as itself, which isn't declared until its definition ends
+        let model = SemanticModel::build(&parse("program p: const a = a; begin end p."));
+        assert_eq!(model.const_value(model.lookup(ScopeId::Global, "a").unwrap()), None);
+        let messages: Vec<String> = model.diagnostics().iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, ["1:22: error: undeclared identifier 'a'"]);
+    }
+}